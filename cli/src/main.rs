@@ -8,38 +8,169 @@ extern crate libhyperchain;
 extern crate clap;
 extern crate base_62;
 extern crate pretty_env_logger;
+extern crate dirs;
 
 use libhyperchain::service::command::{Command, Response};
 use libhyperchain::service::client::Client;
 use libhyperchain::wallet::Wallet;
 use libhyperchain::wallet::private_wallet::PrivateWallet;
+use libhyperchain::wallet::public_wallet::PublicWallet;
+use libhyperchain::wallet::address::{encode_address, decode_address};
+use libhyperchain::wallet::address_book::AddressBook;
+use libhyperchain::wallet::watch_wallet::WatchWallet;
+use libhyperchain::wallet::message::{MessageSignature, sign_message, verify_message};
+use libhyperchain::wallet::public_wallet::WalletValidationResult;
+use libhyperchain::hash::Hash;
+use libhyperchain::config::NAME_REGISTRATION_FEE;
 use libhyperchain::transaction::TransactionVariant;
+use libhyperchain::transaction::transfer::Transfer;
+use libhyperchain::transaction::unsigned::UnsignedTransaction;
+use libhyperchain::data_store::data_unit::DataUnit;
+use libhyperchain::data_store::page::{SiteBundleData, guess_content_type};
+use libhyperchain::data_store::page::{CreatePageData, DeletePageData, RenamePageData, PatchPageData};
 use clap::{App, Arg, SubCommand, ArgMatches};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs::File;
 use std::io::Read;
 use std::error::Error;
 
+fn address_book_path() -> PathBuf
+{
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("hyperchain")
+        .join("address_book.json")
+}
+
+/// Find the address for a label in the address book, an encoded address,
+/// or a private, public or watch only wallet file.
+fn resolve_address(address_book: &AddressBook, wallet: &str) -> Option<Hash>
+{
+    if let Some(entry) = address_book.get(wallet) {
+        return Some(entry.address);
+    }
+
+    let address_or_error = decode_address(wallet);
+    if address_or_error.is_ok() {
+        return Some(address_or_error.unwrap());
+    }
+
+    let path = PathBuf::from(wallet);
+    if !path.is_file()
+    {
+        println!("{}", address_or_error.unwrap_err());
+        return None;
+    }
+
+    if let Ok(private_wallet) = PrivateWallet::read_from_file(&path) {
+        return Some(private_wallet.get_address());
+    }
+    if let Ok(watch_wallet) = WatchWallet::read_from_file(&path) {
+        return Some(watch_wallet.get_address());
+    }
+    if let Ok(public_wallet) = PublicWallet::read_from_file(&path) {
+        return Some(public_wallet.get_address());
+    }
+
+    println!("Error: Unable to open wallet");
+    None
+}
+
+fn get_balance(client: &mut Client, address: &Hash) -> Result<Option<f32>, Box<dyn Error>>
+{
+    match client.send(Command::Balance(address.data().to_vec()))?
+    {
+        Response::WalletStatus(status) => Ok(Some(status.balance)),
+        Response::TimedOut =>
+        {
+            println!("Error: Timed out waiting for other nodes");
+            Ok(None)
+        },
+        _ => Ok(None),
+    }
+}
+
+fn balance_all(mut client: Client, address_book: &AddressBook) -> Result<(), Box<dyn Error>>
+{
+    let mut total = 0.0;
+    for (label, entry) in address_book.watched()
+    {
+        let balance_or_none = get_balance(&mut client, &entry.address)?;
+        if balance_or_none.is_none()
+        {
+            println!("Error: Unable to get balance for '{}'", label);
+            continue;
+        }
+
+        let balance = balance_or_none.unwrap();
+        println!("{}: {} ({})", label, balance, encode_address(&entry.address));
+        total += balance;
+    }
+
+    println!("Total: {}", total);
+    Ok(())
+}
+
 fn balance(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
 {
-    let wallet_path = options.value_of("wallet").unwrap();
-    let wallet_or_error = PrivateWallet::read_from_file(&PathBuf::from(wallet_path));
-    if wallet_or_error.is_err() 
+    let address_book = AddressBook::open(&address_book_path())?;
+    if options.is_present("all") {
+        return balance_all(client, &address_book);
+    }
+
+    let wallet = options.value_of("wallet").unwrap();
+    let address_or_none = resolve_address(&address_book, wallet);
+    if address_or_none.is_none() {
+        return Ok(());
+    }
+
+    let address = address_or_none.unwrap();
+    if let Some(balance) = get_balance(&mut client, &address)?
     {
-        println!("Error: Unable to open wallet");
+        println!("Address: {}", encode_address(&address));
+        println!("Balance: {}", balance)
+    }
+    Ok(())
+}
+
+fn history(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let address_book = AddressBook::open(&address_book_path())?;
+    let wallet = options.value_of("wallet").unwrap();
+    let address_or_none = resolve_address(&address_book, wallet);
+    if address_or_none.is_none() {
         return Ok(());
     }
 
-    let wallet = wallet_or_error.unwrap();
-    let address = wallet.get_address().data().to_vec();
-    match client.send(Command::Balance(address))?
+    let address = address_or_none.unwrap();
+    match client.send(Command::TransactionHistory(address.data().to_vec()))?
     {
-        Response::WalletStatus(status) =>
+        Response::TransactionHistory(history) =>
         {
-            println!("Address: {}", wallet.get_address());
-            println!("Balance: {}", status.balance)
+            println!("Address: {}", encode_address(&address));
+            for (transaction, block) in history
+            {
+                let block_id =
+                    match block
+                    {
+                        Some(block) => block.header.block_id.to_string(),
+                        None => "Pending".to_owned(),
+                    };
+
+                match transaction
+                {
+                    TransactionVariant::Transfer(transfer) =>
+                        println!("Transfer {} in block {}", transfer.hash()?, block_id),
+                    TransactionVariant::Page(page) =>
+                        println!("Page {} in block {}", page.hash()?, block_id),
+                    TransactionVariant::SiteTransfer(site_transfer) =>
+                        println!("Site transfer {} in block {}", site_transfer.hash()?, block_id),
+                    TransactionVariant::NameRegistration(name) =>
+                        println!("Name registration {} in block {}", name.hash()?, block_id),
+                }
+            }
         },
-        _ => {},
+        _ => println!("Error"),
     }
     Ok(())
 }
@@ -72,11 +203,29 @@ fn parse_outputs<'a, I>(to_addresses: I, amounts: &mut I)
     -> Result<Option<Vec<(Vec<u8>, f32)>>, Box<dyn Error>>
     where I: Iterator<Item = &'a str>
 {
+    let address_book = AddressBook::open(&address_book_path())?;
+
     let mut outputs = Vec::new();
     for to_address_str in to_addresses
     {
         let amount_str = amounts.nth(0).unwrap();
-        let to = base_62::decode(to_address_str)?;
+        let to =
+            match address_book.get(to_address_str)
+            {
+                Some(entry) => entry.address.data().to_vec(),
+                None =>
+                {
+                    let to_or_error = decode_address(to_address_str);
+                    if to_or_error.is_err()
+                    {
+                        println!("{}", to_or_error.unwrap_err());
+                        return Ok(None);
+                    }
+
+                    to_or_error.unwrap().data().to_vec()
+                },
+            };
+
         let amount = amount_str.parse::<f32>()?;
         outputs.push((to, amount));
     }
@@ -117,7 +266,418 @@ fn send(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
     Ok(())
 }
 
+fn tx_create(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let from_paths = options.values_of("from").unwrap();
+    let to_addresses = options.values_of("to").unwrap();
+    let mut amounts = options.values_of("amount").unwrap();
+    if from_paths.len() + to_addresses.len() != amounts.len()
+    {
+        println!("Error: Number of from and amounts arguments did not match");
+        return Ok(());
+    }
+
+    let mut inputs = Vec::new();
+    for from_path in from_paths
+    {
+        let amount_str = amounts.nth(0).unwrap();
+
+        let from_or_error = PublicWallet::read_from_file(&PathBuf::from(from_path));
+        if from_or_error.is_err()
+        {
+            println!("Error: Unable to open public wallet");
+            return Ok(());
+        }
+
+        let amount = amount_str.parse::<f32>()?;
+        inputs.push((from_or_error.unwrap(), amount));
+    }
+
+    let outputs_or_none = parse_outputs(to_addresses, &mut amounts)?;
+    if outputs_or_none.is_none() {
+        return Ok(());
+    }
+
+    let outputs = outputs_or_none.unwrap();
+    let fee = options.value_of("fee").unwrap().parse::<f32>()?;
+    let output = options.value_of("output").unwrap();
+    match client.send(Command::CreateTransfer(inputs, outputs, fee))?
+    {
+        Response::UnsignedTransfer(unsigned) =>
+        {
+            unsigned.write_to_file(&PathBuf::from(output))?;
+            println!("Unsigned transaction written to {}", output);
+        },
+        _ => println!("Error"),
+    }
+    Ok(())
+}
+
+fn tx_sign(options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let wallet_path = options.value_of("wallet").unwrap();
+    let wallet_or_error = PrivateWallet::read_from_file(&PathBuf::from(wallet_path));
+    if wallet_or_error.is_err()
+    {
+        println!("Error: Unable to open wallet");
+        return Ok(());
+    }
+
+    let input = options.value_of("input").unwrap();
+    let output = options.value_of("output").unwrap_or(input);
+    let mut unsigned = UnsignedTransaction::<Transfer>::read_from_file(&PathBuf::from(input))?;
+
+    // Show what's being signed, so it can be checked on the offline machine
+    println!("Transfer:");
+    for input in &unsigned.header.inputs
+    {
+        println!("From: {}", encode_address(&input.get_address()));
+        println!("Amount: {}", input.amount);
+    }
+    for output in &unsigned.header.content.outputs
+    {
+        println!("To: {}", encode_address(&output.to));
+        println!("Amount: {}", output.amount);
+    }
+    println!("Fee: {}", unsigned.header.content.fee);
+
+    let wallet = wallet_or_error.unwrap();
+    let result = unsigned.sign(&wallet);
+    if result.is_err()
+    {
+        println!("Error: {}", result.unwrap_err());
+        return Ok(());
+    }
+
+    unsigned.write_to_file(&PathBuf::from(output))?;
+    let missing = unsigned.missing_signatures();
+    if missing.is_empty() {
+        println!("Fully signed, written to {}", output);
+    } else {
+        println!("Signed, still waiting on {} signature(s)", missing.len());
+    }
+    Ok(())
+}
+
+fn tx_submit(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let input = options.value_of("input").unwrap();
+    let unsigned = UnsignedTransaction::<Transfer>::read_from_file(&PathBuf::from(input))?;
+    let transfer_or_error = unsigned.into_transaction();
+    if transfer_or_error.is_err()
+    {
+        println!("Error: {}", transfer_or_error.unwrap_err());
+        return Ok(());
+    }
+
+    match client.send(Command::SubmitTransfer(transfer_or_error.unwrap()))?
+    {
+        Response::Sent(id) =>
+            println!("Success, TxID: {}", base_62::encode(&id)),
+        _ => println!("Error"),
+    }
+    Ok(())
+}
+
+fn tx(client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    match options.subcommand_name()
+    {
+        Some("create") => tx_create(client, options.subcommand().1.unwrap()),
+        Some("submit") => tx_submit(client, options.subcommand().1.unwrap()),
+        Some(&_) | None =>
+        {
+            println!("Error: Must specify a tx action");
+            Ok(())
+        },
+    }
+}
+
+/// Returns the raw site address given with '--site', if any. The outer 
+/// option is none if it couldn't be resolved.
+fn parse_site(options: &ArgMatches) -> Result<Option<Option<Vec<u8>>>, Box<dyn Error>>
+{
+    match options.value_of("site")
+    {
+        Some(site) =>
+        {
+            let address_book = AddressBook::open(&address_book_path())?;
+            let site_or_none = resolve_address(&address_book, site);
+            if site_or_none.is_none() {
+                return Ok(None);
+            }
+            Ok(Some(Some(site_or_none.unwrap().data().to_vec())))
+        },
+        None => Ok(Some(None)),
+    }
+}
+
+fn add_dir_to_bundle(bundle: &mut SiteBundleData, root: &Path, dir: &Path) 
+    -> Result<(), Box<dyn Error>>
+{
+    for entry_or_error in std::fs::read_dir(dir)?
+    {
+        let path = entry_or_error?.path();
+        if path.is_dir()
+        {
+            add_dir_to_bundle(bundle, root, &path)?;
+            continue;
+        }
+
+        let relative_path = path.strip_prefix(root)?
+            .components()
+            .map(|x| x.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let mut data = Vec::new();
+        File::open(&path)?.read_to_end(&mut data)?;
+
+        let content_type = guess_content_type(&relative_path);
+        println!("Adding {} ({} bytes)", relative_path, data.len());
+        bundle.add_file(relative_path, content_type, data);
+    }
+
+    Ok(())
+}
+
+fn publish_dir(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let dir = PathBuf::from(options.value_of("path").unwrap());
+    if !dir.is_dir()
+    {
+        println!("Error: '{}' is not a directory", dir.display());
+        return Ok(());
+    }
+
+    let mut bundle = SiteBundleData::new();
+    add_dir_to_bundle(&mut bundle, &dir, &dir)?;
+    publish_data(&mut client, options, DataUnit::SiteBundle(bundle))
+}
+
+/// Sends a data unit to the site given with '--site', or the '--from' 
+/// wallets own site.
+fn publish_data(client: &mut Client, options: &ArgMatches, data_unit: DataUnit)
+    -> Result<(), Box<dyn Error>>
+{
+    let from_path = options.value_of("from").unwrap();
+    let from_or_error = PrivateWallet::read_from_file(&PathBuf::from(from_path));
+    if from_or_error.is_err() 
+    {
+        println!("Error: Unable to open wallet");
+        return Ok(());
+    }
+
+    let site_or_none = parse_site(options)?;
+    if site_or_none.is_none() {
+        return Ok(());
+    }
+
+    let from = from_or_error.unwrap();
+    let site = site_or_none.unwrap();
+    match client.send(Command::PublishData(from.serialize(), site, data_unit))?
+    {
+        Response::Sent(id) => 
+            println!("Success, TxID: {}", base_62::encode(&id)),
+        _ => println!("Error"),
+    }
+    Ok(())
+}
+
+fn delete_page(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let name = options.value_of("name").unwrap().to_owned();
+    publish_data(&mut client, options, DataUnit::DeletePage(DeletePageData { name }))
+}
+
+fn rename_page(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let from = options.value_of("name").unwrap().to_owned();
+    let to = options.value_of("to").unwrap().to_owned();
+    publish_data(&mut client, options, DataUnit::RenamePage(RenamePageData { from, to }))
+}
+
+fn patch_page(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let from_path = options.value_of("from").unwrap();
+    let from_or_error = PrivateWallet::read_from_file(&PathBuf::from(from_path));
+    if from_or_error.is_err() 
+    {
+        println!("Error: Unable to open wallet");
+        return Ok(());
+    }
+
+    let site_or_none = parse_site(options)?;
+    if site_or_none.is_none() {
+        return Ok(());
+    }
+
+    let site = match site_or_none.unwrap()
+    {
+        Some(site) => Hash::from(&site),
+        None => from_or_error.unwrap().get_address(),
+    };
+
+    let name = options.value_of("name").unwrap().to_owned();
+    let base = match client.send(Command::SiteFile(site.data().to_vec(), name.clone()))?
+    {
+        Response::SiteFile(data, _, _) => data,
+        _ =>
+        {
+            println!("Error: No page '{}' to patch, use update-page instead", name);
+            return Ok(());
+        },
+    };
+
+    let page_path = options.value_of("page").unwrap();
+    let mut page = Vec::new();
+    File::open(page_path)?.read_to_end(&mut page)?;
+
+    let patch = PatchPageData::diff(name, &base, &page);
+    publish_data(&mut client, options, DataUnit::PatchPage(patch))
+}
+
 fn update_page(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let name = options.value_of("name").unwrap().to_owned();
+    let content_type = options.value_of("content-type").map(str::to_owned);
+    
+    let page_path = options.value_of("page").unwrap();
+    let mut page = Vec::new();
+    File::open(page_path)?.read_to_end(&mut page)?;
+
+    let create_page = CreatePageData::new(name, page)
+        .with_content_type(content_type);
+    publish_data(&mut client, options, DataUnit::CreatePage(create_page))
+}
+
+fn rotate_key(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let from_path = options.value_of("from").unwrap();
+    let from_or_error = PrivateWallet::read_from_file(&PathBuf::from(from_path));
+    if from_or_error.is_err() 
+    {
+        println!("Error: Unable to open wallet");
+        return Ok(());
+    }
+
+    let address_book = AddressBook::open(&address_book_path())?;
+    let to_or_none = resolve_address(&address_book, options.value_of("to").unwrap());
+    if to_or_none.is_none() {
+        return Ok(());
+    }
+
+    let from = from_or_error.unwrap();
+    let to = to_or_none.unwrap();
+    let fee = options.value_of("fee").unwrap().parse::<f32>()?;
+    if to == from.get_address()
+    {
+        println!("Error: New key must be different from the old one");
+        return Ok(());
+    }
+
+    // Move sites over first, as they're paid for out of the old balance
+    let sites =
+        match client.send(Command::SitesOwned(from.get_address().data().to_vec()))?
+        {
+            Response::Sites(sites) => sites,
+            _ => 
+            {
+                println!("Error: Unable to find sites owned");
+                return Ok(());
+            },
+        };
+
+    for site in &sites
+    {
+        let command = Command::TransferSite(from.serialize(), 
+            site.data().to_vec(), to.data().to_vec(), fee);
+
+        match client.send(command)?
+        {
+            Response::Sent(id) => 
+                println!("Site {} moved, TxID: {}", encode_address(site), base_62::encode(&id)),
+            _ => 
+            {
+                println!("Error: Unable to move site {}", encode_address(site));
+                return Ok(());
+            },
+        }
+    }
+
+    let balance =
+        match client.send(Command::Balance(from.get_address().data().to_vec()))?
+        {
+            Response::WalletStatus(status) => status.balance,
+            _ => 0.0,
+        };
+
+    let amount = balance - fee * sites.len() as f32 - fee;
+    if amount <= 0.0
+    {
+        println!("Nothing left to sweep");
+        return Ok(());
+    }
+
+    let inputs = vec![(from.serialize(), amount + fee)];
+    let outputs = vec![(to.data().to_vec(), amount)];
+    match client.send(Command::Send(inputs, outputs, fee))?
+    {
+        Response::Sent(id) => 
+            println!("Swept {} to {}, TxID: {}", amount, encode_address(&to), base_62::encode(&id)),
+        _ => println!("Error: Unable to sweep balance"),
+    }
+    Ok(())
+}
+
+fn send_name_registration(client: &mut Client, from: &PrivateWallet, name: &str,
+                          site: &Hash, owner: &Hash, fee: f32)
+    -> Result<(), Box<dyn Error>>
+{
+    let command = Command::RegisterName(from.serialize(), name.to_owned(), 
+        site.data().to_vec(), owner.data().to_vec(), fee);
+
+    match client.send(command)?
+    {
+        Response::Sent(id) => 
+            println!("Success, TxID: {}", base_62::encode(&id)),
+        _ => println!("Error: Unable to register '{}', it may be owned by someone else", name),
+    }
+    Ok(())
+}
+
+fn register_name(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let from_path = options.value_of("from").unwrap();
+    let from_or_error = PrivateWallet::read_from_file(&PathBuf::from(from_path));
+    if from_or_error.is_err() 
+    {
+        println!("Error: Unable to open wallet");
+        return Ok(());
+    }
+
+    let from = from_or_error.unwrap();
+    let address_book = AddressBook::open(&address_book_path())?;
+    let site = match options.value_of("site")
+    {
+        Some(site) => resolve_address(&address_book, site),
+        None => Some(from.get_address()),
+    };
+    let owner = match options.value_of("owner")
+    {
+        Some(owner) => resolve_address(&address_book, owner),
+        None => Some(from.get_address()),
+    };
+    if site.is_none() || owner.is_none() {
+        return Ok(());
+    }
+
+    let name = options.value_of("name").unwrap();
+    let fee = options.value_of("fee").unwrap().parse::<f32>()?;
+    send_name_registration(&mut client, &from, name, &site.unwrap(), &owner.unwrap(), fee)
+}
+
+fn transfer_name(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
 {
     let from_path = options.value_of("from").unwrap();
     let from_or_error = PrivateWallet::read_from_file(&PathBuf::from(from_path));
@@ -127,82 +687,353 @@ fn update_page(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn E
         return Ok(());
     }
 
-    let from = from_or_error.unwrap();
-    let name = options.value_of("name").unwrap().to_owned();
-    
-    let page_path = options.value_of("page").unwrap();
-    let mut page = Vec::new();
-    File::open(&page_path)?.read_to_end(&mut page)?;
+    let address_book = AddressBook::open(&address_book_path())?;
+    let to_or_none = resolve_address(&address_book, options.value_of("to").unwrap());
+    if to_or_none.is_none() {
+        return Ok(());
+    }
+
+    // The name keeps pointing at the same site, only its owner changes
+    let name = options.value_of("name").unwrap();
+    let record = match client.send(Command::ResolveName(name.to_owned()))?
+    {
+        Response::Name(record) => record,
+        _ =>
+        {
+            println!("Error: '{}' is not registered", name);
+            return Ok(());
+        },
+    };
+
+    let from = from_or_error.unwrap();
+    let fee = options.value_of("fee").unwrap().parse::<f32>()?;
+    send_name_registration(&mut client, &from, name, &record.site, &to_or_none.unwrap(), fee)
+}
+
+fn resolve_name(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let name = options.value_of("name").unwrap();
+    match client.send(Command::ResolveName(name.to_owned()))?
+    {
+        Response::Name(record) =>
+        {
+            println!("Site: {}", encode_address(&record.site));
+            println!("Owner: {}", encode_address(&record.owner));
+            println!("Expires: block {}", record.expires);
+        },
+        _ => println!("'{}' is not registered", name),
+    }
+    Ok(())
+}
+
+fn transaction_info(mut client: Client, options: &ArgMatches) 
+    -> Result<(), Box<dyn Error>>
+{
+    let id = base_62::decode(options.value_of("id").unwrap())?;
+
+    match client.send(Command::TransactionInfo(id))?
+    {
+        Response::TransactionInfo(transaction, block) => 
+        {
+            match transaction
+            {
+                TransactionVariant::Transfer(transfer) =>
+                {
+                    println!("Transfer:");
+                    for input in &transfer.header.inputs 
+                    {
+                        println!("From: {}", encode_address(&input.get_address()));
+                        println!("Amount: {}", input.amount);
+                    }
+                    for output in &transfer.header.content.outputs
+                    {
+                        println!("To: {}", encode_address(&output.to));
+                        println!("Amount: {}", output.amount);
+                    }
+                    println!("Fee: {}", transfer.header.content.fee);
+                },
+
+                TransactionVariant::Page(page) =>
+                {
+                    println!("Page:");
+                    for input in &page.header.inputs 
+                    {
+                        println!("From: {}", encode_address(&input.get_address()));
+                        println!("Amount: {}", input.amount);
+                    }
+                    println!("Site: {}", encode_address(&page.header.content.site));
+                    println!("Length: {} bytes", page.header.content.data_length);
+                    println!("New Data: {} bytes", page.header.content.new_data_length);
+                    println!("Chunks: {}", page.header.content.chunk_count);
+                    println!("Chunk Root: {}", page.header.content.chunk_root);
+                    println!("Fee: {}", page.header.content.fee);
+                },
+
+                TransactionVariant::SiteTransfer(site_transfer) =>
+                {
+                    println!("Site Transfer:");
+                    for input in &site_transfer.header.inputs 
+                    {
+                        println!("From: {}", encode_address(&input.get_address()));
+                        println!("Amount: {}", input.amount);
+                    }
+                    println!("Site: {}", encode_address(&site_transfer.header.content.site));
+                    println!("New Owner: {}", encode_address(&site_transfer.header.content.new_owner));
+                    println!("Fee: {}", site_transfer.header.content.fee);
+                },
+
+                TransactionVariant::NameRegistration(name) =>
+                {
+                    println!("Name Registration:");
+                    for input in &name.header.inputs 
+                    {
+                        println!("From: {}", encode_address(&input.get_address()));
+                        println!("Amount: {}", input.amount);
+                    }
+                    println!("Name: {}", name.header.content.name);
+                    println!("Site: {}", encode_address(&name.header.content.site));
+                    println!("Owner: {}", encode_address(&name.header.content.owner));
+                    println!("Fee: {}", name.header.content.fee);
+                },
+            }
+            
+            match block
+            {
+                Some(block) => println!("In block: {}", block.header.block_id),
+                None => println!("In block: Pending"),
+            }
+        },
+        _ => println!("Error"),
+    }
+    Ok(())
+}
+
+fn new_wallet(options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let output = options.value_of("output").unwrap();
+
+    println!("Creating new wallet...");
+    let wallet = PrivateWallet::new()?;
+    wallet.write_to_file(&PathBuf::from(output))?;
+    println!("Done!");
+
+    Ok(())
+}
+
+fn export_public(options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let wallet_path = options.value_of("wallet").unwrap();
+    let wallet_or_error = PrivateWallet::read_from_file(&PathBuf::from(wallet_path));
+    if wallet_or_error.is_err()
+    {
+        println!("Error: Unable to open wallet");
+        return Ok(());
+    }
+
+    let output = options.value_of("output").unwrap();
+    wallet_or_error.unwrap().as_public().write_to_file(&PathBuf::from(output))?;
+    println!("Public wallet written to {}", output);
+    Ok(())
+}
+
+fn watch_wallet(options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let output = options.value_of("output").unwrap();
+    let watch_wallet =
+        if let Some(public_path) = options.value_of("public")
+        {
+            let public_or_error = PublicWallet::read_from_file(&PathBuf::from(public_path));
+            if public_or_error.is_err()
+            {
+                println!("Error: Unable to open public wallet");
+                return Ok(());
+            }
+
+            WatchWallet::from_public(public_or_error.unwrap())
+        }
+        else
+        {
+            let address_or_error = decode_address(options.value_of("address").unwrap());
+            if address_or_error.is_err()
+            {
+                println!("{}", address_or_error.unwrap_err());
+                return Ok(());
+            }
+
+            WatchWallet::from_address(address_or_error.unwrap())
+        };
+
+    watch_wallet.write_to_file(&PathBuf::from(output))?;
+    println!("Watch only wallet for {} written to {}",
+        encode_address(&watch_wallet.get_address()), output);
+    Ok(())
+}
+
+fn address_book(options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let mut address_book = AddressBook::open(&address_book_path())?;
+    match options.subcommand()
+    {
+        ("add", Some(add_options)) =>
+        {
+            let label = add_options.value_of("label").unwrap();
+            let wallet = add_options.value_of("address").unwrap();
+            let address_or_none = resolve_address(&address_book, wallet);
+            if address_or_none.is_none() {
+                return Ok(());
+            }
+
+            address_book.add(label, address_or_none.unwrap(), add_options.is_present("watch"))?;
+            address_book.save()?;
+            println!("Added '{}'", label);
+        },
+
+        ("remove", Some(remove_options)) =>
+        {
+            let label = remove_options.value_of("label").unwrap();
+            if address_book.remove(label).is_none()
+            {
+                println!("Error: No entry labelled '{}'", label);
+                return Ok(());
+            }
+
+            address_book.save()?;
+            println!("Removed '{}'", label);
+        },
+
+        ("list", _) =>
+        {
+            for (label, entry) in address_book.entries()
+            {
+                let watch = if entry.watch { " (watched)" } else { "" };
+                println!("{}: {}{}", label, encode_address(&entry.address), watch);
+            }
+        },
+
+        _ => println!("Error: Must specify an address book action"),
+    }
+    Ok(())
+}
+
+fn sign_message_command(options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let wallet_path = options.value_of("wallet").unwrap();
+    let wallet_or_error = PrivateWallet::read_from_file(&PathBuf::from(wallet_path));
+    if wallet_or_error.is_err()
+    {
+        println!("Error: Unable to open wallet");
+        return Ok(());
+    }
+
+    let wallet = wallet_or_error.unwrap();
+    let message = options.value_of("message").unwrap();
+    let signature = sign_message(&wallet, message)?;
+    println!("Address: {}", wallet.get_address_string());
+    println!("Signature: {}", signature.encode()?);
+    Ok(())
+}
+
+fn verify_message_command(options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let address_book = AddressBook::open(&address_book_path())?;
+    let address_or_none = resolve_address(&address_book, options.value_of("address").unwrap());
+    if address_or_none.is_none() {
+        return Ok(());
+    }
+
+    let signature_or_error = MessageSignature::decode(options.value_of("signature").unwrap());
+    if signature_or_error.is_err()
+    {
+        println!("{}", signature_or_error.unwrap_err());
+        return Ok(());
+    }
 
-    match client.send(Command::UpdatePage(from.serialize(), name, page))?
+    let address = address_or_none.unwrap();
+    let message = options.value_of("message").unwrap();
+    match verify_message(&address, message, &signature_or_error.unwrap())
     {
-        Response::Sent(id) => 
-            println!("Success, TxID: {}", base_62::encode(&id)),
-        _ => println!("Error"),
+        Ok(WalletValidationResult::Ok) =>
+            println!("Valid, signed by {}", encode_address(&address)),
+        Ok(result) => println!("Invalid: {}", result),
+        Err(err) => println!("Invalid: {}", err),
     }
     Ok(())
 }
 
-fn transaction_info(mut client: Client, options: &ArgMatches) 
-    -> Result<(), Box<dyn Error>>
+/// The service may not be running from the same directory, so it's given
+/// the full path.
+fn snapshot_path(options: &ArgMatches) -> Result<String, Box<dyn Error>>
 {
-    let id = base_62::decode(options.value_of("id").unwrap())?;
+    let path = std::env::current_dir()?.join(options.value_of("file").unwrap());
+    Ok(path.to_string_lossy().into_owned())
+}
 
-    match client.send(Command::TransactionInfo(id))?
+fn export_snapshot(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let path = snapshot_path(options)?;
+    match client.send(Command::ExportSnapshot(path.clone()))?
     {
-        Response::TransactionInfo(transaction, block) => 
+        Response::Snapshot(block_id, commitment) =>
         {
-            match transaction
-            {
-                TransactionVariant::Transfer(transfer) =>
-                {
-                    println!("Transfer:");
-                    for input in &transfer.header.inputs 
-                    {
-                        println!("From: {}", input.get_address());
-                        println!("Amount: {}", input.amount);
-                    }
-                    for output in &transfer.header.content.outputs
-                    {
-                        println!("To: {}", output.to);
-                        println!("Amount: {}", output.amount);
-                    }
-                    println!("Fee: {}", transfer.header.content.fee);
-                },
+            println!("Exported snapshot to {}", path);
+            println!("Block: {}", block_id);
+            println!("Commitment: {}", commitment);
+        },
+        _ => println!("Error: Could not export snapshot"),
+    }
+    Ok(())
+}
 
-                TransactionVariant::Page(page) =>
-                {
-                    println!("Page:");
-                    for input in &page.header.inputs 
-                    {
-                        println!("From: {}", input.get_address());
-                        println!("Amount: {}", input.amount);
-                    }
-                    println!("Length: {} bytes", page.header.content.data_length);
-                    println!("Fee: {}", page.header.content.fee);
-                },
-            }
-            
-            match block
-            {
-                Some(block) => println!("In block: {}", block.header.block_id),
-                None => println!("In block: Pending"),
-            }
+fn verify_snapshot(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let path = snapshot_path(options)?;
+    match client.send(Command::VerifySnapshot(path))?
+    {
+        Response::Snapshot(block_id, commitment) =>
+        {
+            println!("Snapshot matches the chain");
+            println!("Block: {}", block_id);
+            println!("Commitment: {}", commitment);
         },
-        _ => println!("Error"),
+        _ => println!("Snapshot does not match the chain"),
     }
     Ok(())
 }
 
-fn new_wallet(options: &ArgMatches) -> Result<(), Box<dyn Error>>
+fn print_bans(bans: Vec<(String, u128)>)
 {
-    let output = options.value_of("output").unwrap();
+    if bans.is_empty()
+    {
+        println!("No nodes are banned");
+        return;
+    }
 
-    println!("Creating new wallet...");
-    let wallet = PrivateWallet::new()?;
-    wallet.write_to_file(&PathBuf::from(output))?;
-    println!("Done!");
+    let now = libhyperchain::block::current_timestamp();
+    for (address, until) in bans
+    {
+        let minutes_left = until.saturating_sub(now) / (60 * 1000);
+        println!("{} (for {} more minute(s))", address, minutes_left);
+    }
+}
+
+fn bans(mut client: Client) -> Result<(), Box<dyn Error>>
+{
+    match client.send(Command::Bans)?
+    {
+        Response::Bans(bans) => print_bans(bans),
+        _ => println!("Error: Could not get banned nodes"),
+    }
+    Ok(())
+}
 
+fn unban(mut client: Client, options: &ArgMatches) -> Result<(), Box<dyn Error>>
+{
+    let address = options.value_of("address").map(str::to_owned);
+    match client.send(Command::Unban(address))?
+    {
+        Response::Bans(bans) => print_bans(bans),
+        _ => println!("Error: Could not lift ban"),
+    }
     Ok(())
 }
 
@@ -220,6 +1051,7 @@ fn main() -> Result<(), Box<dyn Error>>
 {
     pretty_env_logger::init();
 
+    let name_fee = NAME_REGISTRATION_FEE.to_string();
     let matches = App::new("Hyperchain Cli")
         .version("0.2.0")
         .author("Ben Jilks <benjyjilks@gmail.com>")
@@ -227,12 +1059,25 @@ fn main() -> Result<(), Box<dyn Error>>
 
         .subcommand(SubCommand::with_name("balance")
             .about("Display a wallets balance")
+            .arg(Arg::with_name("wallet")
+                .short("w")
+                .long("wallet")
+                .takes_value(true)
+                .required_unless("all")
+                .help("Path to wallet file, address or address book label"))
+            .arg(Arg::with_name("all")
+                .long("all")
+                .conflicts_with("wallet")
+                .help("Summarise the balance of every watched address")))
+
+        .subcommand(SubCommand::with_name("history")
+            .about("Display a wallets transaction history")
             .arg(Arg::with_name("wallet")
                 .short("w")
                 .long("wallet")
                 .takes_value(true)
                 .required(true)
-                .help("Path to wallet file")))
+                .help("Path to wallet file, address or address book label")))
 
         .subcommand(SubCommand::with_name("send")
             .about("Sent coins to someone")
@@ -256,7 +1101,7 @@ fn main() -> Result<(), Box<dyn Error>>
                 .takes_value(true)
                 .required(true)
                 .multiple(true)
-                .help("Address of recipient"))
+                .help("Address or address book label of recipient"))
             .arg(Arg::with_name("fee")
                 .short("e")
                 .long("fee")
@@ -264,6 +1109,71 @@ fn main() -> Result<(), Box<dyn Error>>
                 .required(true)
                 .help("Transaction fee")))
         
+        .subcommand(SubCommand::with_name("tx")
+            .about("Create, sign and submit transactions offline")
+            .subcommand(SubCommand::with_name("create")
+                .about("Create an unsigned transfer file")
+                .arg(Arg::with_name("from")
+                    .short("f")
+                    .long("from")
+                    .takes_value(true)
+                    .required(true)
+                    .multiple(true)
+                    .help("Path to from public wallet file"))
+                .arg(Arg::with_name("amount")
+                    .short("a")
+                    .long("amount")
+                    .takes_value(true)
+                    .required(true)
+                    .multiple(true)
+                    .help("Amount to send"))
+                .arg(Arg::with_name("to")
+                    .short("t")
+                    .long("to")
+                    .takes_value(true)
+                    .required(true)
+                    .multiple(true)
+                    .help("Address of recipient"))
+                .arg(Arg::with_name("fee")
+                    .short("e")
+                    .long("fee")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Transaction fee"))
+                .arg(Arg::with_name("output")
+                    .short("o")
+                    .long("output")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Output path of unsigned transaction")))
+            .subcommand(SubCommand::with_name("sign")
+                .about("Sign a transaction file, without connecting to the service")
+                .arg(Arg::with_name("wallet")
+                    .short("w")
+                    .long("wallet")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Path to wallet file"))
+                .arg(Arg::with_name("input")
+                    .short("i")
+                    .long("input")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Path to transaction file"))
+                .arg(Arg::with_name("output")
+                    .short("o")
+                    .long("output")
+                    .takes_value(true)
+                    .help("Output path of signed transaction, defaults to the input")))
+            .subcommand(SubCommand::with_name("submit")
+                .about("Submit a fully signed transaction file")
+                .arg(Arg::with_name("input")
+                    .short("i")
+                    .long("input")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Path to transaction file"))))
+
         .subcommand(SubCommand::with_name("update-page")
             .about("Update your page")
             .arg(Arg::with_name("from")
@@ -283,7 +1193,185 @@ fn main() -> Result<(), Box<dyn Error>>
                 .long("page")
                 .takes_value(true)
                 .required(true)
-                .help("Path to page file")))
+                .help("Path to page file"))
+            .arg(Arg::with_name("content-type")
+                .short("c")
+                .long("content-type")
+                .takes_value(true)
+                .help("MIME type to serve the page with, if it can't be guessed from its name"))
+            .arg(Arg::with_name("site")
+                .short("s")
+                .long("site")
+                .takes_value(true)
+                .help("Site to update, if it was transferred to this wallet")))
+
+        .subcommand(SubCommand::with_name("publish-dir")
+            .about("Publish a whole directory as your site, in one transaction")
+            .arg(Arg::with_name("path")
+                .required(true)
+                .help("Path to the directory to publish"))
+            .arg(Arg::with_name("from")
+                .short("f")
+                .long("from")
+                .takes_value(true)
+                .required(true)
+                .help("Path to from wallet file"))
+            .arg(Arg::with_name("site")
+                .short("s")
+                .long("site")
+                .takes_value(true)
+                .help("Site to publish to, if it was transferred to this wallet")))
+
+        .subcommand(SubCommand::with_name("delete-page")
+            .about("Remove a page from your site")
+            .arg(Arg::with_name("from")
+                .short("f")
+                .long("from")
+                .takes_value(true)
+                .required(true)
+                .help("Path to from wallet file"))
+            .arg(Arg::with_name("name")
+                .short("n")
+                .long("name")
+                .takes_value(true)
+                .required(true)
+                .help("Page path and file name"))
+            .arg(Arg::with_name("site")
+                .short("s")
+                .long("site")
+                .takes_value(true)
+                .help("Site to update, if it was transferred to this wallet")))
+
+        .subcommand(SubCommand::with_name("rename-page")
+            .about("Move a page to a new path")
+            .arg(Arg::with_name("from")
+                .short("f")
+                .long("from")
+                .takes_value(true)
+                .required(true)
+                .help("Path to from wallet file"))
+            .arg(Arg::with_name("name")
+                .short("n")
+                .long("name")
+                .takes_value(true)
+                .required(true)
+                .help("Page path and file name"))
+            .arg(Arg::with_name("to")
+                .short("t")
+                .long("to")
+                .takes_value(true)
+                .required(true)
+                .help("New page path and file name"))
+            .arg(Arg::with_name("site")
+                .short("s")
+                .long("site")
+                .takes_value(true)
+                .help("Site to update, if it was transferred to this wallet")))
+
+        .subcommand(SubCommand::with_name("patch-page")
+            .about("Update a page by sending only the changes from its current version")
+            .arg(Arg::with_name("from")
+                .short("f")
+                .long("from")
+                .takes_value(true)
+                .required(true)
+                .help("Path to from wallet file"))
+            .arg(Arg::with_name("name")
+                .short("n")
+                .long("name")
+                .takes_value(true)
+                .required(true)
+                .help("Page path and file name"))
+            .arg(Arg::with_name("page")
+                .short("p")
+                .long("page")
+                .takes_value(true)
+                .required(true)
+                .help("Path to the new version of the page"))
+            .arg(Arg::with_name("site")
+                .short("s")
+                .long("site")
+                .takes_value(true)
+                .help("Site to update, if it was transferred to this wallet")))
+
+        .subcommand(SubCommand::with_name("rotate-key")
+            .about("Move a wallets balance and sites to a new key")
+            .arg(Arg::with_name("from")
+                .short("f")
+                .long("from")
+                .takes_value(true)
+                .required(true)
+                .help("Path to old wallet file"))
+            .arg(Arg::with_name("to")
+                .short("t")
+                .long("to")
+                .takes_value(true)
+                .required(true)
+                .help("New wallet file, address or address book label"))
+            .arg(Arg::with_name("fee")
+                .short("e")
+                .long("fee")
+                .takes_value(true)
+                .required(true)
+                .help("Fee for each transaction")))
+        
+        .subcommand(SubCommand::with_name("register-name")
+            .about("Register or renew a name for a site")
+            .arg(Arg::with_name("name")
+                .required(true)
+                .help("Name to register"))
+            .arg(Arg::with_name("from")
+                .short("f")
+                .long("from")
+                .takes_value(true)
+                .required(true)
+                .help("Path to wallet file paying for the name"))
+            .arg(Arg::with_name("site")
+                .short("s")
+                .long("site")
+                .takes_value(true)
+                .help("Site the name points to, defaults to the wallets own"))
+            .arg(Arg::with_name("owner")
+                .short("o")
+                .long("owner")
+                .takes_value(true)
+                .help("Owner of the name, defaults to the paying wallet"))
+            .arg(Arg::with_name("fee")
+                .short("e")
+                .long("fee")
+                .takes_value(true)
+                .default_value(&name_fee)
+                .help("Registration fee")))
+
+        .subcommand(SubCommand::with_name("transfer-name")
+            .about("Hand a name over to a new owner")
+            .arg(Arg::with_name("name")
+                .required(true)
+                .help("Name to transfer"))
+            .arg(Arg::with_name("from")
+                .short("f")
+                .long("from")
+                .takes_value(true)
+                .required(true)
+                .help("Path to the current owners wallet file"))
+            .arg(Arg::with_name("to")
+                .short("t")
+                .long("to")
+                .takes_value(true)
+                .required(true)
+                .help("New owners wallet file, address or address book label"))
+            .arg(Arg::with_name("fee")
+                .short("e")
+                .long("fee")
+                .takes_value(true)
+                .default_value(&name_fee)
+                .help("Registration fee")))
+
+        .subcommand(SubCommand::with_name("resolve-name")
+            .about("Display what a name points to")
+            .arg(Arg::with_name("name")
+                .required(true)
+                .help("Name to look up")))
         
         .subcommand(SubCommand::with_name("transaction-info")
             .about("Display transaction information")
@@ -303,6 +1391,128 @@ fn main() -> Result<(), Box<dyn Error>>
                  .required(true)
                  .help("Output path of new wallet")))
 
+        .subcommand(SubCommand::with_name("export-public")
+            .about("Export the public part of a wallet, for creating transactions offline")
+            .arg(Arg::with_name("wallet")
+                 .short("w")
+                 .long("wallet")
+                 .takes_value(true)
+                 .required(true)
+                 .help("Path to wallet file"))
+            .arg(Arg::with_name("output")
+                 .short("o")
+                 .long("output")
+                 .takes_value(true)
+                 .required(true)
+                 .help("Output path of public wallet")))
+
+        .subcommand(SubCommand::with_name("watch-wallet")
+            .about("Create a watch only wallet, which holds no private key")
+            .arg(Arg::with_name("address")
+                 .short("a")
+                 .long("address")
+                 .takes_value(true)
+                 .required_unless("public")
+                 .help("Address to watch"))
+            .arg(Arg::with_name("public")
+                 .short("p")
+                 .long("public")
+                 .takes_value(true)
+                 .conflicts_with("address")
+                 .help("Path to public wallet file to watch"))
+            .arg(Arg::with_name("output")
+                 .short("o")
+                 .long("output")
+                 .takes_value(true)
+                 .required(true)
+                 .help("Output path of watch only wallet")))
+
+        .subcommand(SubCommand::with_name("address-book")
+            .about("Manage labelled addresses")
+            .subcommand(SubCommand::with_name("add")
+                .about("Add or replace a label")
+                .arg(Arg::with_name("label")
+                     .short("l")
+                     .long("label")
+                     .takes_value(true)
+                     .required(true)
+                     .help("Label for the address"))
+                .arg(Arg::with_name("address")
+                     .short("a")
+                     .long("address")
+                     .takes_value(true)
+                     .required(true)
+                     .help("Address or path to wallet file"))
+                .arg(Arg::with_name("watch")
+                     .long("watch")
+                     .help("Include this address in 'balance --all'")))
+            .subcommand(SubCommand::with_name("remove")
+                .about("Remove a label")
+                .arg(Arg::with_name("label")
+                     .short("l")
+                     .long("label")
+                     .takes_value(true)
+                     .required(true)
+                     .help("Label to remove")))
+            .subcommand(SubCommand::with_name("list")
+                .about("List all labels")))
+
+        .subcommand(SubCommand::with_name("sign-message")
+            .about("Sign a message to prove you own an address")
+            .arg(Arg::with_name("wallet")
+                 .short("w")
+                 .long("wallet")
+                 .takes_value(true)
+                 .required(true)
+                 .help("Path to wallet file"))
+            .arg(Arg::with_name("message")
+                 .short("m")
+                 .long("message")
+                 .takes_value(true)
+                 .required(true)
+                 .help("Message to sign")))
+
+        .subcommand(SubCommand::with_name("verify-message")
+            .about("Verify a signed message")
+            .arg(Arg::with_name("address")
+                 .short("a")
+                 .long("address")
+                 .takes_value(true)
+                 .required(true)
+                 .help("Address or address book label of the signer"))
+            .arg(Arg::with_name("message")
+                 .short("m")
+                 .long("message")
+                 .takes_value(true)
+                 .required(true)
+                 .help("Message that was signed"))
+            .arg(Arg::with_name("signature")
+                 .short("s")
+                 .long("signature")
+                 .takes_value(true)
+                 .required(true)
+                 .help("Signature to verify")))
+
+        .subcommand(SubCommand::with_name("export-snapshot")
+            .about("Save a snapshot of the chain, for new nodes to start from")
+            .arg(Arg::with_name("file")
+                 .required(true)
+                 .help("Path to write the snapshot to")))
+
+        .subcommand(SubCommand::with_name("verify-snapshot")
+            .about("Check a snapshot matches the chain")
+            .arg(Arg::with_name("file")
+                 .required(true)
+                 .help("Path of the snapshot")))
+
+        .subcommand(SubCommand::with_name("bans")
+            .about("List nodes banned for misbehaving"))
+
+        .subcommand(SubCommand::with_name("unban")
+            .about("Lift the ban on a node, or every node if none is given")
+            .arg(Arg::with_name("address")
+                 .help("IP of the node, with or without its port")))
+
         .subcommand(SubCommand::with_name("shutdown")
             .about("Shutdown service"))
         
@@ -311,6 +1521,18 @@ fn main() -> Result<(), Box<dyn Error>>
     match matches.subcommand_name()
     {
         Some("new-wallet") => return new_wallet(matches.subcommand().1.unwrap()),
+        Some("export-public") => return export_public(matches.subcommand().1.unwrap()),
+        Some("watch-wallet") => return watch_wallet(matches.subcommand().1.unwrap()),
+        Some("address-book") => return address_book(matches.subcommand().1.unwrap()),
+        Some("sign-message") => return sign_message_command(matches.subcommand().1.unwrap()),
+        Some("verify-message") => return verify_message_command(matches.subcommand().1.unwrap()),
+        Some("tx") =>
+        {
+            let tx_options = matches.subcommand().1.unwrap();
+            if tx_options.subcommand_name() == Some("sign") {
+                return tx_sign(tx_options.subcommand().1.unwrap());
+            }
+        },
         Some(&_) | None => {},
     }
 
@@ -325,9 +1547,23 @@ fn main() -> Result<(), Box<dyn Error>>
     match matches.subcommand_name()
     {
         Some("balance") => balance(client, matches.subcommand().1.unwrap())?,
+        Some("history") => history(client, matches.subcommand().1.unwrap())?,
         Some("send") => send(client, matches.subcommand().1.unwrap())?,
+        Some("tx") => tx(client, matches.subcommand().1.unwrap())?,
+        Some("publish-dir") => publish_dir(client, matches.subcommand().1.unwrap())?,
+        Some("rotate-key") => rotate_key(client, matches.subcommand().1.unwrap())?,
         Some("update-page") => update_page(client, matches.subcommand().1.unwrap())?,
+        Some("delete-page") => delete_page(client, matches.subcommand().1.unwrap())?,
+        Some("rename-page") => rename_page(client, matches.subcommand().1.unwrap())?,
+        Some("patch-page") => patch_page(client, matches.subcommand().1.unwrap())?,
+        Some("register-name") => register_name(client, matches.subcommand().1.unwrap())?,
+        Some("transfer-name") => transfer_name(client, matches.subcommand().1.unwrap())?,
+        Some("resolve-name") => resolve_name(client, matches.subcommand().1.unwrap())?,
         Some("transaction-info") => transaction_info(client, matches.subcommand().1.unwrap())?,
+        Some("export-snapshot") => export_snapshot(client, matches.subcommand().1.unwrap())?,
+        Some("verify-snapshot") => verify_snapshot(client, matches.subcommand().1.unwrap())?,
+        Some("bans") => bans(client)?,
+        Some("unban") => unban(client, matches.subcommand().1.unwrap())?,
         Some("shutdown") => shutdown(client)?,
         Some(&_) | None => println!("Error: Must specify an action"),
     }
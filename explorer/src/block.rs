@@ -10,6 +10,7 @@ use super::transaction::data_for_transaction;
 use libhyperchain::service::command::{Command, Response};
 use libhyperchain::service::client::Client;
 use libhyperchain::block::target::difficulty;
+use libhyperchain::wallet::address::encode_address;
 use actix_web::{get, web};
 use actix_web::{HttpRequest, HttpResponse, Responder};
 use serde::Deserialize;
@@ -54,10 +55,11 @@ pub async fn block_handler(request: HttpRequest) -> impl Responder
                 "last_block_id": block_id - 1,
                 "top_block_id": top_block_id,
                 "timestamp": (block.header.timestamp / 1000) as u64,
-                "winner": format!("{}", block.header.raward_to),
+                "winner": encode_address(&block.header.raward_to),
                 "merkle_root": format!("{}", block.header.transaction_merkle_root),
                 "difficulty": difficulty,
                 "pow": block.header.pow,
+                "storage_proof": block.header.storage_proof.map(|proof| format!("{}", proof)),
                 "transactions":
                     block.transactions()
                         .iter()
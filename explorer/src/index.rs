@@ -25,6 +25,27 @@ pub async fn index_handler(request: HttpRequest)
                 "hash_rate": stats.hash_rate,
                 "known_chunks": stats.known_chunks,
                 "replication_percent": stats.replication * 100.0,
+                "replication_target": stats.replication_target,
+                "under_replicated": stats.under_replicated,
+                "over_replicated": stats.over_replicated,
+                "disk_usage": stats.disk_usage as f64 / (1000.0 * 1000.0),
+                "disk_quota": stats.disk_quota as f64 / (1000.0 * 1000.0),
+                "replication_decisions": stats.replication_decisions
+                    .iter()
+                    .map(|decision| json!(
+                    {
+                        "chunk": format!("{}", decision.chunk),
+                        "action": format!("{:?}", decision.action),
+                        "holders": decision.holders,
+                    }))
+                    .collect::<Vec<_>>(),
+                "sync": stats.sync.map(|sync| json!(
+                {
+                    "peer": sync.peer,
+                    "blocks": sync.blocks,
+                    "total": sync.top_block + 1 - sync.fork_block,
+                    "requests_in_flight": sync.requests_in_flight,
+                })),
             });
 
             let body = app_data.hb.render("index", &data).unwrap();
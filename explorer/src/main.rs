@@ -22,6 +22,7 @@ mod transaction;
 mod block;
 mod site;
 mod index;
+mod verify_message;
 
 use libhyperchain::service::client::Client;
 use actix_web::{web, App};
@@ -85,6 +86,7 @@ async fn main() -> Result<(), Box<dyn Error>>
                 .service(site::site_index_handler)
                 .service(site::site_handler)
                 .service(index::index_handler)
+                .service(verify_message::verify_message_handler)
                 .service(Files::new("/", "./static/root").index_file("index.html"))
         })
         .bind("0.0.0.0:8080")?
@@ -8,79 +8,147 @@ use super::AppData;
 
 use libhyperchain::service::client::Client;
 use libhyperchain::service::command::{Command, Response};
-use libhyperchain::transaction::Transaction;
-use libhyperchain::transaction::page::Page;
-use libhyperchain::data_store::data_unit::DataUnit;
+use libhyperchain::wallet::address::decode_address;
+use libhyperchain::error::ErrorMessage;
+use libhyperchain::hash::Hash;
 use actix_web::{get, web};
 use actix_web::{HttpRequest, HttpResponse, Responder};
 use std::error::Error;
 
-fn apply_data_unit(result: &mut Vec<u8>, site: &str, page_name: &str, data: DataUnit)
+// NOTE: A site can be updated by any new block, so browsers must always
+//       revalidate, which the ETag makes cheap
+const CACHE_CONTROL: &str = "public, no-cache";
+
+fn header<'a>(request: &'a HttpRequest, name: &str) -> Option<&'a str>
+{
+    request.headers().get(name)?.to_str().ok()
+}
+
+fn matches_etag(if_none_match: &str, etag: &str) -> bool
+{
+    if_none_match.trim() == "*" ||
+        if_none_match.split(',').any(|tag| tag.trim().trim_start_matches("W/") == etag)
+}
+
+/// Parse a single 'bytes=' range into an inclusive start and end, or none
+/// if it can't be satisfied for a file of this length.
+fn parse_range(range: &str, len: usize) -> Option<(usize, usize)>
 {
-    match data
+    let (start, end) = range.strip_prefix("bytes=")?.trim().split_once('-')?;
+    if len == 0 {
+        return None;
+    }
+
+    if start.is_empty()
     {
-        DataUnit::CreatePage(create_page) =>
-        {
-            info!("[{}] [{}] new page of {} bytes", site, page_name, create_page.page.len());
-            if create_page.name == page_name {
-                *result = create_page.page;
-            }
-        },
+        // Suffix range, i.e. the last n bytes
+        let suffix = end.parse::<usize>().ok()?;
+        if suffix == 0 {
+            return None;
+        }
+        return Some((len.saturating_sub(suffix), len - 1));
     }
+
+    let start = start.parse::<usize>().ok()?;
+    let end = 
+        if end.is_empty() {
+            len - 1
+        } else {
+            std::cmp::min(end.parse::<usize>().ok()?, len - 1)
+        };
+
+    if start > end {
+        return None;
+    }
+    Some((start, end))
 }
 
-fn render_updates(client: &mut Client, site: &str, page_name: &str, updates: &[Transaction<Page>]) 
-    -> Result<Vec<u8>, Box<dyn Error>>
+/// Multiple ranges aren't supported, so those requests get the whole file.
+fn is_single_range(range: &str) -> bool
 {
-    let mut result = Vec::<u8>::new();
-    for update in updates
+    !range.contains(',')
+}
+
+/// Sites are given either by address, or by a registered name as '@name'.
+fn resolve_site(client: &mut Client, site: &str) -> Result<Hash, Box<dyn Error>>
+{
+    match site.strip_prefix('@')
     {
-        let id = update.hash()?;
-        match client.send(Command::PageData(id.data().to_vec()))?
+        Some(name) =>
         {
-            Response::PageData(data) => 
-                apply_data_unit(&mut result, site, page_name, data),
-
-            _ => 
+            match client.send(Command::ResolveName(name.to_owned()))?
             {
-                warn!("[{}] [{}] No data found for update '{}'",
-                      site, page_name, id);
-            },
-        }
-    }
+                Response::Name(record) => Ok(record.site),
+                _ => Err(ErrorMessage::new(&format!("Unknown name '{}'", name))),
+            }
+        },
 
-    if result.is_empty() {
-        Ok(format!("Page '{}' not found", page_name).as_bytes().to_vec())
-    } else {
-        Ok(result)
+        None => decode_address(site),
     }
 }
 
-fn get_page(client: &mut Client, site: String, page: String) -> impl Responder
+fn get_page(client: &mut Client, request: &HttpRequest, site: String, page: String) 
+    -> HttpResponse
 {
-    info!("[{}] Rendering new page {}", site, page);
+    info!("[{}] Rendering page {}", site, page);
 
-    let id_or_error = base_62::decode(&site);
+    let id_or_error = resolve_site(client, &site);
     if id_or_error.is_err() {
-        return HttpResponse::Ok().body(format!("Unkown site: {}", site));
+        return HttpResponse::NotFound().body(id_or_error.unwrap_err().to_string());
+    }
+
+    let id = id_or_error.unwrap().data().to_vec();
+    let (data, content_type, hash) = match client.send(Command::SiteFile(id, page.clone())).unwrap()
+    {
+        Response::SiteFile(data, content_type, hash) => (data, content_type, hash),
+        _ => return HttpResponse::NotFound().body(format!("Page '{}' not found", page)),
+    };
+
+    let etag = format!("\"{}\"", hash);
+    if let Some(if_none_match) = header(request, "If-None-Match")
+    {
+        if matches_etag(if_none_match, &etag)
+        {
+            return HttpResponse::NotModified()
+                .header("ETag", etag)
+                .header("Cache-Control", CACHE_CONTROL)
+                .finish();
+        }
     }
 
-    let id = id_or_error.unwrap();
-    let response = client.send(Command::PageUpdates(id)).unwrap();
-    match response
+    // Only honour the range if the client's copy is still this version
+    let range = match header(request, "If-Range")
+    {
+        Some(if_range) if if_range.trim() != etag => None,
+        _ => header(request, "Range").filter(|range| is_single_range(range)),
+    };
+
+    let (mut response, body) = match range.map(|range| parse_range(range, data.len()))
     {
-        Response::PageUpdates(updates) =>
+        Some(Some((start, end))) =>
         {
-            info!("[{}] [{}] Got {} updates", site, page, updates.len());
-            let page = render_updates(client, &site, &page, &updates).unwrap();
+            let mut response = HttpResponse::PartialContent();
+            response.header("Content-Range", format!("bytes {}-{}/{}", start, end, data.len()));
+            (response, data[start..=end].to_vec())
+        },
 
-            HttpResponse::Ok()
-                .header("Location", format!("/site/{}/", site))
-                .body(page)
+        Some(None) =>
+        {
+            return HttpResponse::RangeNotSatisfiable()
+                .header("Content-Range", format!("bytes */{}", data.len()))
+                .finish();
         },
 
-        _ => HttpResponse::Ok().body(format!("Unkown site: {}", site)),
+        None => (HttpResponse::Ok(), data),
+    };
+
+    response.header("ETag", etag);
+    response.header("Cache-Control", CACHE_CONTROL);
+    response.header("Accept-Ranges", "bytes");
+    if let Some(content_type) = content_type {
+        response.content_type(content_type);
     }
+    response.body(body)
 }
 
 #[get("/site/{site}")]
@@ -99,7 +167,7 @@ pub async fn site_index_handler(web::Path(site): web::Path<String>,
 {
     let app_data = request.app_data::<web::Data<AppData>>().unwrap();
     let mut client = app_data.client();
-    get_page(&mut client, site, "index.html".to_owned())
+    get_page(&mut client, &request, site, "index.html".to_owned())
 }
 
 #[get("/site/{site}/{page:.*}")]
@@ -109,6 +177,58 @@ pub async fn site_handler(web::Path((site, page)): web::Path<(String, String)>,
 {
     let app_data = request.app_data::<web::Data<AppData>>().unwrap();
     let mut client = app_data.client();
-    get_page(&mut client, site, page)
+
+    // Directories inside a bundle resolve to their index page
+    let page_path =
+        if page.is_empty() || page.ends_with('/') {
+            format!("{}index.html", page)
+        } else {
+            page
+        };
+
+    get_page(&mut client, &request, site, page_path)
 }
 
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+
+    #[test]
+    fn test_parse_range()
+    {
+        assert_eq!(parse_range("bytes=0-3", 10), Some((0, 3)));
+        assert_eq!(parse_range("bytes=4-", 10), Some((4, 9)));
+        assert_eq!(parse_range("bytes=4-100", 10), Some((4, 9)));
+        assert_eq!(parse_range("items=0-3", 10), None);
+        assert_eq!(parse_range("bytes=a-3", 10), None);
+
+        // Suffix ranges
+        assert_eq!(parse_range("bytes=-3", 10), Some((7, 9)));
+        assert_eq!(parse_range("bytes=-100", 10), Some((0, 9)));
+        assert_eq!(parse_range("bytes=-0", 10), None);
+
+        // Out of bounds
+        assert_eq!(parse_range("bytes=10-", 10), None);
+        assert_eq!(parse_range("bytes=5-4", 10), None);
+        assert_eq!(parse_range("bytes=0-", 0), None);
+        assert_eq!(parse_range("bytes=-1", 0), None);
+
+        // Multiple ranges
+        assert_eq!(is_single_range("bytes=0-3"), true);
+        assert_eq!(is_single_range("bytes=0-1, 3-4"), false);
+        assert_eq!(parse_range("bytes=0-1,3-4", 10), None);
+    }
+
+    #[test]
+    fn test_matches_etag()
+    {
+        assert_eq!(matches_etag("\"abc\"", "\"abc\""), true);
+        assert_eq!(matches_etag("\"xyz\", W/\"abc\"", "\"abc\""), true);
+        assert_eq!(matches_etag("*", "\"abc\""), true);
+        assert_eq!(matches_etag("\"xyz\"", "\"abc\""), false);
+    }
+
+}
@@ -9,8 +9,11 @@ use crate::AppData;
 use libhyperchain::transaction::{Transaction, TransactionContent, TransactionVariant};
 use libhyperchain::transaction::transfer::Transfer;
 use libhyperchain::transaction::page::Page;
+use libhyperchain::transaction::site_transfer::SiteTransfer;
+use libhyperchain::transaction::name::NameRegistration;
 use libhyperchain::block::Block;
 use libhyperchain::service::command::{Command, Response};
+use libhyperchain::wallet::address::encode_address;
 use actix_web::{get, web};
 use actix_web::{HttpRequest, HttpResponse, Responder};
 use serde::Deserialize;
@@ -31,7 +34,7 @@ fn inputs_from_transaction<C>(transaction: &Transaction<C>)
         {
             json!(
             {
-                "address": format!("{}", input.get_address()),
+                "address": encode_address(&input.get_address()),
                 "amount": input.amount,
             })
         })
@@ -49,7 +52,7 @@ fn transfer_data(transfer: &Transaction<Transfer>, block_id: String)
         {
             json!(
             {
-                "address": format!("{}", output.to),
+                "address": encode_address(&output.to),
                 "amount": output.amount,
             })
         })
@@ -78,21 +81,12 @@ fn page_data(page: &Transaction<Page>, block_id: String)
     let inputs = inputs_from_transaction(&page);
     let outputs = vec![json!(
     {
-        "address": format!("{}", &page.header.content.site),
+        "address": encode_address(&page.header.content.site),
         "amount": page.header.content.cost(),
     })];
 
-    let data = page.header.content.data_hashes
-        .iter()
-        .map(|x| json!(
-        {
-            "hash": format!("{}", x),
-        }))
-        .collect::<Vec<_>>();
-
-    let data_size_bytes = page.header.content.data_length;
-    let data_size = data_size_bytes as f32 / (1000.0 * 1000.0);
-    let chunk_count = page.header.content.data_hashes.len();
+    let data_size = page.header.content.data_length as f32 / (1000.0 * 1000.0);
+    let chunk_count = page.header.content.chunk_count;
 
     json!(
     {
@@ -100,7 +94,7 @@ fn page_data(page: &Transaction<Page>, block_id: String)
         "id": format!("{}", id),
         "inputs": inputs,
         "outputs": outputs,
-        "data": data,
+        "chunk_root": format!("{}", page.header.content.chunk_root),
         "amount": page.header.content.cost(),
         "fee": page.header.content.fee,
         "block": block_id,
@@ -109,6 +103,55 @@ fn page_data(page: &Transaction<Page>, block_id: String)
     })
 }
 
+fn site_transfer_data(site_transfer: &Transaction<SiteTransfer>, block_id: String) 
+    -> serde_json::Value
+{
+    let id = site_transfer.hash().unwrap();
+    let inputs = inputs_from_transaction(&site_transfer);
+    let outputs = vec![json!(
+    {
+        "address": encode_address(&site_transfer.header.content.new_owner),
+        "amount": 0.0,
+    })];
+
+    json!(
+    {
+        "type": "Site Transfer",
+        "id": format!("{}", id),
+        "inputs": inputs,
+        "outputs": outputs,
+        "site": encode_address(&site_transfer.header.content.site),
+        "total_amount": 0.0,
+        "fee": site_transfer.header.content.fee,
+        "block": block_id,
+    })
+}
+
+fn name_data(name: &Transaction<NameRegistration>, block_id: String) 
+    -> serde_json::Value
+{
+    let id = name.hash().unwrap();
+    let inputs = inputs_from_transaction(name);
+    let outputs = vec![json!(
+    {
+        "address": encode_address(&name.header.content.owner),
+        "amount": 0.0,
+    })];
+
+    json!(
+    {
+        "type": "Name Registration",
+        "id": format!("{}", id),
+        "inputs": inputs,
+        "outputs": outputs,
+        "site": encode_address(&name.header.content.site),
+        "name": name.header.content.name,
+        "total_amount": 0.0,
+        "fee": name.header.content.fee,
+        "block": block_id,
+    })
+}
+
 pub fn data_for_transaction((transaction, block): &(TransactionVariant, Option<Block>)) 
     -> serde_json::Value
 {
@@ -123,6 +166,8 @@ pub fn data_for_transaction((transaction, block): &(TransactionVariant, Option<B
     {
         TransactionVariant::Transfer(transfer) => transfer_data(transfer, block_id),
         TransactionVariant::Page(page) => page_data(page, block_id),
+        TransactionVariant::SiteTransfer(site_transfer) => site_transfer_data(site_transfer, block_id),
+        TransactionVariant::NameRegistration(name) => name_data(name, block_id),
     }
 }
 
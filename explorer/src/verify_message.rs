@@ -0,0 +1,62 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::AppData;
+
+use libhyperchain::wallet::address::decode_address;
+use libhyperchain::wallet::message::{MessageSignature, verify_message};
+use libhyperchain::wallet::public_wallet::WalletValidationResult;
+use actix_web::{get, web};
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct VerifyMessageParameters
+{
+    address: Option<String>,
+    message: Option<String>,
+    signature: Option<String>,
+}
+
+fn verify(address_str: &str, message: &str, signature_str: &str) -> Result<(), String>
+{
+    let address = decode_address(address_str).map_err(|e| e.to_string())?;
+    let signature = MessageSignature::decode(signature_str).map_err(|e| e.to_string())?;
+    match verify_message(&address, message, &signature).map_err(|e| e.to_string())?
+    {
+        WalletValidationResult::Ok => Ok(()),
+        result => Err(result.to_string()),
+    }
+}
+
+#[get("/verify-message")]
+pub async fn verify_message_handler(request: HttpRequest) -> impl Responder
+{
+    let parameters = web::Query::<VerifyMessageParameters>::from_query(request.query_string()).unwrap();
+    let app_data = request.app_data::<web::Data<AppData>>().unwrap();
+
+    let address = parameters.address.clone().unwrap_or_default();
+    let message = parameters.message.clone().unwrap_or_default();
+    let signature = parameters.signature.clone().unwrap_or_default();
+    let result =
+        if address.is_empty() || signature.is_empty() {
+            None
+        } else {
+            Some(verify(&address, &message, &signature))
+        };
+
+    let data = json!({
+        "address": address,
+        "message": message,
+        "signature": signature,
+        "checked": result.is_some(),
+        "valid": matches!(result, Some(Ok(()))),
+        "error": match result { Some(Err(err)) => err, _ => String::new() },
+    });
+
+    let body = app_data.hb.render("verify-message", &data).unwrap();
+    HttpResponse::Ok().body(body)
+}
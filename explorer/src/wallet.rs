@@ -8,6 +8,7 @@ use super::AppData;
 use super::transaction::data_for_transaction;
 use libhyperchain::service::command::{Command, Response};
 use libhyperchain::wallet::WalletStatus;
+use libhyperchain::wallet::address::decode_address;
 use libhyperchain::transaction::TransactionVariant;
 use libhyperchain::block::Block;
 use libhyperchain::service::client::Client;
@@ -49,7 +50,12 @@ pub async fn wallet_handler(request: HttpRequest) -> impl Responder
     let app_data = request.app_data::<web::Data<AppData>>().unwrap();
 
     let mut client = app_data.client();
-    let address = base_62::decode(&parameters.address).unwrap();
+    let address_or_error = decode_address(&parameters.address);
+    if address_or_error.is_err() {
+        return HttpResponse::BadRequest().body(address_or_error.unwrap_err().to_string());
+    }
+
+    let address = address_or_error.unwrap().data().to_vec();
     let wallet_status = get_wallet_status(&mut client, &address);
     let transacion_history = get_transaction_history(&mut client, &address);
 
@@ -9,6 +9,8 @@ use crate::chain::BlockChain;
 use crate::transaction::Transaction;
 use crate::transaction::transfer::Transfer;
 use crate::transaction::page::Page;
+use crate::transaction::site_transfer::SiteTransfer;
+use crate::transaction::name::NameRegistration;
 use crate::wallet::Wallet;
 
 use std::error::Error;
@@ -19,6 +21,8 @@ pub struct BlockBuilder<'a, W>
     raward_to: &'a W,
     transfers: Vec<Transaction<Transfer>>,
     pages: Vec<Transaction<Page>>,
+    site_transfers: Vec<Transaction<SiteTransfer>>,
+    names: Vec<Transaction<NameRegistration>>,
 }
 
 impl<'a, W> BlockBuilder<'a, W>
@@ -32,6 +36,8 @@ impl<'a, W> BlockBuilder<'a, W>
             raward_to,
             transfers: Vec::new(),
             pages: Vec::new(),
+            site_transfers: Vec::new(),
+            names: Vec::new(),
         }
     }
 
@@ -47,10 +53,22 @@ impl<'a, W> BlockBuilder<'a, W>
         self
     }
 
+    pub fn add_site_transfer(mut self, site_transfer: Transaction<SiteTransfer>) -> Self
+    {
+        self.site_transfers.push(site_transfer);
+        self
+    }
+
+    pub fn add_name(mut self, name: Transaction<NameRegistration>) -> Self
+    {
+        self.names.push(name);
+        self
+    }
+
     pub fn build(self, chain: &mut BlockChain) -> Result<Block, Box<dyn Error>>
     {
         Block::new(chain, self.raward_to, 
-            self.transfers, self.pages)
+            self.transfers, self.pages, self.site_transfers, self.names)
     }
 
 }
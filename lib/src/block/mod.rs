@@ -7,12 +7,16 @@
 pub mod validate;
 pub mod target;
 pub mod builder;
+pub mod storage_proof;
 mod transactions;
 use target::{calculate_target, Target};
 use transactions::merkle_root_for_transactions;
+use storage_proof::StorageSegment;
 use crate::transaction::Transaction;
 use crate::transaction::transfer::Transfer;
 use crate::transaction::page::Page;
+use crate::transaction::site_transfer::SiteTransfer;
+use crate::transaction::name::NameRegistration;
 use crate::chain::BlockChain;
 use crate::wallet::Wallet;
 use crate::config::HASH_LEN;
@@ -29,7 +33,7 @@ pub fn current_timestamp() -> u128
     SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_millis()
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct BlockHeader
 {
     pub prev_hash: Hash,
@@ -38,6 +42,7 @@ pub struct BlockHeader
     pub raward_to: Hash,
     pub target: Target,
     pub transaction_merkle_root: Hash,
+    pub storage_proof: Option<Hash>,
     pub pow: u64, // TODO: This should be a correct size
 }
 
@@ -47,6 +52,27 @@ pub struct Block
     pub header: BlockHeader,
     pub pages: Vec<Transaction<Page>>,
     pub transfers: Vec<Transaction<Transfer>>,
+    pub site_transfers: Vec<Transaction<SiteTransfer>>,
+    pub names: Vec<Transaction<NameRegistration>>,
+
+    /// A segment of each chunk picked for `header.storage_proof`, with
+    /// proofs it's part of the page the chunk was picked from
+    pub storage_segments: Vec<StorageSegment>,
+}
+
+impl BlockHeader
+{
+
+    /// A block's hash only covers its header, so chains of headers can be
+    /// checked without the blocks.
+    pub fn hash(&self) -> Result<Hash, Box<dyn Error>>
+    {
+        let mut hasher = Sha256::default();
+        let bytes = bincode::serialize(self)?;
+        hasher.update(&bytes);
+        Ok(Hash::from(&hasher.clone().finalize()))
+    }
+
 }
 
 impl std::fmt::Debug for Block
@@ -70,12 +96,14 @@ impl Block
     pub fn new_blank<W: Wallet>(chain: &mut BlockChain, raward_to: &W)
         -> Result<Self, Box<dyn Error>>
     {
-        Self::new(chain, raward_to, Vec::new(), Vec::new())
+        Self::new(chain, raward_to, Vec::new(), Vec::new(), Vec::new(), Vec::new())
     }
 
     pub fn new<W: Wallet>(chain: &mut BlockChain, raward_to: &W, 
                           transfers: Vec<Transaction<Transfer>>,
-                          pages: Vec<Transaction<Page>>)
+                          pages: Vec<Transaction<Page>>,
+                          site_transfers: Vec<Transaction<SiteTransfer>>,
+                          names: Vec<Transaction<NameRegistration>>)
         -> Result<Self, Box<dyn Error>>
     {
         let (sample_start, sample_end) = chain.take_sample();
@@ -95,7 +123,7 @@ impl Block
             };
 
         let timestamp = current_timestamp();
-        let transaction_merkle_root = merkle_root_for_transactions(&transfers, &pages)?;
+        let transaction_merkle_root = merkle_root_for_transactions(block_id, &transfers, &pages, &site_transfers, &names)?;
         Ok(Block
         {
             header: BlockHeader
@@ -106,11 +134,15 @@ impl Block
                 raward_to: raward_to.get_address(),
                 target,
                 transaction_merkle_root,
+                storage_proof: None,
                 pow: 0,
             },
 
             pages,
             transfers,
+            site_transfers,
+            names,
+            storage_segments: Vec::new(),
         })
     }
 
@@ -122,10 +154,7 @@ impl Block
 
     pub fn hash(&self) -> Result<Hash, Box<dyn Error>>
     {
-        let mut hasher = Sha256::default();
-        let bytes = bincode::serialize(&self.header)?;
-        hasher.update(&bytes);
-        Ok(Hash::from(&hasher.clone().finalize()))
+        self.header.hash()
     }
 
 }
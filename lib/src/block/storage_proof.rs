@@ -0,0 +1,326 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::{Block, BlockHeader, Hash};
+use super::validate::BlockValidationResult;
+use crate::data_store::page::chunk_segments;
+use crate::merkle_tree::{calculate_merkle_proof, MerkleProof, MerkleVersion};
+use crate::config::STORAGE_PROOF_CHUNKS;
+
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use std::error::Error;
+
+/// A chunk picked for a storage proof, by the page it's in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChunkPosition
+{
+    pub chunk_root: Hash,
+    pub chunk_count: u32,
+    pub index: u32,
+
+    /// Picks which of the chunk's segments to prove
+    pub segment_seed: u64,
+}
+
+impl ChunkPosition
+{
+
+    pub fn segment_index(&self, segment_count: usize) -> usize
+    {
+        (self.segment_seed % std::cmp::max(segment_count, 1) as u64) as usize
+    }
+
+}
+
+/// The segment of a picked chunk a block carries, with proofs that it's 
+/// part of the chunk and the chunk is part of its page. This is all that's
+/// needed to check the proof, so every node agrees on it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StorageSegment
+{
+    pub chunk: Hash,
+    pub chunk_proof: MerkleProof,
+    pub segment_count: u32,
+    pub data: Vec<u8>,
+    pub segment_proof: MerkleProof,
+}
+
+impl StorageSegment
+{
+
+    /// The segment picked by `position` from the chunk's data, given the 
+    /// proof the chunk is in its page.
+    pub fn new(position: &ChunkPosition, data: &[u8], chunk_proof: MerkleProof) -> Option<Self>
+    {
+        let segments = chunk_segments(data);
+        let index = position.segment_index(segments.len());
+        let segment_proof = calculate_merkle_proof(&segments, index, MerkleVersion::V2)?;
+
+        Some(Self
+        {
+            chunk: segment_proof.root(segments[index]),
+            chunk_proof,
+            segment_count: segments.len() as u32,
+            data: segments[index].to_vec(),
+            segment_proof,
+        })
+    }
+
+    fn is_valid_for(&self, position: &ChunkPosition) -> bool
+    {
+        let segment_count = self.segment_count as usize;
+        let is_in_chunk = self.segment_proof.version == MerkleVersion::V2 && 
+            self.segment_proof.verify_at(&self.data, position.segment_index(segment_count), 
+                segment_count, &self.chunk);
+        let is_in_page = self.chunk_proof.version == MerkleVersion::V2 && 
+            self.chunk_proof.verify_at(self.chunk.data(), position.index as usize, 
+                position.chunk_count as usize, &position.chunk_root);
+
+        is_in_chunk && is_in_page
+    }
+
+}
+
+/// Whether a block's storage proof holds. It's checked from the block
+/// alone, so every node comes to the same answer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StorageProofStatus
+{
+    /// The block doesn't have one
+    None,
+    Proven,
+    Invalid,
+}
+
+/// What picks the chunks and segments for a block. It covers the whole
+/// header apart from the proof, nonce included, so each attempt at mining
+/// the block needs the chunks again.
+pub fn proof_seed(header: &BlockHeader) -> Result<Hash, Box<dyn Error>>
+{
+    let mut header = header.clone();
+    header.storage_proof = None;
+    header.hash()
+}
+
+fn seeded_number(seed: &Hash, i: usize, salt: u8) -> u64
+{
+    let mut hasher = Sha256::default();
+    hasher.update(seed.data());
+    hasher.update((i as u64).to_le_bytes());
+    hasher.update([salt]);
+    let bytes = hasher.finalize();
+
+    let mut number = [0u8; 8];
+    number.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(number)
+}
+
+/// The chunks a block with this seed must prove it stores, picked from the
+/// pages in the chain before it.
+pub fn select_chunks(seed: &Hash, page_chunks: &[(Hash, u32)]) -> Vec<ChunkPosition>
+{
+    if page_chunks.is_empty() {
+        return Vec::new();
+    }
+
+    (0..STORAGE_PROOF_CHUNKS)
+        .map(|i|
+        {
+            let page = seeded_number(seed, i, 0) % page_chunks.len() as u64;
+            let (chunk_root, chunk_count) = page_chunks[page as usize];
+            let index = seeded_number(seed, i, 1) % std::cmp::max(chunk_count, 1) as u64;
+            let segment_seed = seeded_number(seed, i, 2);
+            ChunkPosition { chunk_root, chunk_count, index: index as u32, segment_seed }
+        })
+        .collect()
+}
+
+/// Hash of the segments carried with the block, which is what the header
+/// commits to.
+fn commitment(segments: &[StorageSegment]) -> Hash
+{
+    let mut hasher = Sha256::default();
+    for segment in segments
+    {
+        hasher.update(segment.chunk.data());
+        hasher.update((segment.data.len() as u64).to_le_bytes());
+        hasher.update(&segment.data);
+    }
+
+    Hash::from(&hasher.finalize())
+}
+
+impl Block
+{
+
+    /// Prove we store the chunks picked for this block's current nonce,
+    /// which lowers the work needed to mine it. If any of them can't be
+    /// found the block is left without a proof and false is returned.
+    pub fn add_storage_proof<F>(&mut self, page_chunks: &[(Hash, u32)], mut get_segment: F)
+        -> Result<bool, Box<dyn Error>>
+        where F: FnMut(&ChunkPosition) -> Option<StorageSegment>
+    {
+        self.header.storage_proof = None;
+        self.storage_segments.clear();
+
+        let seed = proof_seed(&self.header)?;
+        let selected = select_chunks(&seed, page_chunks);
+        if selected.is_empty() {
+            return Ok(false);
+        }
+
+        let mut segments = Vec::new();
+        for position in &selected
+        {
+            let segment_or_none = get_segment(position);
+            if segment_or_none.is_none() {
+                return Ok(false);
+            }
+
+            segments.push(segment_or_none.unwrap());
+        }
+
+        self.header.storage_proof = Some(commitment(&segments));
+        self.storage_segments = segments;
+        Ok(true)
+    }
+
+    /// Work out the storage proof for the block's current nonce, if we can,
+    /// then check the block has the work it needs with it. Called for each
+    /// nonce while mining.
+    pub fn validate_pow_with_storage_proof<F>(&mut self, page_chunks: &[(Hash, u32)], get_segment: F)
+        -> Result<BlockValidationResult, Box<dyn Error>>
+        where F: FnMut(&ChunkPosition) -> Option<StorageSegment>
+    {
+        let is_proven = self.add_storage_proof(page_chunks, get_segment)?;
+        self.header.validate_pow_with_discount(is_proven)
+    }
+
+    /// Check the segments carried with the block are from the chunks picked
+    /// from `page_chunks`, and are the ones the header commits to.
+    pub fn validate_storage_proof(&self, page_chunks: &[(Hash, u32)]) -> StorageProofStatus
+    {
+        if self.header.storage_proof.is_none()
+        {
+            if self.storage_segments.is_empty() {
+                return StorageProofStatus::None;
+            }
+            return StorageProofStatus::Invalid;
+        }
+
+        let seed_or_error = proof_seed(&self.header);
+        if seed_or_error.is_err() {
+            return StorageProofStatus::Invalid;
+        }
+
+        let seed = seed_or_error.unwrap();
+        let selected = select_chunks(&seed, page_chunks);
+        if selected.is_empty() || selected.len() != self.storage_segments.len() {
+            return StorageProofStatus::Invalid;
+        }
+
+        let is_valid = selected.iter()
+            .zip(&self.storage_segments)
+            .all(|(position, segment)| segment.is_valid_for(position));
+        if !is_valid || self.header.storage_proof != Some(commitment(&self.storage_segments)) {
+            return StorageProofStatus::Invalid;
+        }
+
+        StorageProofStatus::Proven
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+    use crate::block::builder::BlockBuilder;
+    use crate::chain::BlockChain;
+    use crate::data_store::DataStore;
+    use crate::data_store::data_unit::DataUnit;
+    use crate::data_store::page::{CreatePageData, content_hash};
+    use crate::config::CHUNK_SEGMENT_SIZE;
+    use crate::transaction::page::Page;
+    use crate::wallet::private_wallet::PrivateWallet;
+    use crate::miner;
+
+    #[test]
+    fn test_block_storage_proof()
+    {
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let mut chain = BlockChain::open_temp();
+        let data_store = DataStore::open_temp();
+
+        let page_chunks = (0..4u8)
+            .map(|i| DataUnit::CreatePage(CreatePageData::new(format!("{}.html", i), vec![i; 100_000])))
+            .map(|data_unit|
+            {
+                data_store.store_data_unit(&data_unit).unwrap();
+                let page = Page::new_from_data(1, Hash::empty(), &data_unit, |_| None, 0.0).unwrap();
+                (page.chunk_root, page.chunk_count)
+            })
+            .collect::<Vec<_>>();
+        let get_segment = |position: &ChunkPosition|
+        {
+            let (compressed, proof) = data_store.page_chunk(&position.chunk_root, position.index).ok()?;
+            StorageSegment::new(position, &compressed.decompress().ok()?, proof)
+        };
+
+        let block = BlockBuilder::new(&wallet).build(&mut chain).unwrap();
+        assert_eq!(block.validate_storage_proof(&page_chunks), StorageProofStatus::None);
+
+        let block = miner::mine_block_with_storage_proof(block, &page_chunks, get_segment);
+        assert!(block.header.storage_proof.is_some());
+        assert_eq!(block.storage_segments.len(), STORAGE_PROOF_CHUNKS);
+        assert_eq!(block.validate_storage_proof(&page_chunks), StorageProofStatus::Proven);
+        assert_eq!(block.validate_content(None, None, &page_chunks).unwrap(), BlockValidationResult::Ok);
+
+        // Only one segment of each chunk is carried, and it's part of the chunk
+        for segment in &block.storage_segments
+        {
+            assert!(segment.data.len() <= CHUNK_SEGMENT_SIZE);
+            assert_eq!(content_hash(&data_store.get(&segment.chunk).unwrap()), segment.chunk);
+        }
+
+        // Each nonce picks its own chunks and segments, so a proof can't be reused
+        let mut next_nonce = block.clone();
+        next_nonce.header.pow += 1;
+        assert_ne!(proof_seed(&next_nonce.header).unwrap(), proof_seed(&block.header).unwrap());
+        assert_eq!(next_nonce.validate_storage_proof(&page_chunks), StorageProofStatus::Invalid);
+
+        // The picked chunks depend on what's in the chain
+        let seed = proof_seed(&block.header).unwrap();
+        let other_chunks = page_chunks.iter().rev().copied().collect::<Vec<_>>();
+        if select_chunks(&seed, &other_chunks) != select_chunks(&seed, &page_chunks) {
+            assert_eq!(block.validate_storage_proof(&other_chunks), StorageProofStatus::Invalid);
+        }
+
+        let mut tampered = block.clone();
+        tampered.storage_segments[0].data[0] ^= 1;
+        assert_eq!(tampered.validate_storage_proof(&page_chunks), StorageProofStatus::Invalid);
+
+        // Another segment of the same chunk isn't the one that was picked
+        let (chunk_root, chunk_count) = page_chunks[0];
+        let position = ChunkPosition { chunk_root, chunk_count, index: 1, segment_seed: 0 };
+        let (compressed, proof) = data_store.page_chunk(&chunk_root, 1).unwrap();
+        let data = compressed.decompress().unwrap();
+        let other_position = ChunkPosition { segment_seed: 1, ..position };
+        let segment = StorageSegment::new(&position, &data, proof.clone()).unwrap();
+        let other_segment = StorageSegment::new(&other_position, &data, proof).unwrap();
+        assert!(segment.segment_count > 1);
+        assert!(segment.is_valid_for(&position));
+        assert!(!other_segment.is_valid_for(&position));
+
+        // Without every chunk there's no proof
+        let mut unproven = BlockBuilder::new(&wallet).build(&mut chain).unwrap();
+        assert_eq!(unproven.add_storage_proof(&page_chunks, |_| None).unwrap(), false);
+        assert_eq!(unproven.header.storage_proof, None);
+    }
+
+}
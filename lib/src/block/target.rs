@@ -5,7 +5,7 @@
  */
 
 use super::HASH_LEN;
-use super::Block;
+use super::{Block, BlockHeader};
 use crate::config::{BLOCK_SAMPLE_SIZE, BLOCK_TIME};
 
 const TARGET_LEN: usize = 4;
@@ -76,6 +76,14 @@ pub fn hash_from_target(compact: &Target) -> [u8; HASH_LEN]
 
 pub fn calculate_target(sample_start_or_none: Option<Block>, 
                         sample_end_or_none: Option<Block>) -> Target
+{
+    calculate_target_for_headers(
+        sample_start_or_none.as_ref().map(|block| &block.header),
+        sample_end_or_none.as_ref().map(|block| &block.header))
+}
+
+pub fn calculate_target_for_headers(sample_start_or_none: Option<&BlockHeader>, 
+                                    sample_end_or_none: Option<&BlockHeader>) -> Target
 {
     // If we do not have enough data for a sample, use the min target
     if sample_start_or_none.is_none() || sample_end_or_none.is_none() {
@@ -84,14 +92,14 @@ pub fn calculate_target(sample_start_or_none: Option<Block>,
 
     // We're within the sample range, so keep the last target
     let sample_end = sample_end_or_none.unwrap();
-    if sample_end.header.block_id % BLOCK_SAMPLE_SIZE != 0 {
-        return sample_end.header.target;
+    if sample_end.block_id % BLOCK_SAMPLE_SIZE != 0 {
+        return sample_end.target;
     }
 
     // Calculate new target with sample
     let sample_start = sample_start_or_none.unwrap();
-    let sample_time = sample_end.header.timestamp - sample_start.header.timestamp;
-    let curr_diff = difficulty(&sample_end.header.target);
+    let sample_time = sample_end.timestamp - sample_start.timestamp;
+    let curr_diff = difficulty(&sample_end.target);
     let curr_hash_rate = hash_rate(curr_diff, sample_time as u64);
 
     let new_diff = diff_for_hash_rate(curr_hash_rate);
@@ -9,16 +9,21 @@ use crate::wallet::WalletStatus;
 use crate::transaction::Transaction;
 use crate::transaction::transfer::Transfer;
 use crate::transaction::page::Page;
+use crate::transaction::site_transfer::SiteTransfer;
+use crate::transaction::name::NameRegistration;
 use crate::transaction::TransactionVariant;
-use crate::merkle_tree::calculate_merkle_root;
+use crate::merkle_tree::{calculate_merkle_root, calculate_merkle_proof, MerkleProof, MerkleVersion};
 use crate::hash::Hash;
 
 use std::collections::HashSet;
 use std::error::Error;
 
-pub fn merkle_root_for_transactions(transfers: &Vec<Transaction<Transfer>>,
-                                    pages: &Vec<Transaction<Page>>)
-    -> Result<Hash, Box<dyn Error>>
+/// Hashes of every transaction, in the order they're put in the merkle tree.
+fn transaction_hashes(transfers: &Vec<Transaction<Transfer>>,
+                      pages: &Vec<Transaction<Page>>,
+                      site_transfers: &Vec<Transaction<SiteTransfer>>,
+                      names: &Vec<Transaction<NameRegistration>>)
+    -> Result<Vec<Hash>, Box<dyn Error>>
 {
     let mut hashes = Vec::new();
     for transfer in transfers {
@@ -27,8 +32,25 @@ pub fn merkle_root_for_transactions(transfers: &Vec<Transaction<Transfer>>,
     for page in pages {
         hashes.push(page.hash()?);
     }
+    for site_transfer in site_transfers {
+        hashes.push(site_transfer.hash()?);
+    }
+    for name in names {
+        hashes.push(name.hash()?);
+    }
+
+    Ok(hashes)
+}
 
-    Ok(calculate_merkle_root(&hashes))
+pub fn merkle_root_for_transactions(block_id: u64,
+                                    transfers: &Vec<Transaction<Transfer>>,
+                                    pages: &Vec<Transaction<Page>>,
+                                    site_transfers: &Vec<Transaction<SiteTransfer>>,
+                                    names: &Vec<Transaction<NameRegistration>>)
+    -> Result<Hash, Box<dyn Error>>
+{
+    let hashes = transaction_hashes(transfers, pages, site_transfers, names)?;
+    Ok(calculate_merkle_root(&hashes, MerkleVersion::for_block(block_id)))
 }
 
 impl Block
@@ -56,6 +78,20 @@ impl Block
             }
         }
 
+        for site_transfer in &self.site_transfers
+        {
+            for address in site_transfer.get_from_addresses() {
+                addresses_in_use.insert(address);
+            }
+        }
+
+        for name in &self.names
+        {
+            for address in name.get_from_addresses() {
+                addresses_in_use.insert(address);
+            }
+        }
+
         addresses_in_use.into_iter().collect::<Vec<_>>()
     }
 
@@ -78,9 +114,36 @@ impl Block
             status = page.update_wallet_status(address, status, is_block_winner)?;
         }
 
+        for site_transfer in &self.site_transfers
+        {
+            let is_block_winner = &self.header.raward_to == address;
+            status = site_transfer.update_wallet_status(address, status, is_block_winner)?;
+        }
+
+        for name in &self.names
+        {
+            let is_block_winner = &self.header.raward_to == address;
+            status = name.update_wallet_status(address, status, is_block_winner)?;
+        }
+
         Ok(status)
     }
 
+    /// Proof the transaction is in this block, which can be checked against
+    /// `header.transaction_merkle_root` with the transaction's hash.
+    pub fn transaction_proof(&self, transaction_id: &Hash) 
+        -> Result<Option<MerkleProof>, Box<dyn Error>>
+    {
+        let hashes = transaction_hashes(&self.transfers, &self.pages, &self.site_transfers, &self.names)?;
+        let index_or_none = hashes.iter().position(|hash| hash == transaction_id);
+        if index_or_none.is_none() {
+            return Ok(None);
+        }
+
+        let version = MerkleVersion::for_block(self.header.block_id);
+        Ok(calculate_merkle_proof(&hashes, index_or_none.unwrap(), version))
+    }
+
     pub fn transactions(&self) -> Vec<TransactionVariant>
     {
         let mut transactions = Vec::new();
@@ -90,6 +153,12 @@ impl Block
         for page in &self.pages {
             transactions.push(TransactionVariant::Page(page.clone()));
         }
+        for site_transfer in &self.site_transfers {
+            transactions.push(TransactionVariant::SiteTransfer(site_transfer.clone()));
+        }
+        for name in &self.names {
+            transactions.push(TransactionVariant::NameRegistration(name.clone()));
+        }
 
         transactions
     }
@@ -4,10 +4,12 @@
  * SPDX-License-Identifier: BSD-2-Clause
  */
 
-use super::{Block, Hash, current_timestamp};
-use super::target::{calculate_target, hash_from_target};
+use super::{Block, BlockHeader, Hash, current_timestamp};
+use super::target::{calculate_target, calculate_target_for_headers, hash_from_target};
 use super::transactions::merkle_root_for_transactions;
+use super::storage_proof::StorageProofStatus;
 use crate::transaction::TransactionValidationResult;
+use crate::config::STORAGE_PROOF_DISCOUNT;
 
 use rsa::BigUint;
 use std::error::Error;
@@ -24,6 +26,11 @@ pub enum BlockValidationResult
     MerkleRoot,
     Transaction(TransactionValidationResult),
     Balance(Hash),
+    SiteOwner(Hash),
+    NameOwner(String),
+    PageCost(Hash),
+    StorageProof,
+    Header,
 }
 
 impl std::fmt::Display for BlockValidationResult
@@ -42,6 +49,85 @@ impl std::fmt::Display for BlockValidationResult
             BlockValidationResult::MerkleRoot => write!(f, "Incorrect merkle root"),
             BlockValidationResult::Transaction(result) => write!(f, "{}", result),
             BlockValidationResult::Balance(_) => write!(f, "Insufficient balance"),
+            BlockValidationResult::SiteOwner(_) => write!(f, "Not signed by the site owner"),
+            BlockValidationResult::NameOwner(_) => write!(f, "Not signed by the name owner"),
+            BlockValidationResult::PageCost(_) => write!(f, "Page doesn't pay for its new data"),
+            BlockValidationResult::StorageProof => write!(f, "Invalid storage proof"),
+            BlockValidationResult::Header => write!(f, "Does not match the known header"),
+        }
+    }
+
+}
+
+impl BlockHeader
+{
+
+    pub fn validate_next(&self, prev: &BlockHeader) 
+        -> Result<BlockValidationResult, Box<dyn Error>>
+    {
+        if self.block_id > 0
+        {
+            if self.block_id != prev.block_id + 1 {
+                return Ok(BlockValidationResult::NotNextBlock);
+            }
+
+            if self.prev_hash != prev.hash()? {
+                return Ok(BlockValidationResult::PrevHash);
+            }
+
+            let now = current_timestamp();
+            if self.timestamp < prev.timestamp || self.timestamp > now {
+                return Ok(BlockValidationResult::Timestamp);
+            }
+        }
+
+        Ok(BlockValidationResult::Ok)
+    }
+
+    /// Check the header has the full work for its target. Storage proofs
+    /// can't be checked from the header alone, so get no discount here.
+    pub fn validate_pow(&self) 
+        -> Result<BlockValidationResult, Box<dyn Error>>
+    {
+        self.validate_pow_with_discount(false)
+    }
+
+    /// Check the header has the work it claims, taking any storage proof
+    /// at its word. Only good for turning away blocks early, as the proof
+    /// still has to be checked before the block is.
+    pub fn validate_claimed_pow(&self) 
+        -> Result<BlockValidationResult, Box<dyn Error>>
+    {
+        self.validate_pow_with_discount(self.storage_proof.is_some())
+    }
+
+    /// Check the work, with less needed if the storage proof is proven.
+    pub fn validate_pow_with_discount(&self, is_storage_proven: bool) 
+        -> Result<BlockValidationResult, Box<dyn Error>>
+    {
+        let hash = self.hash()?;
+        let hash_num = BigUint::from_bytes_be(hash.data());
+        let mut target_num = BigUint::from_bytes_be(&hash_from_target(&self.target));
+        if is_storage_proven {
+            target_num *= STORAGE_PROOF_DISCOUNT;
+        }
+
+        if hash_num < target_num {
+            Ok(BlockValidationResult::Ok)
+        } else {
+            Ok(BlockValidationResult::POW)
+        }
+    }
+
+    pub fn validate_target(&self, 
+                           start_sample: Option<&BlockHeader>, 
+                           end_sample: Option<&BlockHeader>) 
+        -> BlockValidationResult
+    {
+        if self.target == calculate_target_for_headers(start_sample, end_sample) {
+            BlockValidationResult::Ok
+        } else {
+            BlockValidationResult::Target
         }
     }
 
@@ -53,7 +139,7 @@ impl Block
     fn validate_transactions(&self) 
         -> Result<BlockValidationResult, Box<dyn Error>>
     {
-        let merkle_root = merkle_root_for_transactions(&self.transfers, &self.pages)?;
+        let merkle_root = merkle_root_for_transactions(self.header.block_id, &self.transfers, &self.pages, &self.site_transfers, &self.names)?;
         if merkle_root != self.header.transaction_merkle_root {
             return Ok(BlockValidationResult::MerkleRoot);
         }
@@ -67,42 +153,37 @@ impl Block
             }
         }
 
-        Ok(BlockValidationResult::Ok)
-    }
-
-    pub fn validate_next(&self, prev: &Block) 
-        -> Result<BlockValidationResult, Box<dyn Error>>
-    {
-        if self.header.block_id > 0
+        for site_transfer in &self.site_transfers
         {
-            if self.header.block_id != prev.header.block_id + 1 {
-                return Ok(BlockValidationResult::NotNextBlock);
-            }
-
-            if self.header.prev_hash != prev.hash()? {
-                return Ok(BlockValidationResult::PrevHash);
+            match site_transfer.validate_content()?
+            {
+                TransactionValidationResult::Ok => {},
+                result => return Ok(BlockValidationResult::Transaction(result)),
             }
+        }
 
-            let now = current_timestamp();
-            if self.header.timestamp < prev.header.timestamp || self.header.timestamp > now {
-                return Ok(BlockValidationResult::Timestamp);
+        for name in &self.names
+        {
+            match name.validate_content()?
+            {
+                TransactionValidationResult::Ok => {},
+                result => return Ok(BlockValidationResult::Transaction(result)),
             }
         }
 
         Ok(BlockValidationResult::Ok)
     }
 
+    pub fn validate_next(&self, prev: &Block) 
+        -> Result<BlockValidationResult, Box<dyn Error>>
+    {
+        self.header.validate_next(&prev.header)
+    }
+
     pub fn validate_pow(&self) 
         -> Result<BlockValidationResult, Box<dyn Error>>
     {
-        let hash = self.hash()?;
-        let hash_num = BigUint::from_bytes_be(hash.data());
-        let target_num = BigUint::from_bytes_be(&hash_from_target(&self.header.target));
-        if hash_num < target_num {
-            Ok(BlockValidationResult::Ok)
-        } else {
-            Ok(BlockValidationResult::POW)
-        }
+        self.header.validate_pow()
     }
 
     pub fn validate_target(&self, 
@@ -117,12 +198,33 @@ impl Block
         }
     }
 
+    /// Check this is the block for a header we already trust, and that its
+    /// transactions are the ones the header commits to.
+    pub fn validate_against_header(&self, header: &BlockHeader)
+        -> Result<BlockValidationResult, Box<dyn Error>>
+    {
+        if &self.header != header {
+            return Ok(BlockValidationResult::Header);
+        }
+
+        self.validate_transactions()
+    }
+
+    /// Check everything that can be without the rest of the chain, given
+    /// the chunk root and count of every page before this block.
     pub fn validate_content(&self,
                             start_sample: Option<Block>, 
-                            end_sample: Option<Block>) 
+                            end_sample: Option<Block>,
+                            page_chunks: &[(Hash, u32)]) 
         -> Result<BlockValidationResult, Box<dyn Error>>
     {
-        match self.validate_pow()?
+        let storage_proof = self.validate_storage_proof(page_chunks);
+        if storage_proof == StorageProofStatus::Invalid {
+            return Ok(BlockValidationResult::StorageProof);
+        }
+
+        let is_storage_proven = storage_proof == StorageProofStatus::Proven;
+        match self.header.validate_pow_with_discount(is_storage_proven)?
         {
             BlockValidationResult::Ok => {},
             err => return Ok(err),
@@ -170,6 +272,7 @@ mod tests
                     .build())
                 .add_input(&wallet, 5.0)
                 .build().unwrap();
+        let transaction_id = transaction.hash().unwrap();
         let mut block = BlockBuilder::new(&wallet)
             .add_transfer(transaction)
             .build(&mut chain)
@@ -177,11 +280,11 @@ mod tests
 
         assert_ne!(block.validate_pow().unwrap(), BlockValidationResult::Ok);
         assert_eq!(block.validate_target(None, None), BlockValidationResult::Ok);
-        assert_ne!(block.validate_content(None, None).unwrap(), BlockValidationResult::Ok);
+        assert_ne!(block.validate_content(None, None, &[]).unwrap(), BlockValidationResult::Ok);
 
         block = miner::mine_block(block);
         assert_eq!(block.validate_pow().unwrap(), BlockValidationResult::Ok);
-        assert_eq!(block.validate_content(None, None).unwrap(), BlockValidationResult::Ok);
+        assert_eq!(block.validate_content(None, None, &[]).unwrap(), BlockValidationResult::Ok);
 
         {
             let mut wallet_status = WalletStatus::default();
@@ -197,6 +300,10 @@ mod tests
             assert_eq!(wallet_status.max_id, 0);
         }
 
+        let proof = block.transaction_proof(&transaction_id).unwrap().unwrap();
+        assert!(proof.verify(transaction_id.data(), &block.header.transaction_merkle_root));
+        assert_eq!(block.transaction_proof(&Hash::empty()).unwrap(), None);
+
         let addresses_used = block.get_addresses_used();
         assert_eq!(addresses_used.len(), 2);
         assert_eq!(addresses_used.contains(&wallet.get_address()), true);
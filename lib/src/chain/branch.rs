@@ -7,9 +7,13 @@
 use super::{BlockChain, BLOCK_SAMPLE_SIZE};
 use super::{BlockValidationResult, BlockChainAddResult};
 use crate::block::Block;
+use crate::transaction::{Transaction, TransactionContent};
+use crate::transaction::name::NameRecord;
+use crate::config::NAME_REGISTRATION_PERIOD;
 use crate::wallet::WalletStatus;
 use crate::hash::Hash;
 
+use serde::Serialize;
 use std::error::Error;
 use std::collections::HashMap;
 
@@ -20,6 +24,7 @@ pub enum BlockChainCanMergeResult
     Empty,
     Above,
     Short,
+    Pruned,
     Invalid(BlockValidationResult),
 }
 
@@ -57,6 +62,27 @@ impl BlockChain
         (sample_start, sample_end)
     }
 
+    fn is_signed_by_site_owner<C>(&mut self, transaction: &Transaction<C>,
+                                  site_owners: &mut HashMap<Hash, Hash>,
+                                  last_block_id: u64)
+            -> bool
+        where C: TransactionContent + Serialize
+    {
+        let site_or_none = transaction.header.content.get_site();
+        if site_or_none.is_none() {
+            return true;
+        }
+
+        let site = site_or_none.unwrap();
+        if !site_owners.contains_key(&site) 
+        {
+            let owner = self.get_site_owner_up_to_block(last_block_id, &site);
+            site_owners.insert(site, owner);
+        }
+
+        transaction.get_from_addresses().contains(&site_owners[&site])
+    }
+
     pub fn validate_branch(&mut self, branch: &[Block])
         -> Result<BlockValidationResult, Box<dyn Error>>
     {
@@ -70,6 +96,10 @@ impl BlockChain
 
         let mut last_block_or_none = self.block(last_block_id);
         let mut wallets = HashMap::<Hash, WalletStatus>::new();
+        let mut site_owners = HashMap::<Hash, Hash>::new();
+        let mut names = HashMap::<String, Option<NameRecord>>::new();
+        let mut page_chunks = self.get_page_chunks_before_block(bottom.header.block_id);
+        let mut page_chunk_counts = page_chunks.iter().copied().collect::<HashMap<_, _>>();
         for block in branch
         {
             for address in block.get_addresses_used()
@@ -88,6 +118,55 @@ impl BlockChain
                 *status = new_status;
             }
 
+            // NOTE: Pages are checked against the owners at the start of the 
+            //       block, then any site transfers are applied in order.
+            for page in &block.pages
+            {
+                if !self.is_signed_by_site_owner(page, &mut site_owners, last_block_id) {
+                    return Ok(BlockValidationResult::SiteOwner(page.header.content.site));
+                }
+
+                // Only chunks already on the chain can go unpaid for
+                if !page.header.content.is_reuse_valid(|root| page_chunk_counts.get(root).copied()) {
+                    return Ok(BlockValidationResult::PageCost(page.hash()?));
+                }
+            }
+
+            for site_transfer in &block.site_transfers
+            {
+                let content = &site_transfer.header.content;
+                if !self.is_signed_by_site_owner(site_transfer, &mut site_owners, last_block_id) {
+                    return Ok(BlockValidationResult::SiteOwner(content.site));
+                }
+                site_owners.insert(content.site, content.new_owner);
+            }
+
+            for name in &block.names
+            {
+                let content = &name.header.content;
+                if !names.contains_key(&content.name) 
+                {
+                    let record = self.get_name_up_to_block(last_block_id, &content.name);
+                    names.insert(content.name.clone(), record);
+                }
+
+                // Only names that haven't expired yet need their owner's signature
+                let block_id = block.header.block_id;
+                if let Some(record) = names[&content.name].as_ref().filter(|record| record.expires > block_id)
+                {
+                    if !name.get_from_addresses().contains(&record.owner) {
+                        return Ok(BlockValidationResult::NameOwner(content.name.clone()));
+                    }
+                }
+
+                names.insert(content.name.clone(), Some(NameRecord
+                {
+                    site: content.site,
+                    owner: content.owner,
+                    expires: block_id + NAME_REGISTRATION_PERIOD,
+                }));
+            }
+
             if last_block_or_none.is_some()
             {
                 let last_block = last_block_or_none.unwrap();
@@ -100,13 +179,23 @@ impl BlockChain
                     result => return Ok(result),
                 }
 
-                match block.validate_content(sample_start, sample_end)?
+                match block.validate_content(sample_start, sample_end, &page_chunks)?
                 {
                     BlockValidationResult::Ok => {},
                     result => return Ok(result),
                 }
             }
 
+            // NOTE: A block's storage proof and reused chunks can only come
+            //       from blocks before it, so pages in the same block sharing
+            //       new data each pay for it
+            for page in &block.pages 
+            {
+                let content = &page.header.content;
+                page_chunks.push((content.chunk_root, content.chunk_count));
+                page_chunk_counts.insert(content.chunk_root, content.chunk_count);
+            }
+
             last_block_or_none = Some( block.clone() );
         }
  
@@ -126,6 +215,14 @@ impl BlockChain
             return Ok(BlockChainCanMergeResult::Above);
         }
 
+        // Starts before the blocks we'd need to validate it
+        if let Some(oldest) = self.oldest_block_id()
+        {
+            if oldest > 0 && bottom.header.block_id <= oldest + BLOCK_SAMPLE_SIZE {
+                return Ok(BlockChainCanMergeResult::Pruned);
+            }
+        }
+
         // Not longer then the current branch
         let top = branch.last().unwrap();
         if top.header.block_id < self.blocks.next_top() as u64 {
@@ -147,6 +244,7 @@ impl BlockChain
         let bottom = branch.first().unwrap();
         self.metadata.truncate(bottom.header.block_id);
         self.blocks.truncate(bottom.header.block_id);
+        self.unindex_page_chunks_from(bottom.header.block_id);
 
         for block in branch {
             assert_eq!(self.add(&block).unwrap(), BlockChainAddResult::Ok);
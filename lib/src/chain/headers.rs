@@ -0,0 +1,293 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::storage::Storage;
+use super::{BlockChain, BLOCK_SAMPLE_SIZE};
+use crate::block::BlockHeader;
+use crate::block::validate::BlockValidationResult;
+use crate::hash::Hash;
+
+use std::path::PathBuf;
+use std::error::Error;
+
+#[derive(Debug, PartialEq)]
+pub enum HeaderChainAddResult
+{
+    Ok,
+    MoreNeeded,
+    Short,
+    Invalid(BlockValidationResult),
+}
+
+/// Ids and hashes of blocks going back from the top, close together at
+/// first then doubling the gap each time, ending at the first block. A node
+/// sent this can find where our chains split from the first one it has.
+pub fn block_locator<F>(next_top: u64, mut hash_at: F) -> Vec<(u64, Hash)>
+    where F: FnMut(u64) -> Option<Hash>
+{
+    let mut locator = Vec::new();
+    if next_top == 0 {
+        return locator;
+    }
+
+    let mut block_id = next_top - 1;
+    let mut step = 1;
+    loop
+    {
+        if let Some(hash) = hash_at(block_id) {
+            locator.push((block_id, hash));
+        }
+        if block_id == 0 {
+            break;
+        }
+
+        if locator.len() >= 10 {
+            step *= 2;
+        }
+        block_id = block_id.saturating_sub(step);
+    }
+
+    locator
+}
+
+/// Just the headers of the chain, for nodes that only want to follow the
+/// longest chain without storing every block.
+pub struct HeaderChain
+{
+    headers: Storage<BlockHeader>,
+}
+
+impl HeaderChain
+{
+
+    pub fn open(path: &PathBuf) -> Result<Self, Box<dyn Error>>
+    {
+        info!("Open header chain in {:?}", path);
+        Ok(Self
+        {
+            headers: Storage::new(path)?,
+        })
+    }
+
+    pub fn header(&mut self, block_id: u64) -> Option<BlockHeader>
+    {
+        if block_id >= self.headers.next_top() {
+            return None;
+        }
+
+        self.headers.get(block_id)
+    }
+
+    pub fn top(&mut self) -> Option<BlockHeader>
+    {
+        if self.headers.next_top() == 0 {
+            None
+        } else {
+            self.headers.get(self.headers.next_top() - 1)
+        }
+    }
+
+    /// Id of the next header we need.
+    pub fn next_top(&self) -> u64
+    {
+        self.headers.next_top()
+    }
+
+    pub fn locator(&mut self) -> Vec<(u64, Hash)>
+    {
+        block_locator(self.next_top(), |block_id| self.header(block_id)?.hash().ok())
+    }
+
+    fn take_sample_of_batch_at(&mut self, batch: &[BlockHeader], block_id: u64)
+        -> (Option<BlockHeader>, Option<BlockHeader>)
+    {
+        if block_id < BLOCK_SAMPLE_SIZE {
+            return (None, None);
+        }
+
+        let bottom_id = batch.first().unwrap().block_id;
+        let mut header_at = |block_id: u64|
+        {
+            if block_id >= bottom_id {
+                batch.get((block_id - bottom_id) as usize).cloned()
+            } else {
+                self.header(block_id)
+            }
+        };
+
+        let sample_start = header_at(block_id - BLOCK_SAMPLE_SIZE);
+        let sample_end = header_at(block_id);
+        (sample_start, sample_end)
+    }
+
+    /// Add a run of headers, replacing any of ours from the first one on if
+    /// they make a longer chain.
+    pub fn add(&mut self, headers: &[BlockHeader])
+        -> Result<HeaderChainAddResult, Box<dyn Error>>
+    {
+        if headers.is_empty() {
+            return Ok(HeaderChainAddResult::Short);
+        }
+
+        // Needs to follow on from a header we have
+        let bottom = headers.first().unwrap();
+        if bottom.block_id > self.headers.next_top() {
+            return Ok(HeaderChainAddResult::MoreNeeded);
+        }
+
+        let mut prev_or_none =
+            if bottom.block_id == 0 {
+                None
+            } else {
+                self.header(bottom.block_id - 1)
+            };
+
+        if let Some(prev) = &prev_or_none
+        {
+            if bottom.prev_hash != prev.hash()? {
+                return Ok(HeaderChainAddResult::MoreNeeded);
+            }
+        }
+
+        // Not longer than the chain we have
+        let top = headers.last().unwrap();
+        if top.block_id < self.headers.next_top() {
+            return Ok(HeaderChainAddResult::Short);
+        }
+
+        for header in headers
+        {
+            if let Some(prev) = &prev_or_none
+            {
+                match header.validate_next(prev)?
+                {
+                    BlockValidationResult::Ok => {},
+                    result => return Ok(HeaderChainAddResult::Invalid(result)),
+                }
+
+                let (sample_start, sample_end) = self.take_sample_of_batch_at(headers, prev.block_id);
+                match header.validate_target(sample_start.as_ref(), sample_end.as_ref())
+                {
+                    BlockValidationResult::Ok => {},
+                    result => return Ok(HeaderChainAddResult::Invalid(result)),
+                }
+            }
+
+            // NOTE: Storage proofs can't be checked without the block and
+            //       its chunks, so headers only count their full work
+            match header.validate_pow()?
+            {
+                BlockValidationResult::Ok => {},
+                result => return Ok(HeaderChainAddResult::Invalid(result)),
+            }
+
+            prev_or_none = Some(header.clone());
+        }
+
+        self.headers.truncate(bottom.block_id);
+        for header in headers {
+            self.headers.store(header.block_id, header.clone());
+        }
+
+        Ok(HeaderChainAddResult::Ok)
+    }
+
+}
+
+impl BlockChain
+{
+
+    /// NOTE: Pruned blocks are left out, so nodes we've forked from since
+    ///       before them will only find the first block in common.
+    pub fn locator(&mut self) -> Vec<(u64, Hash)>
+    {
+        block_locator(self.blocks.next_top(), |block_id| self.block(block_id)?.hash().ok())
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+    use crate::chain::BlockChain;
+    use crate::block::Block;
+    use crate::hash::Hash;
+    use crate::wallet::private_wallet::PrivateWallet;
+    use crate::miner;
+
+    fn mine_headers(chain: &mut BlockChain, wallet: &PrivateWallet, count: usize)
+        -> Vec<BlockHeader>
+    {
+        (0..count)
+            .map(|_|
+            {
+                let block = miner::mine_block(Block::new_blank(chain, wallet).unwrap());
+                chain.add(&block).unwrap();
+                block.header
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_header_chain()
+    {
+        let _ = pretty_env_logger::try_init();
+
+        let path = std::env::temp_dir().join(rand::random::<u32>().to_string());
+        let mut headers = HeaderChain::open(&path).unwrap();
+        let mut chain_a = BlockChain::open_temp();
+        let mut chain_b = BlockChain::open_temp();
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let other = PrivateWallet::open_temp(1).unwrap();
+
+        let shared = mine_headers(&mut chain_a, &wallet, 3);
+        for header in &shared {
+            chain_b.add(&chain_a.block(header.block_id).unwrap()).unwrap();
+        }
+        let headers_a = mine_headers(&mut chain_a, &wallet, 2);
+        let headers_b = mine_headers(&mut chain_b, &other, 3);
+
+        // Headers have to join onto what we have
+        assert_eq!(headers.add(&headers_a).unwrap(), HeaderChainAddResult::MoreNeeded);
+        assert_eq!(headers.add(&shared).unwrap(), HeaderChainAddResult::Ok);
+        assert_eq!(headers.add(&shared[1..]).unwrap(), HeaderChainAddResult::Short);
+
+        let mut tampered = headers_a.clone();
+        tampered[1].prev_hash = Hash::empty();
+        assert_eq!(headers.add(&tampered).unwrap(), HeaderChainAddResult::Invalid(BlockValidationResult::PrevHash));
+        assert_eq!(headers.add(&headers_a).unwrap(), HeaderChainAddResult::Ok);
+        assert_eq!(headers.top(), headers_a.last().cloned());
+
+        // A longer fork replaces our headers from where it branched off
+        assert_eq!(headers.add(&headers_b[..2]).unwrap(), HeaderChainAddResult::Short);
+        assert_eq!(headers.add(&headers_b).unwrap(), HeaderChainAddResult::Ok);
+        assert_eq!(headers.header(3), headers_b.first().cloned());
+        assert_eq!(headers.next_top(), 6);
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_block_locator()
+    {
+        assert_eq!(block_locator(0, |_| Some(Hash::empty())), Vec::new());
+
+        let ids = block_locator(100, |_| Some(Hash::empty()))
+            .into_iter()
+            .map(|(block_id, _)| block_id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![99, 98, 97, 96, 95, 94, 93, 92, 91, 90, 88, 84, 76, 60, 28, 0]);
+
+        let mut chain = BlockChain::open_temp();
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let headers = mine_headers(&mut chain, &wallet, 3);
+        assert_eq!(chain.locator().first(), Some(&(2, headers[2].hash().unwrap())));
+        assert_eq!(chain.locator().last(), Some(&(0, headers[0].hash().unwrap())));
+    }
+
+}
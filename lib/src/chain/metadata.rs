@@ -7,6 +7,8 @@
 use super::BlockChain;
 use crate::wallet::WalletStatus;
 use crate::block::Block;
+use crate::transaction::name::NameRecord;
+use crate::config::NAME_REGISTRATION_PERIOD;
 use crate::hash::Hash;
 
 use serde::{Serialize, Deserialize};
@@ -18,16 +20,29 @@ pub struct PageMetadata
     pub is_creation: bool,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct BlockMetadata
 {
     pub wallets: HashMap<Hash, WalletStatus>,
     pub page_updates: HashMap<Hash, PageMetadata>,
+
+    /// New owners of any sites transferred in this block
+    pub site_owners: HashMap<Hash, Hash>,
+
+    /// Names registered or renewed in this block
+    pub names: HashMap<String, NameRecord>,
 }
 
 impl BlockChain
 {
 
+    /// Blocks before an imported snapshot have no metadata of their own, so
+    /// are treated as having changed nothing.
+    pub fn block_metadata(&mut self, block_id: u64) -> BlockMetadata
+    {
+        self.metadata.get(block_id).unwrap_or_default()
+    }
+
     pub fn metadata_for_block(&mut self, block: &Block) -> BlockMetadata
     {
         // NOTE: We assume the block is valid at this point
@@ -43,17 +58,38 @@ impl BlockChain
         let mut page_updates = HashMap::new();
         for page in &block.pages 
         {
-            let is_creation = self.last_page_update(&page.header.content.site).is_none();
+            let is_creation = !self.has_page_updates(&page.header.content.site);
             page_updates.insert(page.header.content.site, PageMetadata
             {
                 is_creation,
             });
         }
 
+        let mut site_owners = HashMap::new();
+        for site_transfer in &block.site_transfers
+        {
+            let content = &site_transfer.header.content;
+            site_owners.insert(content.site, content.new_owner);
+        }
+
+        let mut names = HashMap::new();
+        for name in &block.names
+        {
+            let content = &name.header.content;
+            names.insert(content.name.clone(), NameRecord
+            {
+                site: content.site,
+                owner: content.owner,
+                expires: block.header.block_id + NAME_REGISTRATION_PERIOD,
+            });
+        }
+
         BlockMetadata
         {
             wallets,
             page_updates,
+            site_owners,
+            names,
         }
     }
 
@@ -5,6 +5,9 @@
  */
 
 pub mod branch;
+pub mod headers;
+pub mod prune;
+pub mod snapshot;
 mod transaction_queue;
 mod storage;
 mod transactions;
@@ -13,10 +16,14 @@ use storage::Storage;
 use metadata::BlockMetadata;
 use crate::block::Block;
 use crate::block::validate::BlockValidationResult;
+use crate::transaction::Transaction;
 use crate::transaction::transfer::Transfer;
 use crate::transaction::page::Page;
+use crate::transaction::site_transfer::SiteTransfer;
+use crate::transaction::name::NameRegistration;
 use crate::transaction_queue::TransactionQueue;
 use crate::config::BLOCK_SAMPLE_SIZE;
+use crate::hash::Hash;
 
 use std::error::Error;
 use std::path::PathBuf;
@@ -26,8 +33,19 @@ pub struct BlockChain
     metadata: Storage<BlockMetadata>,
     blocks: Storage<Block>,
 
+    /// Pages of blocks that have been pruned
+    pruned_pages: Storage<Vec<Transaction<Page>>>,
+    prune_depth: Option<u64>,
+
+    /// Block id, chunk root and chunk count of each page on the chain.
+    /// Built the first time it's needed, then kept up to date as blocks
+    /// are added and replaced.
+    page_chunks: Option<Vec<(u64, Hash, u32)>>,
+
     transfer_queue: TransactionQueue<Transfer>,
     page_queue: TransactionQueue<Page>,
+    site_transfer_queue: TransactionQueue<SiteTransfer>,
+    name_queue: TransactionQueue<NameRegistration>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -50,9 +68,14 @@ impl BlockChain
         {
             metadata: Storage::new(&path.join("metadata"))?,
             blocks: Storage::new(path)?,
+            pruned_pages: Storage::new(&path.join("pruned_pages"))?,
+            prune_depth: None,
+            page_chunks: None,
 
             page_queue: TransactionQueue::new(),
             transfer_queue: TransactionQueue::new(),
+            site_transfer_queue: TransactionQueue::new(),
+            name_queue: TransactionQueue::new(),
         })
     }
 
@@ -81,8 +104,13 @@ impl BlockChain
     {
         if block.header.block_id < self.blocks.next_top() as u64
         {
-            let original = self.block(block.header.block_id).unwrap();
-            if block == &original {
+            // NOTE: Pruned blocks can't be replaced, so are treated the same
+            let original_or_none = self.block(block.header.block_id);
+            if original_or_none.is_none() {
+                return Ok(BlockChainAddResult::Duplicate);
+            }
+
+            if block == &original_or_none.unwrap() {
                 return Ok(BlockChainAddResult::Duplicate);
             } else {
                 return Ok(BlockChainAddResult::Invalid(BlockValidationResult::NotNextBlock));
@@ -104,6 +132,8 @@ impl BlockChain
                 // NOTE: Purge any pending transfers coming from this address
                 self.transfer_queue.remove_from_address(&address);
                 self.page_queue.remove_from_address(&address);
+                self.site_transfer_queue.remove_from_address(&address);
+                self.name_queue.remove_from_address(&address);
 
                 return Ok(BlockChainAddResult::Invalid(BlockValidationResult::Balance(address)));
             },
@@ -113,15 +143,21 @@ impl BlockChain
         let metadata = self.metadata_for_block(&block);
         self.metadata.store(block.header.block_id, metadata);
         self.blocks.store(block.header.block_id, block.clone());
+        self.index_page_chunks(block);
         self.remove_from_transaction_queue(block);
+        self.prune_old_blocks();
         Ok(BlockChainAddResult::Ok)
     }
 
+    /// Call `on_block` for every block we have, skipping any pruned ones.
     pub fn walk<F>(&mut self, on_block: &mut F)
         where F: FnMut(&Block)
     {
-        for block_id in 0..self.blocks.next_top() {
-            on_block(&self.block(block_id).unwrap());
+        for block_id in 0..self.blocks.next_top() 
+        {
+            if let Some(block) = self.block(block_id) {
+                on_block(&block);
+            }
         }
     }
 
@@ -139,6 +175,12 @@ impl BlockChain
         }
     }
 
+    /// Id of the next block to be added.
+    pub fn next_top(&self) -> u64
+    {
+        self.blocks.next_top()
+    }
+
 }
 
 #[cfg(test)]
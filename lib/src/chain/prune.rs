@@ -0,0 +1,169 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::{BlockChain, BLOCK_SAMPLE_SIZE};
+use crate::transaction::Transaction;
+use crate::transaction::page::Page;
+
+/// Enough blocks to work out the target of the next one.
+pub const MIN_PRUNE_DEPTH: u64 = BLOCK_SAMPLE_SIZE + 1;
+
+impl BlockChain
+{
+
+    /// Only keep the last `depth` full blocks, or every block if none. Older
+    /// blocks are dropped except for their pages, which are needed to build
+    /// sites, while their metadata is kept for validation.
+    ///
+    /// NOTE: Forks from before the blocks we have can't be followed.
+    pub fn set_prune_depth(&mut self, depth: Option<u64>)
+    {
+        self.prune_depth = depth.map(|depth| std::cmp::max(depth, MIN_PRUNE_DEPTH));
+        self.prune_old_blocks();
+    }
+
+    pub fn is_pruned(&mut self, block_id: u64) -> bool
+    {
+        block_id < self.blocks.next_top() && self.blocks.get(block_id).is_none()
+    }
+
+    /// Whether every full block is kept, so they can all be given to other
+    /// nodes. Chains set to prune, or started from a snapshot, don't.
+    pub fn has_all_blocks(&mut self) -> bool
+    {
+        self.prune_depth.is_none() && !self.is_pruned(0)
+    }
+
+    /// Id of the oldest full block we have.
+    pub fn oldest_block_id(&mut self) -> Option<u64>
+    {
+        if self.blocks.next_top() == 0 {
+            return None;
+        }
+
+        // NOTE: Blocks are only ever pruned from the bottom up
+        let (mut low, mut high) = (0, self.blocks.next_top() - 1);
+        while low < high
+        {
+            let middle = (low + high) / 2;
+            if self.blocks.get(middle).is_some() {
+                high = middle;
+            } else {
+                low = middle + 1;
+            }
+        }
+
+        Some(low)
+    }
+
+    /// The pages confirmed in a block, even if it's been pruned.
+    pub fn block_pages(&mut self, block_id: u64) -> Vec<Transaction<Page>>
+    {
+        match self.blocks.get(block_id)
+        {
+            Some(block) => block.pages,
+            None => self.pruned_pages.get(block_id).unwrap_or_default(),
+        }
+    }
+
+    pub(super) fn prune_old_blocks(&mut self)
+    {
+        let next_top = self.blocks.next_top();
+        let oldest_or_none = self.oldest_block_id();
+        if self.prune_depth.is_none() || oldest_or_none.is_none() {
+            return;
+        }
+
+        let depth = self.prune_depth.unwrap();
+        for block_id in oldest_or_none.unwrap()..next_top.saturating_sub(depth)
+        {
+            let block = self.blocks.get(block_id).unwrap();
+            if !block.pages.is_empty() {
+                self.pruned_pages.store(block_id, block.pages);
+            }
+
+            debug!("Pruned block {}", block_id);
+            self.blocks.remove(block_id);
+        }
+    }
+
+}
+
+#[cfg(test)]
+pub mod tests
+{
+
+    use super::*;
+    use crate::chain::BlockChainAddResult;
+    use crate::chain::branch::BlockChainCanMergeResult;
+    use crate::block::{Block, current_timestamp};
+    use crate::wallet::Wallet;
+    use crate::wallet::private_wallet::PrivateWallet;
+    use crate::data_store::data_unit::DataUnit;
+    use crate::data_store::page::CreatePageData;
+    use crate::config::BLOCK_TIME;
+    use crate::miner;
+
+    /// Mine blocks a block time apart, starting far enough in the past that
+    /// the target doesn't go up.
+    pub fn mine_spaced_blocks(chain: &mut BlockChain, wallet: &PrivateWallet, count: u64)
+        -> Vec<Block>
+    {
+        let start = current_timestamp() - (1000 * BLOCK_TIME) as u128;
+        (0..count)
+            .map(|_|
+            {
+                let mut block = Block::new_blank(chain, wallet).unwrap();
+                block.header.timestamp = start + (block.header.block_id * BLOCK_TIME) as u128;
+                block = miner::mine_block(block);
+                assert_eq!(chain.add(&block).unwrap(), BlockChainAddResult::Ok);
+                block
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pruned_chain()
+    {
+        let _ = pretty_env_logger::try_init();
+
+        let mut chain = BlockChain::open_temp();
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+
+        let blocks = mine_spaced_blocks(&mut chain, &wallet, 2);
+        let data_unit = DataUnit::CreatePage(CreatePageData::new("index.html".to_owned(), b"<p>Hi</p>".to_vec()));
+        let page = chain.new_page(&wallet, &data_unit, 0.0).unwrap();
+        chain.push_page_queue(page.clone()).unwrap();
+        let mut block = Block::new(&mut chain, &wallet, Vec::new(), vec![page.clone()], Vec::new(), Vec::new()).unwrap();
+        block.header.timestamp = blocks[1].header.timestamp + BLOCK_TIME as u128;
+        assert_eq!(chain.add(&miner::mine_block(block)).unwrap(), BlockChainAddResult::Ok);
+
+        let status = chain.get_wallet_status(&wallet.get_address());
+        mine_spaced_blocks(&mut chain, &wallet, MIN_PRUNE_DEPTH + 2);
+        let status_after = chain.get_wallet_status(&wallet.get_address());
+        assert_eq!(chain.has_all_blocks(), true);
+
+        // Only the most recent blocks are kept
+        chain.set_prune_depth(Some(1));
+        assert_eq!(chain.has_all_blocks(), false);
+        assert_eq!(chain.oldest_block_id(), Some(5));
+        assert_eq!(chain.is_pruned(2), true);
+        assert_eq!(chain.block(2), None);
+        assert_eq!(chain.block_pages(2), vec![page.clone()]);
+
+        // Everything needed to validate new blocks is still there
+        assert_eq!(chain.get_wallet_status_up_to_block(2, &wallet.get_address()), status);
+        assert_eq!(chain.get_page_updates(&wallet.get_address()), vec![page]);
+        mine_spaced_blocks(&mut chain, &wallet, 1);
+        assert_eq!(chain.oldest_block_id(), Some(6));
+        assert_ne!(chain.get_wallet_status(&wallet.get_address()), status_after);
+
+        // Old blocks can't be replaced
+        assert_eq!(chain.add(&blocks[0]).unwrap(), BlockChainAddResult::Duplicate);
+        assert_eq!(chain.can_merge_branch(&blocks[1..]).unwrap(), BlockChainCanMergeResult::Pruned);
+    }
+
+}
@@ -0,0 +1,312 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::BlockChain;
+use super::prune::MIN_PRUNE_DEPTH;
+use super::metadata::{BlockMetadata, PageMetadata};
+use crate::block::{Block, BlockHeader};
+use crate::block::validate::BlockValidationResult;
+use crate::transaction::Transaction;
+use crate::transaction::page::Page;
+use crate::transaction::name::NameRecord;
+use crate::wallet::WalletStatus;
+use crate::error::ErrorMessage;
+use crate::hash::Hash;
+
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use std::collections::HashMap;
+use std::path::Path;
+use std::fs::File;
+use std::error::Error;
+
+/// The chain's state as of some block, so new nodes can start from there
+/// instead of replaying every block.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChainSnapshot
+{
+    /// State as of the block before the first one in `blocks`
+    pub wallets: Vec<(Hash, WalletStatus)>,
+    pub site_owners: Vec<(Hash, Hash)>,
+    pub names: Vec<(String, NameRecord)>,
+
+    /// Every page before `blocks`, oldest first
+    pub pages: Vec<Transaction<Page>>,
+
+    /// The most recent blocks, enough to validate the ones after them
+    pub blocks: Vec<Block>,
+    pub top: BlockHeader,
+
+    /// Hash of everything above
+    pub commitment: Hash,
+}
+
+fn sorted<T>(map: HashMap<Hash, T>) -> Vec<(Hash, T)>
+{
+    let mut items = map.into_iter().collect::<Vec<_>>();
+    items.sort_by(|(a, _), (b, _)| a.data().cmp(b.data()));
+    items
+}
+
+impl ChainSnapshot
+{
+
+    fn calculate_commitment(&self) -> Result<Hash, Box<dyn Error>>
+    {
+        let mut hasher = Sha256::default();
+        hasher.update(bincode::serialize(&self.wallets)?);
+        hasher.update(bincode::serialize(&self.site_owners)?);
+        hasher.update(bincode::serialize(&self.names)?);
+        hasher.update(bincode::serialize(&self.pages)?);
+        for block in &self.blocks {
+            hasher.update(block.hash()?.data());
+        }
+        hasher.update(self.top.hash()?.data());
+        Ok(Hash::from(&hasher.finalize()))
+    }
+
+    /// Check the snapshot is consistent with itself, and is the one with
+    /// `trusted_commitment`. A snapshot can be made up from scratch, so the 
+    /// commitment has to come from somewhere we trust, like a node that's
+    /// synced the whole chain.
+    pub fn verify(&self, trusted_commitment: &Hash) -> Result<(), Box<dyn Error>>
+    {
+        if &self.commitment != trusted_commitment {
+            return Err(ErrorMessage::new("Snapshot isn't the one trusted"));
+        }
+
+        if self.blocks.last().map(|block| &block.header) != Some(&self.top) {
+            return Err(ErrorMessage::new("Snapshot blocks don't end at its top"));
+        }
+
+        let mut prev_or_none: Option<&Block> = None;
+        for block in &self.blocks
+        {
+            if let Some(prev) = prev_or_none
+            {
+                if block.validate_next(prev)? != BlockValidationResult::Ok {
+                    return Err(ErrorMessage::new(&format!("Snapshot block {} doesn't follow on", block.header.block_id)));
+                }
+            }
+
+            // NOTE: Snapshots come without page data, so storage proofs are
+            //       taken as claimed. The commitment covers them.
+            if block.header.validate_claimed_pow()? != BlockValidationResult::Ok ||
+               block.validate_against_header(&block.header)? != BlockValidationResult::Ok
+            {
+                return Err(ErrorMessage::new(&format!("Snapshot block {} is invalid", block.header.block_id)));
+            }
+            prev_or_none = Some(block);
+        }
+
+        if self.calculate_commitment()? != self.commitment {
+            return Err(ErrorMessage::new("Snapshot commitment doesn't match"));
+        }
+
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>>
+    {
+        let file = File::create(path)?;
+        bincode::serialize_into(file, self)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>>
+    {
+        let file = File::open(path)?;
+        Ok(bincode::deserialize_from(file)?)
+    }
+
+}
+
+impl BlockChain
+{
+
+    /// Snapshot of the chain up to and including `top_id`.
+    pub fn snapshot_at(&mut self, top_id: u64) -> Result<ChainSnapshot, Box<dyn Error>>
+    {
+        if top_id >= self.blocks.next_top() {
+            return Err(ErrorMessage::new("No block to take a snapshot at"));
+        }
+
+        let first_block_id = (top_id + 1).saturating_sub(MIN_PRUNE_DEPTH);
+        let mut blocks = Vec::new();
+        for block_id in first_block_id..=top_id
+        {
+            let block_or_none = self.block(block_id);
+            if block_or_none.is_none() {
+                return Err(ErrorMessage::new("Blocks for the snapshot have been pruned"));
+            }
+            blocks.push(block_or_none.unwrap());
+        }
+
+        let mut wallets = HashMap::new();
+        let mut site_owners = HashMap::new();
+        let mut names = HashMap::new();
+        let mut pages = Vec::new();
+        for block_id in 0..first_block_id
+        {
+            let metadata = self.block_metadata(block_id);
+            wallets.extend(metadata.wallets);
+            site_owners.extend(metadata.site_owners);
+            names.extend(metadata.names);
+            if !metadata.page_updates.is_empty() {
+                pages.extend(self.block_pages(block_id));
+            }
+        }
+
+        let mut names = names.into_iter().collect::<Vec<_>>();
+        names.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut snapshot = ChainSnapshot
+        {
+            wallets: sorted(wallets),
+            site_owners: sorted(site_owners),
+            names,
+            pages,
+            top: blocks.last().unwrap().header.clone(),
+            blocks,
+            commitment: Hash::empty(),
+        };
+        snapshot.commitment = snapshot.calculate_commitment()?;
+        Ok(snapshot)
+    }
+
+    pub fn snapshot(&mut self) -> Result<ChainSnapshot, Box<dyn Error>>
+    {
+        if self.blocks.next_top() == 0 {
+            return Err(ErrorMessage::new("No block to take a snapshot at"));
+        }
+
+        self.snapshot_at(self.blocks.next_top() - 1)
+    }
+
+    /// Check a snapshot matches our own chain.
+    pub fn verify_snapshot(&mut self, snapshot: &ChainSnapshot) -> Result<(), Box<dyn Error>>
+    {
+        let commitment = self.snapshot_at(snapshot.top.block_id)?.commitment;
+        if snapshot.verify(&commitment).is_err() {
+            return Err(ErrorMessage::new("Snapshot doesn't match our chain"));
+        }
+
+        Ok(())
+    }
+
+    /// Start an empty chain from a snapshot with the commitment we trust.
+    /// Everything before its blocks is stored as if it all happened in the
+    /// block before them, with their pages kept as though it had been 
+    /// pruned.
+    pub fn import_snapshot(&mut self, snapshot: &ChainSnapshot, trusted_commitment: &Hash) 
+        -> Result<(), Box<dyn Error>>
+    {
+        if self.blocks.next_top() != 0 {
+            return Err(ErrorMessage::new("Can only import a snapshot into an empty chain"));
+        }
+        snapshot.verify(trusted_commitment)?;
+
+        let first_block_id = snapshot.blocks.first().unwrap().header.block_id;
+        if first_block_id > 0
+        {
+            let base_id = first_block_id - 1;
+            let page_updates = snapshot.pages.iter()
+                .map(|page| (page.header.content.site, PageMetadata { is_creation: true }))
+                .collect();
+
+            self.metadata.store(base_id, BlockMetadata
+            {
+                wallets: snapshot.wallets.iter().cloned().collect(),
+                page_updates,
+                site_owners: snapshot.site_owners.iter().cloned().collect(),
+                names: snapshot.names.iter().cloned().collect(),
+            });
+            self.pruned_pages.store(base_id, snapshot.pages.clone());
+            self.blocks.truncate(first_block_id);
+        }
+
+        // NOTE: These were checked by `verify`, and their state follows on
+        //       from the snapshot's
+        for block in &snapshot.blocks
+        {
+            let metadata = self.metadata_for_block(block);
+            self.metadata.store(block.header.block_id, metadata);
+            self.blocks.store(block.header.block_id, block.clone());
+        }
+
+        self.page_chunks = None;
+        info!("Imported snapshot up to block {}", snapshot.top.block_id);
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+    use crate::chain::prune::tests::mine_spaced_blocks;
+    use crate::wallet::Wallet;
+    use crate::wallet::private_wallet::PrivateWallet;
+
+    #[test]
+    fn test_snapshot()
+    {
+        let _ = pretty_env_logger::try_init();
+
+        let mut chain = BlockChain::open_temp();
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let other = PrivateWallet::open_temp(1).unwrap();
+        mine_spaced_blocks(&mut chain, &other, 2);
+        mine_spaced_blocks(&mut chain, &wallet, MIN_PRUNE_DEPTH + 1);
+
+        let snapshot = chain.snapshot().unwrap();
+        assert_eq!(snapshot.blocks.len() as u64, MIN_PRUNE_DEPTH);
+        assert_eq!(snapshot.wallets.len(), 2);
+        assert!(chain.verify_snapshot(&snapshot).is_ok());
+
+        let path = std::env::temp_dir().join(format!("{}.snapshot", rand::random::<u32>()));
+        snapshot.save(&path).unwrap();
+        let loaded = ChainSnapshot::load(&path).unwrap();
+        assert_eq!(loaded, snapshot);
+        let _ = std::fs::remove_file(path);
+
+        let commitment = snapshot.commitment;
+        assert!(snapshot.verify(&commitment).is_ok());
+
+        // Anyone can make a snapshot that agrees with itself, so only the 
+        // trusted one is accepted
+        let mut tampered = snapshot.clone();
+        tampered.wallets[0].1.balance += 1.0;
+        tampered.commitment = tampered.calculate_commitment().unwrap();
+        assert!(tampered.verify(&tampered.commitment).is_ok());
+        assert!(tampered.verify(&commitment).is_err());
+        assert!(chain.verify_snapshot(&tampered).is_err());
+        assert!(BlockChain::open_temp().import_snapshot(&tampered, &commitment).is_err());
+
+        let mut mislabeled = tampered.clone();
+        mislabeled.commitment = commitment;
+        assert!(mislabeled.verify(&commitment).is_err());
+
+        // A node started from the snapshot has the same state, without the
+        // older blocks
+        let mut imported = BlockChain::open_temp();
+        imported.import_snapshot(&snapshot, &commitment).unwrap();
+        assert_eq!(imported.top(), chain.top());
+        assert_eq!(imported.block(1), None);
+        for address in [wallet.get_address(), other.get_address()] {
+            assert_eq!(imported.get_wallet_status(&address), chain.get_wallet_status(&address));
+        }
+        assert_eq!(imported.snapshot().unwrap(), snapshot);
+        assert!(imported.import_snapshot(&snapshot, &commitment).is_err());
+
+        // And can carry on from there
+        let block = mine_spaced_blocks(&mut chain, &wallet, 1).remove(0);
+        assert_eq!(imported.add(&block).unwrap(), crate::chain::BlockChainAddResult::Ok);
+    }
+
+}
@@ -160,6 +160,15 @@ impl<T> Storage<T>
         self.store_chunk(chunk_id, chunk);
     }
 
+    pub fn remove(&mut self, block_id: u64)
+    {
+        let chunk_id = block_id as usize / CHUNK_SIZE;
+        let index = block_id as usize % CHUNK_SIZE;
+        let mut chunk = self.get_chunk(chunk_id);
+        chunk.data[index] = None;
+        self.store_chunk(chunk_id, chunk);
+    }
+
     pub fn truncate(&mut self, new_size: u64)
     {
         self.metadata.next_top = new_size;
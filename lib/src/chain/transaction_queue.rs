@@ -10,14 +10,20 @@ use crate::transaction::{Transaction, TransactionVariant};
 use crate::transaction::{TransactionContent, TransactionValidationResult};
 use crate::transaction::transfer::{Transfer, TransferBuilder};
 use crate::transaction::page::Page;
+use crate::transaction::site_transfer::SiteTransfer;
+use crate::transaction::name::NameRegistration;
 use crate::transaction::builder::TransactionBuilder;
+use crate::transaction::unsigned::UnsignedTransaction;
 use crate::wallet::{Wallet, WalletStatus};
 use crate::wallet::private_wallet::PrivateWallet;
+use crate::wallet::public_wallet::PublicWallet;
 use crate::data_store::data_unit::DataUnit;
 use crate::error::ErrorMessage;
 use crate::hash::Hash;
 
 use serde::Serialize;
+use std::collections::HashMap;
+use std::rc::Rc;
 use std::error::Error;
 
 impl BlockChain
@@ -28,9 +34,37 @@ impl BlockChain
         let mut status = self.get_wallet_status(address);
         status = self.transfer_queue.update_wallet_status(address, status).unwrap();
         status = self.page_queue.update_wallet_status(address, status).unwrap();
+        status = self.site_transfer_queue.update_wallet_status(address, status).unwrap();
+        status = self.name_queue.update_wallet_status(address, status).unwrap();
         status
     }
 
+    fn get_site_owner_after_queue(&mut self, site: &Hash) -> Hash
+    {
+        let mut owner = self.get_site_owner(site);
+        for site_transfer in self.site_transfer_queue.transactions()
+        {
+            if &site_transfer.header.content.site == site {
+                owner = site_transfer.header.content.new_owner;
+            }
+        }
+
+        owner
+    }
+
+    fn get_name_owner_after_queue(&mut self, name: &str) -> Option<Hash>
+    {
+        let mut owner = self.get_name(name).map(|record| record.owner);
+        for registration in self.name_queue.transactions()
+        {
+            if registration.header.content.name == name {
+                owner = Some(registration.header.content.owner);
+            }
+        }
+
+        owner
+    }
+
     fn new_transaction<C>(&mut self, inputs: Vec<(&PrivateWallet, f32)>, content: C)
             -> Result<Transaction<C>, Box<dyn Error>>
         where C: TransactionContent + Serialize
@@ -54,42 +88,105 @@ impl BlockChain
         Ok(transaction)
     }
 
-    fn next_transaction_id(&mut self, inputs: &Vec<(&PrivateWallet, f32)>) -> u32
+    pub fn next_transaction_id(&mut self, addresses: &[Hash]) -> u32
     {
         let mut max_id = 0;
-        for (wallet, _) in inputs
+        for address in addresses
         {
-            let status = self.get_wallet_status_after_queue(&wallet.get_address());
+            let status = self.get_wallet_status_after_queue(address);
             max_id = std::cmp::max(max_id, status.max_id);
         }
 
         max_id + 1
     }
 
+    fn new_transfer_content(&mut self, addresses: &[Hash],
+                            outputs: Vec<(Hash, f32)>, fee: f32)
+        -> Transfer
+    {
+        let id = self.next_transaction_id(addresses);
+        let mut transfer_builder = TransferBuilder::new(id, fee);
+        for (to, amount) in outputs {
+            transfer_builder = transfer_builder.add_output(to, amount);
+        }
+
+        transfer_builder.build()
+    }
+
     pub fn new_transfer(&mut self, 
                         inputs: Vec<(&PrivateWallet, f32)>, 
                         outputs: Vec<(Hash, f32)>, 
                         fee: f32)
         -> Result<Transaction<Transfer>, Box<dyn Error>>
     {
-        let id = self.next_transaction_id(&inputs);
-        let mut transfer_builder = TransferBuilder::new(id, fee);
-        for (to, amount) in outputs {
-            transfer_builder = transfer_builder.add_output(to, amount);
+        let addresses = inputs.iter().map(|(x, _)| x.get_address()).collect::<Vec<_>>();
+        let transfer = self.new_transfer_content(&addresses, outputs, fee);
+        self.new_transaction(inputs, transfer)
+    }
+
+    pub fn new_unsigned_transfer(&mut self,
+                                 inputs: Vec<(&PublicWallet, f32)>,
+                                 outputs: Vec<(Hash, f32)>,
+                                 fee: f32)
+        -> Result<UnsignedTransaction<Transfer>, Box<dyn Error>>
+    {
+        let addresses = inputs.iter().map(|(x, _)| x.get_address()).collect::<Vec<_>>();
+        let transfer = self.new_transfer_content(&addresses, outputs, fee);
+
+        let mut builder = TransactionBuilder::new(transfer);
+        for (wallet, amount) in &inputs {
+            builder = builder.add_unsigned_input(wallet, *amount)?;
+        }
+
+        // NOTE: We can't check the signatures yet, but we can make sure the 
+        //       amounts add up and the inputs can afford it.
+        let unsigned = builder.build_unsigned()?;
+        if unsigned.header.content.validate(&unsigned.header.inputs)? != TransactionValidationResult::Ok {
+            return Err(ErrorMessage::new("Invalid content"));
         }
 
-        self.new_transaction(inputs, transfer_builder.build())
+        self.is_transaction_valid(&Transaction::new(unsigned.header.clone(), Default::default()))?;
+        Ok(unsigned)
     }
 
     pub fn new_page(&mut self, from: &PrivateWallet, data: &DataUnit, fee: f32)
         -> Result<Transaction<Page>, Box<dyn Error>>
+    {
+        self.new_page_for_site(from, &from.get_address(), data, |_| None, fee)
+    }
+
+    /// Create a page, not paying for the chunks `find_chunk` finds in pages
+    /// already on the chain.
+    pub fn new_page_for_site<F>(&mut self, from: &PrivateWallet, site: &Hash,
+                                data: &DataUnit, find_chunk: F, fee: f32)
+            -> Result<Transaction<Page>, Box<dyn Error>>
+        where F: Fn(&Hash) -> Option<(Hash, u32, Rc<Vec<Hash>>)>
     {
         let status = self.get_wallet_status_after_queue(&from.get_address());
-        let page = Page::new_from_data(status.max_id + 1, from.get_address(), data, fee)?;
+        let page = Page::new_from_data(status.max_id + 1, *site, data, find_chunk, fee)?;
         let total_output = page.cost() + fee;
         self.new_transaction(vec![(from, total_output)], page)
     }
 
+    pub fn new_site_transfer(&mut self, from: &PrivateWallet, site: &Hash,
+                             new_owner: &Hash, fee: f32)
+        -> Result<Transaction<SiteTransfer>, Box<dyn Error>>
+    {
+        let status = self.get_wallet_status_after_queue(&from.get_address());
+        let site_transfer = SiteTransfer::new(status.max_id + 1, *site, *new_owner, fee);
+        self.new_transaction(vec![(from, fee)], site_transfer)
+    }
+
+    pub fn new_name_registration(&mut self, from: &PrivateWallet, name: &str,
+                                 site: &Hash, owner: &Hash, fee: f32)
+        -> Result<Transaction<NameRegistration>, Box<dyn Error>>
+    {
+        let status = self.get_wallet_status_after_queue(&from.get_address());
+        let registration = NameRegistration::new(status.max_id + 1, 
+            name.to_owned(), *site, *owner, fee);
+        self.new_transaction(vec![(from, fee)], registration)
+    }
+
     fn is_transaction_valid<C>(&mut self, transaction: &Transaction<C>) -> Result<(), Box<dyn Error>>
         where C: TransactionContent + Serialize
     {
@@ -106,6 +203,24 @@ impl BlockChain
             }
         }
 
+        if let Some(site) = transaction.header.content.get_site()
+        {
+            let owner = self.get_site_owner_after_queue(&site);
+            if !transaction.get_from_addresses().contains(&owner) {
+                return Err(ErrorMessage::new("Not signed by the site owner"));
+            }
+        }
+
+        if let Some(name) = transaction.header.content.get_name()
+        {
+            if let Some(owner) = self.get_name_owner_after_queue(&name)
+            {
+                if !transaction.get_from_addresses().contains(&owner) {
+                    return Err(ErrorMessage::new("Name is owned by someone else"));
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -121,10 +236,32 @@ impl BlockChain
         -> Result<(), Box<dyn Error>>
     {
         self.is_transaction_valid(&transaction)?;
+
+        let page_chunk_counts = self.get_page_chunks().into_iter().collect::<HashMap<_, _>>();
+        if !transaction.header.content.is_reuse_valid(|root| page_chunk_counts.get(root).copied()) {
+            return Err(ErrorMessage::new("Doesn't pay for new data"));
+        }
+
         self.page_queue.push(transaction)?;
         Ok(())
     }
 
+    pub fn push_site_transfer_queue(&mut self, transaction: Transaction<SiteTransfer>) 
+        -> Result<(), Box<dyn Error>>
+    {
+        self.is_transaction_valid(&transaction)?;
+        self.site_transfer_queue.push(transaction)?;
+        Ok(())
+    }
+
+    pub fn push_name_queue(&mut self, transaction: Transaction<NameRegistration>) 
+        -> Result<(), Box<dyn Error>>
+    {
+        self.is_transaction_valid(&transaction)?;
+        self.name_queue.push(transaction)?;
+        Ok(())
+    }
+
     pub fn get_next_transfers_in_queue(&self, count: usize) 
         -> impl Iterator<Item = &Transaction<Transfer>>
     {
@@ -137,10 +274,54 @@ impl BlockChain
         self.page_queue.get_next(count)
     }
 
+    pub fn get_next_site_transfers_in_queue(&self, count: usize) 
+        -> impl Iterator<Item = &Transaction<SiteTransfer>>
+    {
+        self.site_transfer_queue.get_next(count)
+    }
+
+    pub fn get_next_names_in_queue(&self, count: usize) 
+        -> impl Iterator<Item = &Transaction<NameRegistration>>
+    {
+        self.name_queue.get_next(count)
+    }
+
     pub fn remove_from_transaction_queue(&mut self, block: &Block)
     {
         self.transfer_queue.remove_in_block(&block.transfers);
         self.page_queue.remove_in_block(&block.pages);
+        self.site_transfer_queue.remove_in_block(&block.site_transfers);
+        self.name_queue.remove_in_block(&block.names);
+        if !block.site_transfers.is_empty() {
+            self.remove_pages_from_old_site_owners();
+        }
+    }
+
+    /// Whether a page is signed by its site's owner as of our top block.
+    pub fn is_page_from_site_owner(&mut self, page: &Transaction<Page>) -> bool
+    {
+        let owner = self.get_site_owner(&page.header.content.site);
+        page.get_from_addresses().contains(&owner)
+    }
+
+    /// Drop queued pages signed by someone who won't own the site once the
+    /// queued site transfers have gone through, as they can't be added.
+    pub fn remove_pages_from_old_site_owners(&mut self)
+    {
+        let pages = self.page_queue.transactions().cloned().collect::<Vec<_>>();
+        let stale = pages.into_iter()
+            .filter(|page|
+            {
+                let owner = self.get_site_owner_after_queue(&page.header.content.site);
+                !page.get_from_addresses().contains(&owner)
+            })
+            .collect::<Vec<_>>();
+
+        if !stale.is_empty()
+        {
+            info!("Dropping {} queued page(s) from old site owners", stale.len());
+            self.page_queue.remove_in_block(&stale);
+        }
     }
 
     pub fn find_transaction_in_queue(&self, transaction_id: &Hash) -> Option<TransactionVariant>
@@ -155,6 +336,16 @@ impl BlockChain
             return Some(TransactionVariant::Page(page?));
         }
 
+        let site_transfer = self.site_transfer_queue.find(transaction_id);
+        if site_transfer.is_some() {
+            return Some(TransactionVariant::SiteTransfer(site_transfer?));
+        }
+
+        let name = self.name_queue.find(transaction_id);
+        if name.is_some() {
+            return Some(TransactionVariant::NameRegistration(name?));
+        }
+
         None
     }
 
@@ -7,11 +7,13 @@
 use super::BlockChain;
 use crate::transaction::{Transaction, TransactionContent, TransactionVariant};
 use crate::transaction::page::Page;
+use crate::transaction::name::NameRecord;
 use crate::block::Block;
 use crate::wallet::WalletStatus;
 use crate::hash::Hash;
 
 use serde::Serialize;
+use std::collections::HashMap;
 
 fn find_transaction<C>(transactions: &Vec<Transaction<C>>, transaction_id: &Hash)
         -> Option<Transaction<C>>
@@ -38,7 +40,7 @@ impl BlockChain
         let real_to = std::cmp::min(to + 1, self.blocks.next_top());
         for block_id in (0..real_to).rev()
         {
-            let metadata = self.metadata.get(block_id).unwrap();
+            let metadata = self.block_metadata(block_id);
             if metadata.wallets.contains_key(address) {
                 return metadata.wallets[address].clone();
             }
@@ -56,37 +58,112 @@ impl BlockChain
         }
     }
 
-    pub fn last_page_update(&mut self, address: &Hash) -> Option<Block>
+    pub fn has_page_updates(&mut self, address: &Hash) -> bool
     {
         for block_id in (0..self.blocks.next_top()).rev()
         {
-            let metadata = self.metadata.get(block_id).unwrap();
-            if metadata.page_updates.contains_key(address) {
-                return self.blocks.get(block_id);
+            if self.block_metadata(block_id).page_updates.contains_key(address) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    pub fn get_site_owner_up_to_block(&mut self, to: u64, site: &Hash) -> Hash
+    {
+        let real_to = std::cmp::min(to + 1, self.blocks.next_top());
+        for block_id in (0..real_to).rev()
+        {
+            let metadata = self.block_metadata(block_id);
+            if metadata.site_owners.contains_key(site) {
+                return metadata.site_owners[site];
+            }
+        }
+
+        // Sites are owned by their own address until transferred
+        *site
+    }
+
+    pub fn get_site_owner(&mut self, site: &Hash) -> Hash
+    {
+        if self.blocks.next_top() == 0 {
+            *site
+        } else {
+            self.get_site_owner_up_to_block(self.blocks.next_top() - 1, site)
+        }
+    }
+
+    pub fn get_sites_owned(&mut self, address: &Hash) -> Vec<Hash>
+    {
+        // FIXME: Extremely slow, need to use metadata to 
+        //        optimise this!
+
+        let mut owners = HashMap::<Hash, Hash>::new();
+        for block_id in 0..self.blocks.next_top()
+        {
+            let metadata = self.block_metadata(block_id);
+            for site in metadata.page_updates.keys() {
+                owners.entry(*site).or_insert(*site);
+            }
+            for (site, owner) in metadata.site_owners {
+                owners.insert(site, owner);
+            }
+        }
+
+        owners
+            .into_iter()
+            .filter(|(_, owner)| owner == address)
+            .map(|(site, _)| site)
+            .collect()
+    }
+
+    /// The record for a name as of the given block, if it's registered and
+    /// hasn't expired by then.
+    pub fn get_name_up_to_block(&mut self, to: u64, name: &str) -> Option<NameRecord>
+    {
+        let real_to = std::cmp::min(to + 1, self.blocks.next_top());
+        for block_id in (0..real_to).rev()
+        {
+            let metadata = self.block_metadata(block_id);
+            if let Some(record) = metadata.names.get(name) 
+            {
+                if record.expires <= to {
+                    return None;
+                }
+                return Some(record.clone());
             }
         }
 
         None
     }
 
+    pub fn get_name(&mut self, name: &str) -> Option<NameRecord>
+    {
+        // NOTE: Resolved for the next block, as that's the earliest any new 
+        //       registration could be included in
+        self.get_name_up_to_block(self.blocks.next_top(), name)
+    }
+
     pub fn get_page_updates(&mut self, address: &Hash) 
         -> Vec<Transaction<Page>>
     {
         // FIXME: Extremely slow, need to use metadata to 
         //        optimise this!
 
+        // NOTE: Pages are found by site rather than by who signed them, so
+        //       updates made by every owner the site has had are included.
         let mut updates = Vec::new();
         for block_id in (0..self.blocks.next_top()).rev()
         {
-            let metadata = self.metadata.get(block_id).unwrap();
+            let metadata = self.block_metadata(block_id);
             if !metadata.page_updates.contains_key(address) {
                 continue;
             }
 
-            let block = self.block(block_id).unwrap();
-            for page in block.pages.iter().rev() 
+            for page in self.block_pages(block_id).iter().rev() 
             {
-                if page.get_from_addresses().contains(address) {
+                if &page.header.content.site == address {
                     updates.push(page.clone());
                 }
             }
@@ -100,6 +177,92 @@ impl BlockChain
         updates
     }
 
+    /// Page updates for a site confirmed in blocks after `block_id`, oldest
+    /// first, for bringing an already materialised site up to date.
+    pub fn get_page_updates_after(&mut self, address: &Hash, block_id: u64)
+        -> Vec<Transaction<Page>>
+    {
+        let mut updates = Vec::new();
+        for block_id in (block_id + 1)..self.blocks.next_top()
+        {
+            let metadata = self.block_metadata(block_id);
+            if !metadata.page_updates.contains_key(address) {
+                continue;
+            }
+
+            for page in self.block_pages(block_id) 
+            {
+                if &page.header.content.site == address {
+                    updates.push(page);
+                }
+            }
+        }
+
+        updates
+    }
+
+    fn page_chunk_index(&mut self) -> &Vec<(u64, Hash, u32)>
+    {
+        if self.page_chunks.is_none()
+        {
+            let mut index = Vec::new();
+            for block_id in 0..self.blocks.next_top()
+            {
+                let metadata = self.block_metadata(block_id);
+                if metadata.page_updates.is_empty() {
+                    continue;
+                }
+
+                for page in self.block_pages(block_id)
+                {
+                    let content = &page.header.content;
+                    index.push((block_id, content.chunk_root, content.chunk_count));
+                }
+            }
+
+            self.page_chunks = Some(index);
+        }
+
+        self.page_chunks.as_ref().unwrap()
+    }
+
+    /// Add a new block's pages to the chunk index, if it's been built.
+    pub(super) fn index_page_chunks(&mut self, block: &Block)
+    {
+        if let Some(index) = &mut self.page_chunks
+        {
+            for page in &block.pages
+            {
+                let content = &page.header.content;
+                index.push((block.header.block_id, content.chunk_root, content.chunk_count));
+            }
+        }
+    }
+
+    /// Drop pages in blocks from `block_id` onwards from the chunk index.
+    pub(super) fn unindex_page_chunks_from(&mut self, block_id: u64)
+    {
+        if let Some(index) = &mut self.page_chunks {
+            index.retain(|(page_block_id, _, _)| *page_block_id < block_id);
+        }
+    }
+
+    /// The chunk root and chunk count of every page in blocks before 
+    /// `block_id`, in chain order.
+    pub fn get_page_chunks_before_block(&mut self, block_id: u64) -> Vec<(Hash, u32)>
+    {
+        self.page_chunk_index()
+            .iter()
+            .take_while(|(page_block_id, _, _)| *page_block_id < block_id)
+            .map(|(_, chunk_root, chunk_count)| (*chunk_root, *chunk_count))
+            .collect()
+    }
+
+    pub fn get_page_chunks(&mut self) -> Vec<(Hash, u32)>
+    {
+        self.get_page_chunks_before_block(self.blocks.next_top())
+    }
+
     pub fn find_transaction_in_chain(&mut self, transaction_id: &Hash) 
         -> Option<(TransactionVariant, Block)>
     {
@@ -108,8 +271,12 @@ impl BlockChain
 
         for block_id in 0..self.blocks.next_top() 
         {
-            let block = self.block(block_id).unwrap();
+            let block_or_none = self.block(block_id);
+            if block_or_none.is_none() {
+                continue;
+            }
 
+            let block = block_or_none.unwrap();
             let transfer = find_transaction(&block.transfers, transaction_id);
             if transfer.is_some() {
                 return Some((TransactionVariant::Transfer(transfer.unwrap()), block.clone()));
@@ -119,6 +286,16 @@ impl BlockChain
             if page.is_some() {
                 return Some((TransactionVariant::Page(page.unwrap()), block.clone()));
             }
+
+            let site_transfer = find_transaction(&block.site_transfers, transaction_id);
+            if site_transfer.is_some() {
+                return Some((TransactionVariant::SiteTransfer(site_transfer.unwrap()), block.clone()));
+            }
+
+            let name = find_transaction(&block.names, transaction_id);
+            if name.is_some() {
+                return Some((TransactionVariant::NameRegistration(name.unwrap()), block.clone()));
+            }
         }
 
         None
@@ -171,6 +348,26 @@ impl BlockChain
                         Some(block.clone())));
                 }
             }
+
+            for site_transfer in &block.site_transfers
+            {
+                if site_transfer.get_addresses_used().contains(&address)
+                {
+                    transactions.push((
+                        TransactionVariant::SiteTransfer(site_transfer.clone()),
+                        Some(block.clone())));
+                }
+            }
+
+            for name in &block.names
+            {
+                if name.get_addresses_used().contains(&address)
+                {
+                    transactions.push((
+                        TransactionVariant::NameRegistration(name.clone()),
+                        Some(block.clone())));
+                }
+            }
         });
 
         for transfer in self.transfer_queue.transactions()
@@ -194,6 +391,26 @@ impl BlockChain
             }
         }
 
+        for site_transfer in self.site_transfer_queue.transactions()
+        {
+            if site_transfer.get_addresses_used().contains(&address)
+            {
+                transactions.push((
+                    TransactionVariant::SiteTransfer(site_transfer.clone()),
+                    None));
+            }
+        }
+
+        for name in self.name_queue.transactions()
+        {
+            if name.get_addresses_used().contains(&address)
+            {
+                transactions.push((
+                    TransactionVariant::NameRegistration(name.clone()),
+                    None));
+            }
+        }
+
         transactions.reverse();
         transactions
     }
@@ -207,11 +424,17 @@ mod tests
     use super::*;
     use super::super::BlockChainAddResult;
     use crate::block::builder::BlockBuilder;
+    use crate::block::validate::BlockValidationResult;
     use crate::wallet::Wallet;
     use crate::wallet::private_wallet::PrivateWallet;
     use crate::data_store::data_unit::DataUnit;
     use crate::data_store::page::CreatePageData;
+    use crate::transaction::TransactionValidationResult;
+    use crate::transaction::builder::TransactionBuilder;
+    use crate::transaction::page::merkle_root_for_chunks;
+    use crate::config::{NAME_REGISTRATION_FEE, NAME_REGISTRATION_PERIOD, PAGE_MAX_CHUNKS, HASH_LEN};
     use crate::miner;
+    use std::rc::Rc;
 
     #[test]
     fn test_chain_transaction()
@@ -264,5 +487,319 @@ mod tests
            ]);
     }
 
-}
+    #[test]
+    fn test_site_transfer()
+    {
+        let _ = pretty_env_logger::try_init();
+
+        let mut chain = BlockChain::open_temp();
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let other = PrivateWallet::open_temp(1).unwrap();
+        let site = wallet.get_address();
+
+        let block_a = miner::mine_block(Block::new_blank(&mut chain, &wallet).unwrap());
+        assert_eq!(chain.add(&block_a).unwrap(), BlockChainAddResult::Ok);
+        let block_b = miner::mine_block(Block::new_blank(&mut chain, &other).unwrap());
+        assert_eq!(chain.add(&block_b).unwrap(), BlockChainAddResult::Ok);
+
+        // Create a site, then hand it over to 'other'
+        let page_data = CreatePageData::new("index.html".to_owned(), Vec::new());
+        let page_a = chain.new_page(&wallet, &DataUnit::CreatePage(page_data.clone()), 0.0).unwrap();
+        chain.push_page_queue(page_a.clone()).expect("Valid");
+        let block_c = miner::mine_block(BlockBuilder::new(&wallet)
+            .add_page(page_a.clone())
+            .build(&mut chain)
+            .unwrap());
+        assert_eq!(chain.add(&block_c).unwrap(), BlockChainAddResult::Ok);
 
+        // Queued before the transfer, so it's valid until then
+        let other_page_data = CreatePageData::new("other.html".to_owned(), Vec::new());
+        let queued_page = chain.new_page(&wallet, &DataUnit::CreatePage(other_page_data), 0.0).unwrap();
+        chain.push_page_queue(queued_page.clone()).expect("Valid");
+
+        let site_transfer = chain.new_site_transfer(&wallet, &site, &other.get_address(), 0.1).unwrap();
+        chain.push_site_transfer_queue(site_transfer.clone()).expect("Valid");
+        let block_d = miner::mine_block(BlockBuilder::new(&wallet)
+            .add_site_transfer(site_transfer.clone())
+            .build(&mut chain)
+            .unwrap());
+        assert_eq!(chain.add(&block_d).unwrap(), BlockChainAddResult::Ok);
+        assert_eq!(chain.get_site_owner(&site), other.get_address());
+        assert_eq!(chain.get_sites_owned(&other.get_address()), vec![site]);
+        assert_eq!(chain.get_sites_owned(&wallet.get_address()), vec![]);
+        assert_eq!(chain.is_page_from_site_owner(&queued_page), false);
+        assert_eq!(chain.get_next_pages_in_queue(10).count(), 0);
+
+        // The old key can no longer update the site, but the new one can
+        let old_owner_page = chain.new_page(&wallet, &DataUnit::CreatePage(page_data.clone()), 0.0).unwrap();
+        assert_eq!(chain.push_page_queue(old_owner_page.clone()).is_err(), true);
+        let block_e = miner::mine_block(BlockBuilder::new(&wallet)
+            .add_page(old_owner_page)
+            .build(&mut chain)
+            .unwrap());
+        assert_eq!(chain.add(&block_e).unwrap(), BlockChainAddResult::Invalid(BlockValidationResult::SiteOwner(site)));
+
+        let page_b = chain.new_page_for_site(&other, &site, &DataUnit::CreatePage(page_data), |_| None, 0.0).unwrap();
+        chain.push_page_queue(page_b.clone()).expect("Valid");
+        let block_f = miner::mine_block(BlockBuilder::new(&wallet)
+            .add_page(page_b.clone())
+            .build(&mut chain)
+            .unwrap());
+        assert_eq!(chain.add(&block_f).unwrap(), BlockChainAddResult::Ok);
+
+        // Updates from both owners are part of the site
+        assert_eq!(chain.get_page_updates(&site), [page_a, page_b]);
+    }
+
+    #[test]
+    fn test_name_registration()
+    {
+        let _ = pretty_env_logger::try_init();
+
+        let mut chain = BlockChain::open_temp();
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let other = PrivateWallet::open_temp(1).unwrap();
+        let site = wallet.get_address();
+        let name = "my-site";
+
+        let block_a = miner::mine_block(Block::new_blank(&mut chain, &wallet).unwrap());
+        assert_eq!(chain.add(&block_a).unwrap(), BlockChainAddResult::Ok);
+        let block_b = miner::mine_block(Block::new_blank(&mut chain, &other).unwrap());
+        assert_eq!(chain.add(&block_b).unwrap(), BlockChainAddResult::Ok);
+
+        assert_eq!(chain.new_name_registration(&wallet, "My Site", &site, &site, NAME_REGISTRATION_FEE).is_err(), true);
+        assert_eq!(chain.new_name_registration(&wallet, name, &site, &site, 0.1).is_err(), true);
+
+        let registration = chain.new_name_registration(&wallet, name, &site, &site, NAME_REGISTRATION_FEE).unwrap();
+        chain.push_name_queue(registration.clone()).expect("Valid");
+        let block_c = miner::mine_block(BlockBuilder::new(&wallet)
+            .add_name(registration)
+            .build(&mut chain)
+            .unwrap());
+        assert_eq!(chain.add(&block_c).unwrap(), BlockChainAddResult::Ok);
+        assert_eq!(chain.get_name(name), Some(NameRecord
+        {
+            site,
+            owner: site,
+            expires: 2 + NAME_REGISTRATION_PERIOD,
+        }));
+
+        // Someone else can't claim it while it's registered
+        let other_address = other.get_address();
+        let stolen = chain.new_name_registration(&other, name, &other_address, &other_address, NAME_REGISTRATION_FEE).unwrap();
+        assert_eq!(chain.push_name_queue(stolen.clone()).is_err(), true);
+        let block_d = miner::mine_block(BlockBuilder::new(&wallet)
+            .add_name(stolen)
+            .build(&mut chain)
+            .unwrap());
+        assert_eq!(chain.add(&block_d).unwrap(), BlockChainAddResult::Invalid(BlockValidationResult::NameOwner(name.to_owned())));
+
+        // But the owner can hand it over
+        let transfer = chain.new_name_registration(&wallet, name, &site, &other_address, NAME_REGISTRATION_FEE).unwrap();
+        chain.push_name_queue(transfer.clone()).expect("Valid");
+        let block_e = miner::mine_block(BlockBuilder::new(&wallet)
+            .add_name(transfer)
+            .build(&mut chain)
+            .unwrap());
+        assert_eq!(chain.add(&block_e).unwrap(), BlockChainAddResult::Ok);
+        assert_eq!(chain.get_name(name).unwrap().owner, other_address);
+
+        let renewal = chain.new_name_registration(&wallet, name, &site, &site, NAME_REGISTRATION_FEE).unwrap();
+        assert_eq!(chain.push_name_queue(renewal).is_err(), true);
+
+        // Once expired, it's free to register again
+        assert_eq!(chain.get_name_up_to_block(3 + NAME_REGISTRATION_PERIOD - 1, name).is_some(), true);
+        assert_eq!(chain.get_name_up_to_block(3 + NAME_REGISTRATION_PERIOD, name), None);
+    }
+
+    #[test]
+    fn test_page_chunk_root()
+    {
+        let _ = pretty_env_logger::try_init();
+
+        let mut chain = BlockChain::open_temp();
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let other = PrivateWallet::open_temp(1).unwrap();
+
+        let block_a = miner::mine_block(Block::new_blank(&mut chain, &wallet).unwrap());
+        assert_eq!(chain.add(&block_a).unwrap(), BlockChainAddResult::Ok);
+        let block_b = miner::mine_block(Block::new_blank(&mut chain, &other).unwrap());
+        assert_eq!(chain.add(&block_b).unwrap(), BlockChainAddResult::Ok);
+
+        let content = (0..200_000u32).map(|i| (i * 7 % 251) as u8).collect::<Vec<_>>();
+        let original = DataUnit::CreatePage(CreatePageData::new("index.html".to_owned(), content.clone()));
+        let page_a = chain.new_page(&wallet, &original, 0.0).unwrap();
+        assert_eq!(page_a.header.content.chunk_count as usize, original.hashes().unwrap().len());
+        assert!(page_a.header.content.is_data_valid(&original).is_ok());
+        chain.push_page_queue(page_a.clone()).expect("Valid");
+
+        // The transaction doesn't grow with the page's data
+        let small = DataUnit::CreatePage(CreatePageData::new("index.html".to_owned(), vec![0]));
+        let small_page = Page::new_from_data(1, wallet.get_address(), &small, |_| None, 0.0).unwrap();
+        assert_eq!(bincode::serialize(&small_page).unwrap().len(), 
+                   bincode::serialize(&page_a.header.content).unwrap().len());
+
+        // Nor can it have more chunks than peers would fetch
+        let mut huge = small_page.clone();
+        huge.chunk_count = PAGE_MAX_CHUNKS + 1;
+        huge.data_length = huge.chunk_count as u64;
+        huge.new_data_length = huge.data_length;
+        let cost = huge.cost();
+        let huge = TransactionBuilder::new(huge).add_input(&wallet, cost).build().unwrap();
+        assert_eq!(huge.header.content.validate(&huge.header.inputs).unwrap(), TransactionValidationResult::Negative);
+
+        let block_c = miner::mine_block(BlockBuilder::new(&wallet)
+            .add_page(page_a.clone())
+            .build(&mut chain)
+            .unwrap());
+        assert_eq!(chain.add(&block_c).unwrap(), BlockChainAddResult::Ok);
+        assert_eq!(chain.get_page_chunks_before_block(2), Vec::new());
+        assert_eq!(chain.get_page_chunks(), 
+                   vec![(page_a.header.content.chunk_root, page_a.header.content.chunk_count)]);
+
+        // The same content under another name shares every chunk but the manifest
+        let copy = DataUnit::CreatePage(CreatePageData::new("copy.html".to_owned(), content));
+        let page_b = chain.new_page(&other, &copy, 0.0).unwrap();
+        assert_ne!(page_b.header.content.chunk_root, page_a.header.content.chunk_root);
+        assert_eq!(&copy.hashes().unwrap()[1..], &original.hashes().unwrap()[1..]);
+        assert!(page_b.header.content.is_data_valid(&original).is_err());
+
+        // The index matches one rebuilt from storage, and follows branches
+        // replacing blocks
+        let page_chunks = chain.get_page_chunks();
+        chain.page_chunks = None;
+        assert_eq!(chain.get_page_chunks(), page_chunks);
+
+        let mut other_chain = BlockChain::open_temp();
+        assert_eq!(other_chain.add(&block_a).unwrap(), BlockChainAddResult::Ok);
+        assert_eq!(other_chain.add(&block_b).unwrap(), BlockChainAddResult::Ok);
+        let mut branch = Vec::new();
+        for _ in 0..2
+        {
+            let block = miner::mine_block(Block::new_blank(&mut other_chain, &other).unwrap());
+            assert_eq!(other_chain.add(&block).unwrap(), BlockChainAddResult::Ok);
+            branch.push(block);
+        }
+
+        chain.merge_branch(branch);
+        assert_eq!(chain.get_page_chunks(), Vec::new());
+    }
+
+    #[test]
+    fn test_page_dedup()
+    {
+        let _ = pretty_env_logger::try_init();
+
+        let mut chain = BlockChain::open_temp();
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let other = PrivateWallet::open_temp(1).unwrap();
+
+        let block_a = miner::mine_block(Block::new_blank(&mut chain, &wallet).unwrap());
+        assert_eq!(chain.add(&block_a).unwrap(), BlockChainAddResult::Ok);
+        let block_b = miner::mine_block(Block::new_blank(&mut chain, &other).unwrap());
+        assert_eq!(chain.add(&block_b).unwrap(), BlockChainAddResult::Ok);
+
+        let content = (0..200_000u32).map(|i| (i * 7 % 251) as u8).collect::<Vec<_>>();
+        let original = DataUnit::CreatePage(CreatePageData::new("index.html".to_owned(), content.clone()));
+        let page_a = chain.new_page(&wallet, &original, 0.0).unwrap();
+        assert_eq!(page_a.header.content.new_data_length, page_a.header.content.data_length);
+        chain.push_page_queue(page_a.clone()).expect("Valid");
+        let block_c = miner::mine_block(BlockBuilder::new(&wallet)
+            .add_page(page_a.clone())
+            .build(&mut chain)
+            .unwrap());
+        assert_eq!(chain.add(&block_c).unwrap(), BlockChainAddResult::Ok);
+
+        // The same content under another name and site only pays for its manifest
+        let original_hashes = Rc::new(original.hashes().unwrap());
+        let root_a = page_a.header.content.chunk_root;
+        let find_chunk = |hash: &Hash| original_hashes.iter()
+            .position(|original_hash| original_hash == hash)
+            .map(|index| (root_a, index as u32, original_hashes.clone()));
+
+        let copy = DataUnit::CreatePage(CreatePageData::new("copy.html".to_owned(), content));
+        let page_b = chain.new_page_for_site(&other, &other.get_address(), &copy, find_chunk, 0.0).unwrap();
+        let content_b = &page_b.header.content;
+        let manifest_length = copy.chunks().unwrap()[0].0.len() as u64;
+        assert_eq!(content_b.reused_chunks.len(), 1);
+        assert_eq!(content_b.reused_chunks[0].hashes.len() as u32, content_b.chunk_count - 1);
+        assert_eq!(content_b.new_data_length, manifest_length);
+        assert!(content_b.cost() < page_a.header.content.cost());
+        assert!(content_b.is_data_valid(&copy).is_ok());
+        chain.push_page_queue(page_b.clone()).expect("Valid");
+
+        // Claiming the manifest is already on the chain underpays
+        let changed = DataUnit::CreatePage(CreatePageData::new("changed.html".to_owned(), Vec::new()));
+        let changed_manifest = changed.hashes().unwrap()[0];
+        let claim_manifest = |hash: &Hash| 
+            if *hash == changed_manifest { find_chunk(&original_hashes[0]) } else { None };
+        let underpaid = Page::new_from_data(2, other.get_address(), &changed, claim_manifest, 0.0).unwrap();
+        assert_eq!(underpaid.cost(), 0.0);
+        let underpaid = TransactionBuilder::new(underpaid).add_input(&other, 0.0).build().unwrap();
+        assert!(chain.push_page_queue(underpaid.clone()).is_err());
+
+        let block_d = miner::mine_block(BlockBuilder::new(&other)
+            .add_page(underpaid.clone())
+            .build(&mut chain)
+            .unwrap());
+        assert_eq!(chain.add(&block_d).unwrap(), 
+            BlockChainAddResult::Invalid(BlockValidationResult::PageCost(underpaid.hash().unwrap())));
+
+        // Every new chunk pays for at least the smallest a chunk can be, and
+        // the length has to fit the chunks
+        let mut empty = Page::new_from_data(2, other.get_address(), &changed, |_| None, 0.0).unwrap();
+        empty.data_length = 0;
+        empty.new_data_length = 0;
+        assert!(empty.cost() > 0.0);
+        let cost = empty.cost();
+        let empty = TransactionBuilder::new(empty).add_input(&other, cost).build().unwrap();
+        assert_eq!(empty.header.content.validate(&empty.header.inputs).unwrap(), TransactionValidationResult::Negative);
+
+        let block_e = miner::mine_block(BlockBuilder::new(&other)
+            .add_page(page_b.clone())
+            .build(&mut chain)
+            .unwrap());
+        assert_eq!(chain.add(&block_e).unwrap(), BlockChainAddResult::Ok);
+    }
+
+    #[test]
+    fn test_page_reuse_size()
+    {
+        let mut state = 1u32;
+        let content = (0..4_000_000).map(|_|
+        {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        }).collect::<Vec<_>>();
+        let original = DataUnit::CreatePage(CreatePageData::new("large.bin".to_owned(), content.clone()));
+        let original_hashes = Rc::new(original.hashes().unwrap());
+        let root = merkle_root_for_chunks(&original_hashes);
+        let find_chunk = |hash: &Hash| original_hashes.iter()
+            .position(|original_hash| original_hash == hash)
+            .map(|index| (root, index as u32, original_hashes.clone()));
+
+        // Reusing a large page lists each chunk's hash, not a proof for each
+        let copy = DataUnit::CreatePage(CreatePageData::new("copy.bin".to_owned(), content));
+        let page = Page::new_from_data(1, Hash::empty(), &copy, find_chunk, 0.0).unwrap();
+        let reused_count = page.chunk_count as u64 - 1;
+        assert!(reused_count >= 32);
+        assert_eq!(page.reused_chunks.len(), 1);
+
+        let size = bincode::serialized_size(&page).unwrap();
+        assert!(size < (reused_count + 64) * HASH_LEN as u64, 
+            "{} bytes for {} reused chunks", size, reused_count);
+
+        let chunk_count_of = |chunk_root: &Hash| 
+            if *chunk_root == root { Some(original_hashes.len() as u32) } else { None };
+        assert!(page.is_reuse_valid(chunk_count_of));
+        assert!(page.is_data_valid(&copy).is_ok());
+
+        // The run has to be where it says in the earlier page
+        let mut moved = page.clone();
+        moved.reused_chunks[0].source_index += 1;
+        assert!(!moved.is_reuse_valid(chunk_count_of));
+    }
+
+}
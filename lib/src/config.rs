@@ -9,7 +9,25 @@ pub const BLOCK_TIME: u64 = 10 * 1000; // 10 second blocks
 // pub const BLOCK_TIME: u64 = 10 * 60 * 1000; // 10 minute blocks
 pub const BLOCK_SAMPLE_SIZE: u64 = 100;
 pub const PAGE_CHUNK_SIZE: usize = 1000 * 1000; // 1MB
+pub const CHUNK_MIN_SIZE: usize = 16 * 1024;
+pub const CHUNK_AVG_SIZE: usize = 64 * 1024; // Must be a power of 2
+pub const CHUNK_MAX_SIZE: usize = 256 * 1024;
+pub const CHUNK_SEGMENT_SIZE: usize = 4 * 1024; // Storage proofs carry one segment of each chunk
+pub const PAGE_MAX_CHUNKS: u32 = 64 * 1024; // At least 1GiB of data
+pub const REPLICATION_FACTOR: usize = 3;
+pub const DISK_QUOTA: u64 = 1024 * 1024 * 1024; // 1GiB
+pub const STORAGE_PROOF_CHUNKS: usize = 2;
+pub const STORAGE_PROOF_DISCOUNT: u32 = 2; // Blocks with a storage proof need half the work
+pub const MERKLE_V2_HEIGHT: u64 = 1000; // Blocks from here on use domain separated merkle trees
 
 pub const PUB_KEY_LEN: usize = 256;
 pub const HASH_LEN: usize = 32;
 
+
+pub const ADDRESS_PREFIX: &str = "hc";
+pub const ADDRESS_VERSION: u8 = 0;
+pub const ADDRESS_CHECKSUM_LEN: usize = 4;
+
+pub const NAME_MAX_LEN: usize = 32;
+pub const NAME_REGISTRATION_FEE: f32 = 1.0;
+pub const NAME_REGISTRATION_PERIOD: u64 = 365 * 24 * 60 * 60 * 1000 / BLOCK_TIME; // About a year
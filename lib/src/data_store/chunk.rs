@@ -0,0 +1,94 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use crate::config::{CHUNK_MIN_SIZE, CHUNK_AVG_SIZE, CHUNK_MAX_SIZE};
+
+const fn gear_table() -> [u64; 256]
+{
+    // Fixed pseudo-random values (splitmix64), so every node cuts chunks
+    // in the same places
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0;
+    let mut i = 0;
+    while i < 256
+    {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+
+    table
+}
+
+const GEAR: [u64; 256] = gear_table();
+
+/// Split data into chunks at points picked by a rolling hash of its
+/// contents, so an edit only changes the chunks around it.
+pub fn split(data: &[u8]) -> Vec<&[u8]>
+{
+    // NOTE: The top bits are tested, as those depend on the last 64 bytes 
+    //       rather than just the last few
+    let shift = 64 - CHUNK_AVG_SIZE.trailing_zeros();
+    let mut chunks = Vec::new();
+    let mut chunk_start = 0;
+    while chunk_start < data.len()
+    {
+        let max_end = std::cmp::min(chunk_start + CHUNK_MAX_SIZE, data.len());
+        let mut chunk_end = max_end;
+
+        let mut hash: u64 = 0;
+        for i in (chunk_start + CHUNK_MIN_SIZE)..max_end
+        {
+            hash = (hash << 1).wrapping_add(GEAR[data[i] as usize]);
+            if hash >> shift == 0 
+            {
+                chunk_end = i + 1;
+                break;
+            }
+        }
+
+        chunks.push(&data[chunk_start..chunk_end]);
+        chunk_start = chunk_end;
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+
+    #[test]
+    fn test_split()
+    {
+        let mut state: u32 = 1;
+        let data = (0..1024 * 1024)
+            .map(|_| 
+            {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16) as u8
+            })
+            .collect::<Vec<_>>();
+
+        let chunks = split(&data);
+        assert_eq!(chunks.concat(), data);
+        assert_eq!(chunks.iter().all(|chunk| chunk.len() <= CHUNK_MAX_SIZE), true);
+        assert_eq!(chunks[..chunks.len() - 1].iter().all(|chunk| chunk.len() >= CHUNK_MIN_SIZE), true);
+        assert_eq!(split(&[]).is_empty(), true);
+
+        // Inserting a byte at the start only changes the first chunk
+        let mut shifted = vec![42u8];
+        shifted.extend_from_slice(&data);
+        let shifted_chunks = split(&shifted);
+        assert_eq!(shifted_chunks[1..], chunks[1..]);
+    }
+
+}
@@ -0,0 +1,115 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use flate2::Compression as Level;
+use flate2::write::DeflateEncoder;
+use flate2::read::DeflateDecoder;
+use crate::config::CHUNK_MAX_SIZE;
+use crate::error::ErrorMessage;
+use serde::{Serialize, Deserialize};
+use std::io::{Read, Write};
+use std::error::Error;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Compression
+{
+    None,
+    Deflate,
+}
+
+/// A chunk as it's stored on disk and sent between nodes. Its hash is
+/// always of the uncompressed bytes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CompressedChunk
+{
+    pub compression: Compression,
+    pub data: Vec<u8>,
+}
+
+impl CompressedChunk
+{
+
+    pub fn compress(data: &[u8]) -> Result<Self, Box<dyn Error>>
+    {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Level::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+
+        // NOTE: Images and other already compressed files don't get any
+        //       smaller, so are kept as they are
+        if compressed.len() >= data.len()
+        {
+            return Ok(Self
+            {
+                compression: Compression::None,
+                data: data.to_vec(),
+            });
+        }
+
+        Ok(Self
+        {
+            compression: Compression::Deflate,
+            data: compressed,
+        })
+    }
+
+    pub fn decompress(&self) -> Result<Vec<u8>, Box<dyn Error>>
+    {
+        match self.compression
+        {
+            Compression::None => Ok(self.data.clone()),
+            Compression::Deflate =>
+            {
+                // NOTE: Chunks come from other nodes, so a small one could
+                //       otherwise inflate into far more than any real chunk
+                let mut data = Vec::new();
+                DeflateDecoder::new(&self.data[..])
+                    .take(CHUNK_MAX_SIZE as u64 + 1)
+                    .read_to_end(&mut data)?;
+
+                if data.len() > CHUNK_MAX_SIZE {
+                    return Err(ErrorMessage::new("Decompressed chunk is too large"));
+                }
+                Ok(data)
+            },
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+
+    #[test]
+    fn test_compression()
+    {
+        let html = b"<p>Hello, world!</p>".repeat(100);
+        let chunk = CompressedChunk::compress(&html).unwrap();
+        assert_eq!(chunk.compression, Compression::Deflate);
+        assert!(chunk.data.len() < html.len() / 5);
+        assert_eq!(chunk.decompress().unwrap(), html);
+
+        let tiny = vec![42u8];
+        let chunk = CompressedChunk::compress(&tiny).unwrap();
+        assert_eq!(chunk.compression, Compression::None);
+        assert_eq!(chunk.decompress().unwrap(), tiny);
+
+        let empty = CompressedChunk::compress(&[]).unwrap();
+        assert_eq!(empty.decompress().unwrap(), Vec::<u8>::new());
+
+        let largest = vec![0u8; CHUNK_MAX_SIZE];
+        assert_eq!(CompressedChunk::compress(&largest).unwrap().decompress().unwrap(), largest);
+
+        // Anything inflating to more than a chunk can hold is rejected
+        let bomb = CompressedChunk::compress(&vec![0u8; CHUNK_MAX_SIZE * 64]).unwrap();
+        assert_eq!(bomb.compression, Compression::Deflate);
+        assert!(bomb.decompress().is_err());
+    }
+
+}
@@ -4,43 +4,161 @@
  * SPDX-License-Identifier: BSD-2-Clause
  */
 
-use super::page::CreatePageData;
-use crate::config::PAGE_CHUNK_SIZE;
+use super::page::{CreatePageData, SiteBundleData};
+use super::page::{DeletePageData, RenamePageData, PatchPageData};
+use super::page::content_hash;
+use super::chunk;
+use super::compression::CompressedChunk;
+use crate::error::ErrorMessage;
 use crate::hash::Hash;
 use serde::{Serialize, Deserialize};
-use sha2::{Sha256, Digest};
+use std::collections::{HashSet, HashMap};
 use std::error::Error;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum DataUnit
 {
     CreatePage(CreatePageData),
+    SiteBundle(SiteBundleData),
+    DeletePage(DeletePageData),
+    RenamePage(RenamePageData),
+    PatchPage(PatchPageData),
+}
+
+/// A data unit with its file contents taken out and replaced with the
+/// chunks they're made of. This is stored as the first chunk of a page.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DataManifest
+{
+    pub data_unit: DataUnit,
+    pub contents: Vec<Vec<Hash>>,
+}
+
+impl DataManifest
+{
+
+    /// Hashes of every chunk of the page, in the same order as 
+    /// `DataUnit::chunks`, given this manifest's own hash.
+    pub fn chunk_hashes(&self, manifest_hash: Hash) -> Vec<Hash>
+    {
+        let mut seen = HashSet::new();
+        let mut hashes = vec![manifest_hash];
+        for hash in self.contents.iter().flatten()
+        {
+            if seen.insert(*hash) {
+                hashes.push(*hash);
+            }
+        }
+
+        hashes
+    }
+
 }
 
 impl DataUnit
 {
 
+    fn contents_mut(&mut self) -> Vec<&mut Vec<u8>>
+    {
+        match self
+        {
+            DataUnit::CreatePage(create_page) => vec![&mut create_page.page],
+            DataUnit::SiteBundle(bundle) => 
+                bundle.files.iter_mut().map(|file| &mut file.data).collect(),
+
+            // NOTE: These only carry small amounts of data, which is kept in
+            //       the manifest
+            DataUnit::DeletePage(_) | DataUnit::RenamePage(_) | DataUnit::PatchPage(_) => 
+                Vec::new(),
+        }
+    }
+
+    /// The manifest, followed by each distinct chunk of file contents.
     pub fn chunks(&self) -> Result<Vec<(Vec<u8>, Hash)>, Box<dyn Error>>
     {
-        let data = bincode::serialize(self)?;
-        
-        let mut hashes = Vec::new();
-        let mut chunk_start: usize = 0;
-        while chunk_start < data.len()
+        let mut data_unit = self.clone();
+        let mut contents = Vec::new();
+        let mut content_chunks = Vec::new();
+        let mut seen = HashSet::new();
+        for content in data_unit.contents_mut()
         {
-            let chunk_end = std::cmp::min(chunk_start + PAGE_CHUNK_SIZE, data.len());
-            let chunk = &data[chunk_start..chunk_end];
-            
-            let mut hasher = Sha256::new();
-            hasher.update(chunk);
+            let data = std::mem::take(content);
+            let mut hashes = Vec::new();
+            for chunk in chunk::split(&data)
+            {
+                let hash = content_hash(chunk);
+                if seen.insert(hash) {
+                    content_chunks.push((chunk.to_vec(), hash));
+                }
+                hashes.push(hash);
+            }
+            contents.push(hashes);
+        }
 
-            let hash = Hash::from(&hasher.finalize());
-            hashes.push((chunk.to_vec(), hash));
-            
-            chunk_start = chunk_end;
+        let manifest = bincode::serialize(&DataManifest { data_unit, contents })?;
+        let manifest_hash = content_hash(&manifest);
+
+        let mut chunks = vec![(manifest, manifest_hash)];
+        chunks.append(&mut content_chunks);
+        Ok(chunks)
+    }
+
+    pub fn from_manifest<F>(manifest: DataManifest, mut get_chunk: F)
+            -> Result<Self, Box<dyn Error>>
+        where F: FnMut(&Hash) -> Result<Vec<u8>, Box<dyn Error>>
+    {
+        let DataManifest { mut data_unit, contents } = manifest;
+        let mut slots = data_unit.contents_mut();
+        if slots.len() != contents.len() {
+            return Err(ErrorMessage::new("Manifest doesn't match its data"));
+        }
+
+        for (slot, hashes) in slots.iter_mut().zip(contents)
+        {
+            for hash in &hashes {
+                slot.append(&mut get_chunk(hash)?);
+            }
+        }
+
+        Ok(data_unit)
+    }
+
+    /// Chunks compressed for sending to other nodes, in the same order as
+    /// `chunks`.
+    pub fn compressed_chunks(&self) -> Result<Vec<CompressedChunk>, Box<dyn Error>>
+    {
+        let mut compressed = Vec::new();
+        for (chunk, _) in self.chunks()? {
+            compressed.push(CompressedChunk::compress(&chunk)?);
+        }
+
+        Ok(compressed)
+    }
+
+    pub fn from_compressed_chunks(compressed: &[CompressedChunk])
+        -> Result<Self, Box<dyn Error>>
+    {
+        let mut chunks = HashMap::new();
+        for chunk in compressed.iter().skip(1)
+        {
+            let data = chunk.decompress()?;
+            chunks.insert(content_hash(&data), data);
         }
 
-        Ok(hashes)
+        let manifest_or_none = compressed.first();
+        if manifest_or_none.is_none() {
+            return Err(ErrorMessage::new("No manifest"));
+        }
+
+        let manifest = bincode::deserialize(&manifest_or_none.unwrap().decompress()?)?;
+        Self::from_manifest(manifest, |hash| 
+        {
+            match chunks.get(hash)
+            {
+                Some(data) => Ok(data.clone()),
+                None => Err(ErrorMessage::new("Missing chunk")),
+            }
+        })
     }
 
     pub fn hashes(&self) -> Result<Vec<Hash>, Box<dyn Error>>
@@ -53,9 +171,10 @@ impl DataUnit
 
     pub fn len(&self) -> Result<u32, Box<dyn Error>>
     {
-        let data = bincode::serialize(self)?;
-        Ok(data.len() as u32)
+        Ok(self.chunks()?
+            .iter()
+            .map(|(chunk, _)| chunk.len() as u32)
+            .sum())
     }
 
 }
-
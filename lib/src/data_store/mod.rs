@@ -6,15 +6,43 @@
 
 pub mod page;
 pub mod data_unit;
-use data_unit::DataUnit;
+pub mod chunk;
+pub mod compression;
+pub mod proof;
+pub mod site_tree;
+use data_unit::{DataUnit, DataManifest};
+use site_tree::SiteTree;
+use compression::CompressedChunk;
 use crate::transaction::Transaction;
-use crate::transaction::page::Page;
+use crate::transaction::page::{Page, merkle_root_for_chunks};
+use crate::merkle_tree::{calculate_merkle_proof, MerkleProof, MerkleVersion};
 use crate::hash::Hash;
+use crate::error::ErrorMessage;
 use std::path::{Path, PathBuf};
 use std::fs::File;
 use std::collections::{HashSet, HashMap};
+use std::rc::Rc;
+use serde::{Serialize, Deserialize};
 use std::error::Error;
 
+/// Version 0 stored fixed size slices of whole pages, version 1 stores 
+/// compressed content defined chunks along with each page's chunk list
+const FORMAT_VERSION: u32 = 1;
+const FORMAT_FILE: &str = "format.json";
+
+/// Where chunks from a version 0 store are moved to
+const V0_CHUNKS_DIR: &str = "v0_chunks";
+
+/// Chunk hashes of each page we have the manifest for, by chunk root
+const CHUNK_LISTS_DIR: &str = "chunk_lists";
+
+#[derive(Serialize, Deserialize)]
+struct Format
+{
+    version: u32,
+}
+
+#[derive(Clone)]
 pub struct DataStore
 {
     path: PathBuf,
@@ -25,11 +53,86 @@ impl DataStore
 
     pub fn open(path: &PathBuf) -> Result<Self, Box<dyn Error>>
     {
-        std::fs::create_dir_all(path)?;
-        Ok(Self
+        std::fs::create_dir_all(path.join(CHUNK_LISTS_DIR))?;
+        let data_store = Self
         {
             path: path.clone(),
-        })
+        };
+
+        // NOTE: Stores from before the marker was added have chunks but no
+        //       format file
+        let version = match File::open(path.join(FORMAT_FILE))
+        {
+            Ok(file) => serde_json::from_reader::<_, Format>(file)?.version,
+            Err(_) if data_store.chunk_files()?.is_empty() => FORMAT_VERSION,
+            Err(_) => 0,
+        };
+
+        if version > FORMAT_VERSION 
+        {
+            return Err(ErrorMessage::new(
+                &format!("Data store format {} is newer than supported ({})", 
+                    version, FORMAT_VERSION)));
+        }
+
+        if version < FORMAT_VERSION {
+            data_store.clear_v0_chunks()?;
+        }
+        data_store.write_format()?;
+        Ok(data_store)
+    }
+
+    fn write_format(&self) -> Result<(), Box<dyn Error>>
+    {
+        let file = File::create(self.path.join(FORMAT_FILE))?;
+        serde_json::to_writer(file, &Format { version: FORMAT_VERSION })?;
+        Ok(())
+    }
+
+    fn chunk_files(&self) -> Result<Vec<(Hash, PathBuf)>, Box<dyn Error>>
+    {
+        let mut chunks = Vec::new();
+        for entry_or_error in std::fs::read_dir(&self.path)?
+        {
+            let entry = entry_or_error?;
+            let file_name = entry.file_name();
+            if entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            // Skip the format marker and any temporary files
+            let hash_or_error = base_62::decode(file_name.to_str().unwrap_or("."));
+            if hash_or_error.is_err() {
+                continue;
+            }
+
+            let hash = Hash::from(&hash_or_error.unwrap());
+            chunks.push((hash, entry.path()));
+        }
+
+        Ok(chunks)
+    }
+
+    /// Version 0 chunks are slices of a page's serialized data, which can 
+    /// only be put back together in the order listed by pages from before 
+    /// the chain format changed. They can't be served or proven as chunks of 
+    /// any page on the current chain, so they're moved out of the store and 
+    /// whatever is still needed gets fetched again from other nodes.
+    fn clear_v0_chunks(&self) -> Result<(), Box<dyn Error>>
+    {
+        let chunk_files = self.chunk_files()?;
+        let v0_path = self.path.join(V0_CHUNKS_DIR);
+        warn!("Data store in {:?} is from an older version, moving its {} chunks to {:?}", 
+            self.path, chunk_files.len(), v0_path);
+
+        // NOTE: The format file is only written once every chunk is moved, 
+        //       so an interrupted run picks up where it left off
+        std::fs::create_dir_all(&v0_path)?;
+        for (hash, path) in chunk_files {
+            std::fs::rename(path, v0_path.join(format!("{}", hash)))?;
+        }
+
+        Ok(())
     }
 
     pub fn for_page_updates(&self, updates: &Vec<Transaction<Page>>) 
@@ -46,25 +149,117 @@ impl DataStore
         Ok(data)
     }
 
+    pub fn site_tree(&self, updates: &[Transaction<Page>])
+        -> Result<SiteTree, Box<dyn Error>>
+    {
+        let mut data_units = Vec::new();
+        for update in updates {
+            data_units.push((self.get_data_unit(update)?, update.header.content.chunk_root));
+        }
+
+        Ok(SiteTree::from_updates(&data_units))
+    }
+
     pub fn store(&self, id: &Hash, data: &[u8]) 
         -> Result<(), Box<dyn Error>>
+    {
+        self.store_compressed(id, &CompressedChunk::compress(data)?)
+    }
+
+    pub fn store_compressed(&self, id: &Hash, chunk: &CompressedChunk) 
+        -> Result<(), Box<dyn Error>>
     {
         let file_name = format!("{}", id);
         let file = File::create(self.path.join(file_name))?;
-        bincode::serialize_into(file, &data)?;
+        bincode::serialize_into(file, chunk)?;
         Ok(())
     }
 
     pub fn store_data_unit(&self, data_unit: &DataUnit)
         -> Result<(), Box<dyn Error>>
     {
-        for (chunk, hash) in data_unit.chunks()? {
-            self.store(&hash, &chunk)?;
+        // NOTE: Chunks are named by their hash, so any shared with other 
+        //       pages are only stored once
+        let mut hashes = Vec::new();
+        for (chunk, hash) in data_unit.chunks()? 
+        {
+            if !self.has_chunk(&hash) {
+                self.store(&hash, &chunk)?;
+            }
+            hashes.push(hash);
         }
+
+        self.store_chunk_list(&hashes)
+    }
+
+    pub fn store_chunk_list(&self, hashes: &[Hash]) -> Result<(), Box<dyn Error>>
+    {
+        let file_name = format!("{}", merkle_root_for_chunks(hashes));
+        let file = File::create(self.path.join(CHUNK_LISTS_DIR).join(file_name))?;
+        bincode::serialize_into(file, hashes)?;
         Ok(())
     }
 
+    pub fn chunk_list(&self, chunk_root: &Hash) -> Result<Vec<Hash>, Box<dyn Error>>
+    {
+        let file_name = format!("{}", chunk_root);
+        let file = File::open(self.path.join(CHUNK_LISTS_DIR).join(file_name))?;
+        Ok(bincode::deserialize_from(file)?)
+    }
+
+    /// Find where each of `hashes` is in the pages with these roots, out of 
+    /// the ones we have the chunk list for, along with that list.
+    pub fn find_chunks(&self, page_chunks: &[(Hash, u32)], hashes: &[Hash])
+        -> HashMap<Hash, (Hash, u32, Rc<Vec<Hash>>)>
+    {
+        let wanted = hashes.iter().collect::<HashSet<_>>();
+        let mut found = HashMap::new();
+        for (chunk_root, _) in page_chunks
+        {
+            if found.len() == wanted.len() {
+                break;
+            }
+
+            let chunk_list_or_error = self.chunk_list(chunk_root);
+            if chunk_list_or_error.is_err() {
+                continue;
+            }
+
+            let chunk_list = Rc::new(chunk_list_or_error.unwrap());
+            for (index, hash) in chunk_list.iter().enumerate()
+            {
+                if !wanted.contains(hash) || found.contains_key(hash) {
+                    continue;
+                }
+
+                found.insert(*hash, (*chunk_root, index as u32, chunk_list.clone()));
+            }
+        }
+
+        found
+    }
+
+    /// The chunk at `index` of the page with this root, with the proof that
+    /// it belongs there.
+    pub fn page_chunk(&self, chunk_root: &Hash, index: u32)
+        -> Result<(CompressedChunk, MerkleProof), Box<dyn Error>>
+    {
+        let hashes = self.chunk_list(chunk_root)?;
+        let proof_or_none = calculate_merkle_proof(&hashes, index as usize, MerkleVersion::V2);
+        if proof_or_none.is_none() {
+            return Err(ErrorMessage::new("Chunk index out of range"));
+        }
+
+        let chunk = self.get_compressed(&hashes[index as usize])?;
+        Ok((chunk, proof_or_none.unwrap()))
+    }
+
     pub fn get(&self, id: &Hash) -> Result<Vec<u8>, Box<dyn Error>>
+    {
+        self.get_compressed(id)?.decompress()
+    }
+
+    pub fn get_compressed(&self, id: &Hash) -> Result<CompressedChunk, Box<dyn Error>>
     {
         let file_name = format!("{}", id);
         let file = File::open(self.path.join(file_name))?;
@@ -74,29 +269,52 @@ impl DataStore
     pub fn get_data_unit(&self, transaction: &Transaction<Page>)
         -> Result<DataUnit, Box<dyn Error>>
     {
-        let mut data_unit_bytes = Vec::new();
-        for chunk_hash in &transaction.header.content.data_hashes
-        {
-            let mut chunk = self.get(chunk_hash)?;
-            data_unit_bytes.append(&mut chunk);
+        let hashes = self.chunk_list(&transaction.header.content.chunk_root)?;
+        let manifest_hash_or_none = hashes.first();
+        if manifest_hash_or_none.is_none() {
+            return Err(ErrorMessage::new("Page has no data"));
         }
 
-        Ok(bincode::deserialize(&data_unit_bytes)?)
+        let manifest = self.manifest(manifest_hash_or_none.unwrap())?;
+        DataUnit::from_manifest(manifest, |hash| self.get(hash))
+    }
+
+    pub fn manifest(&self, manifest_hash: &Hash) -> Result<DataManifest, Box<dyn Error>>
+    {
+        Ok(bincode::deserialize(&self.get(manifest_hash)?)?)
     }
 
     pub fn report(&self)
         -> Result<HashSet<Hash>, Box<dyn Error>>
     {
-        let mut stored = HashSet::new();
-        for entry_or_error in std::fs::read_dir(&self.path)?
-        {
-            let entry = entry_or_error?;
-            let hash_vec = base_62::decode(entry.file_name().to_str().unwrap())?;
-            let hash = Hash::from(&hash_vec);
-            stored.insert(hash);
+        Ok(self.chunk_files()?
+            .into_iter()
+            .map(|(hash, _)| hash)
+            .collect())
+    }
+
+    pub fn remove(&self, id: &Hash) -> Result<(), Box<dyn Error>>
+    {
+        let file_name = format!("{}", id);
+        std::fs::remove_file(self.path.join(file_name))?;
+        Ok(())
+    }
+
+    /// Bytes used on disk by this chunk.
+    pub fn chunk_size(&self, id: &Hash) -> Result<u64, Box<dyn Error>>
+    {
+        let file_name = format!("{}", id);
+        Ok(std::fs::metadata(self.path.join(file_name))?.len())
+    }
+
+    pub fn disk_usage(&self) -> Result<u64, Box<dyn Error>>
+    {
+        let mut total = 0;
+        for (_, path) in self.chunk_files()? {
+            total += std::fs::metadata(path)?.len();
         }
 
-        Ok(stored)
+        Ok(total)
     }
 
     pub fn has_chunk(&self, hash: &Hash) -> bool
@@ -113,6 +331,9 @@ mod tests
 
     use super::*;
     use page::CreatePageData;
+    use crate::config::PAGE_CHUNK_SIZE;
+    use sha2::{Sha256, Digest};
+    use crate::transaction::TransactionHeader;
 
     impl DataStore
     {
@@ -143,5 +364,84 @@ mod tests
         assert_eq!(data_store.get(&Hash::empty()).unwrap(), test_data);
     }
 
+    #[test]
+    fn test_clear_v0_chunks()
+    {
+        let data_store = DataStore::open_temp();
+        let data_unit = DataUnit::CreatePage(
+            CreatePageData::new("index.html".to_owned(), b"<p>Hi</p>".repeat(200_000)));
+
+        // Write the page as version 0 did, as fixed size slices of the 
+        // serialized data unit named by their hash, without a format marker
+        let data = bincode::serialize(&data_unit).unwrap();
+        let mut v0_hashes = HashSet::new();
+        for slice in data.chunks(PAGE_CHUNK_SIZE)
+        {
+            let hash = Hash::from(&Sha256::digest(slice));
+            let file = File::create(data_store.path.join(format!("{}", hash))).unwrap();
+            bincode::serialize_into(file, slice).unwrap();
+            v0_hashes.insert(hash);
+        }
+        std::fs::remove_file(data_store.path.join(FORMAT_FILE)).unwrap();
+        assert_eq!(v0_hashes.len(), 2);
+
+        // None of the old slices are taken as chunks, but they're kept aside
+        let cleared = DataStore::open(&data_store.path).unwrap();
+        assert_eq!(cleared.report().unwrap().is_empty(), true);
+        for hash in &v0_hashes
+        {
+            assert_eq!(cleared.get(hash).is_err(), true);
+            assert_eq!(data_store.path.join(V0_CHUNKS_DIR).join(format!("{}", hash)).exists(), true);
+        }
+
+        // The page can be stored again and is kept on the next open
+        cleared.store_data_unit(&data_unit).unwrap();
+        let reopened = DataStore::open(&data_store.path).unwrap();
+        let page = Page::new_from_data(1, Hash::empty(), &data_unit, |_| None, 0.0).unwrap();
+        let header = TransactionHeader { content: page, inputs: Vec::new() };
+        let transaction = Transaction::new(header, HashMap::new());
+        assert_eq!(reopened.get_data_unit(&transaction).unwrap(), data_unit);
+    }
+
+    #[test]
+    fn test_site_bundle()
+    {
+        let data_store = DataStore::open_temp();
+        let mut bundle = page::SiteBundleData::new();
+        bundle.add_file("index.html".to_owned(), page::guess_content_type("index.html"), b"<h1>Hi</h1>".to_vec());
+        bundle.add_file("img/logo.PNG".to_owned(), page::guess_content_type("img/logo.PNG"), vec![0u8; 10]);
+        bundle.add_file("LICENSE".to_owned(), page::guess_content_type("LICENSE"), Vec::new());
+
+        let data_unit = DataUnit::SiteBundle(bundle);
+        data_store.store_data_unit(&data_unit).unwrap();
+
+        let page = Page::new_from_data(1, Hash::empty(), &data_unit, |_| None, 0.0).unwrap();
+        let header = TransactionHeader { content: page.clone(), inputs: Vec::new() };
+        let transaction = Transaction::new(header, HashMap::new());
+        let stored = data_store.get_data_unit(&transaction).unwrap();
+        assert_eq!(stored, data_unit);
+
+        match stored
+        {
+            DataUnit::SiteBundle(bundle) =>
+            {
+                assert_eq!(bundle.file("index.html").unwrap().content_type, Some("text/html".to_owned()));
+                assert_eq!(bundle.file("img/logo.PNG").unwrap().content_type, Some("image/png".to_owned()));
+                assert_eq!(bundle.file("LICENSE").unwrap().content_type, None);
+                assert_eq!(bundle.file("missing.html"), None);
+            },
+            _ => panic!("Expected a site bundle"),
+        }
+
+        // Each chunk can be checked on its own against the page
+        for index in 0..page.chunk_count
+        {
+            let (chunk, proof) = data_store.page_chunk(&page.chunk_root, index).unwrap();
+            assert!(page.is_chunk_valid(index, &chunk.decompress().unwrap(), &proof));
+            assert!(!page.is_chunk_valid(index + 1, &chunk.decompress().unwrap(), &proof));
+        }
+        assert!(data_store.page_chunk(&page.chunk_root, page.chunk_count).is_err());
+    }
+
 }
 
@@ -4,13 +4,22 @@
  * SPDX-License-Identifier: BSD-2-Clause
  */
 
+use crate::hash::Hash;
+use crate::merkle_tree::{calculate_merkle_root, MerkleVersion};
+use crate::config::CHUNK_SEGMENT_SIZE;
+use crate::error::ErrorMessage;
 use serde::{Serialize, Deserialize};
+use std::error::Error;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct CreatePageData
 {
     pub name: String,
     pub page: Vec<u8>,
+
+    /// MIME type to serve the page with, otherwise it's guessed from the
+    /// name's extension
+    pub content_type: Option<String>,
 }
 
 impl CreatePageData
@@ -22,8 +31,207 @@ impl CreatePageData
         {
             name,
             page,
+            content_type: None,
+        }
+    }
+
+    pub fn with_content_type(mut self, content_type: Option<String>) -> Self
+    {
+        self.content_type = content_type;
+        self
+    }
+
+}
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DeletePageData
+{
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RenamePageData
+{
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum PatchOperation
+{
+    Copy(u32, u32),
+    Insert(Vec<u8>),
+}
+
+/// A binary diff against a previous version of a page, identified by the
+/// hash of its contents.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PatchPageData
+{
+    pub name: String,
+    pub base_hash: Hash,
+    pub operations: Vec<PatchOperation>,
+}
+
+/// The pieces a chunk's hash is built from. Empty data is one empty
+/// segment, so still has a hash of its own.
+pub fn chunk_segments(data: &[u8]) -> Vec<&[u8]>
+{
+    if data.is_empty() {
+        return vec![data];
+    }
+
+    data.chunks(CHUNK_SEGMENT_SIZE).collect()
+}
+
+/// The merkle root of the data's segments, so any one segment can be
+/// proven to be part of it without the rest.
+pub fn content_hash(data: &[u8]) -> Hash
+{
+    calculate_merkle_root(&chunk_segments(data), MerkleVersion::V2)
+}
+
+impl PatchPageData
+{
+
+    pub fn diff(name: String, base: &[u8], new: &[u8]) -> Self
+    {
+        // NOTE: This only keeps the common start and end, which covers the
+        //       usual case of a small edit somewhere in a large file.
+        let prefix = base.iter()
+            .zip(new.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let max_suffix = std::cmp::min(base.len(), new.len()) - prefix;
+        let suffix = base.iter().rev()
+            .zip(new.iter().rev())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let mut operations = Vec::new();
+        if prefix > 0 {
+            operations.push(PatchOperation::Copy(0, prefix as u32));
+        }
+        if new.len() - suffix > prefix {
+            operations.push(PatchOperation::Insert(new[prefix..new.len() - suffix].to_vec()));
+        }
+        if suffix > 0 {
+            operations.push(PatchOperation::Copy((base.len() - suffix) as u32, suffix as u32));
         }
+
+        Self
+        {
+            name,
+            base_hash: content_hash(base),
+            operations,
+        }
+    }
+
+    pub fn apply(&self, base: &[u8]) -> Result<Vec<u8>, Box<dyn Error>>
+    {
+        if content_hash(base) != self.base_hash {
+            return Err(ErrorMessage::new("Patch base does not match"));
+        }
+
+        let mut result = Vec::new();
+        for operation in &self.operations
+        {
+            match operation
+            {
+                PatchOperation::Copy(offset, length) =>
+                {
+                    let start = *offset as usize;
+                    let end = start + *length as usize;
+                    if end > base.len() {
+                        return Err(ErrorMessage::new("Patch copies past the end of the base"));
+                    }
+                    result.extend_from_slice(&base[start..end]);
+                },
+
+                PatchOperation::Insert(data) =>
+                    result.extend_from_slice(data),
+            }
+        }
+
+        Ok(result)
     }
 
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BundleFile
+{
+    pub path: String,
+    pub content_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// A whole directory tree published in one go, which replaces every file
+/// the site had before.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SiteBundleData
+{
+    pub files: Vec<BundleFile>,
+}
+
+impl SiteBundleData
+{
+
+    pub fn new() -> Self
+    {
+        Self
+        {
+            files: Vec::new(),
+        }
+    }
+
+    pub fn add_file(&mut self, path: String, content_type: Option<String>, data: Vec<u8>)
+    {
+        self.files.push(BundleFile
+        {
+            path,
+            content_type,
+            data,
+        });
+    }
+
+    pub fn file(&self, path: &str) -> Option<&BundleFile>
+    {
+        self.files.iter().find(|x| x.path == path)
+    }
+
+}
+
+pub fn guess_content_type(path: &str) -> Option<String>
+{
+    let (_, extension) = path.rsplit_once('.')?;
+    let content_type =
+        match extension.to_lowercase().as_str()
+        {
+            "html" | "htm" => "text/html",
+            "css" => "text/css",
+            "js" => "application/javascript",
+            "json" => "application/json",
+            "txt" => "text/plain",
+            "svg" => "image/svg+xml",
+            "png" => "image/png",
+            "jpg" | "jpeg" => "image/jpeg",
+            "gif" => "image/gif",
+            "ico" => "image/x-icon",
+            "webp" => "image/webp",
+            "xml" => "application/xml",
+            "pdf" => "application/pdf",
+            "woff" => "font/woff",
+            "woff2" => "font/woff2",
+            "mp3" => "audio/mpeg",
+            "mp4" => "video/mp4",
+            "webm" => "video/webm",
+            "wasm" => "application/wasm",
+            _ => return None,
+        };
+
+    Some(content_type.to_owned())
+}
@@ -0,0 +1,121 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use crate::hash::Hash;
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use rand::Rng;
+
+const MAX_CHALLENGE_RANGE: u32 = 4 * 1024;
+
+/// Asks a node to prove it has a chunk by hashing part of it. The nonce
+/// stops the answer being worked out once and reused.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StorageChallenge
+{
+    pub chunk: Hash,
+    pub start: u32,
+    pub end: u32,
+    pub nonce: u64,
+}
+
+impl StorageChallenge
+{
+
+    pub fn new(chunk: Hash, start: u32, end: u32, nonce: u64) -> Self
+    {
+        Self
+        {
+            chunk,
+            start,
+            end,
+            nonce,
+        }
+    }
+
+    fn from_start(chunk: Hash, chunk_length: usize, start: u32, nonce: u64) -> Self
+    {
+        let length = chunk_length as u32;
+        let start = if length == 0 { 0 } else { start % length };
+        let end = std::cmp::min(start + MAX_CHALLENGE_RANGE, length);
+        Self::new(chunk, start, end, nonce)
+    }
+
+    /// Challenge a random range of a chunk of this length.
+    pub fn random(chunk: Hash, chunk_length: usize) -> Self
+    {
+        let mut rng = rand::thread_rng();
+        Self::from_start(chunk, chunk_length, rng.gen(), rng.gen())
+    }
+
+    /// Challenge a range picked by the seed, so anyone with the same seed
+    /// can work out the same challenge.
+    pub fn from_seed(seed: &Hash, chunk: Hash, chunk_length: usize) -> Self
+    {
+        let mut hasher = Sha256::default();
+        hasher.update(seed.data());
+        hasher.update(chunk.data());
+        let bytes = hasher.finalize();
+
+        let start = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let mut nonce = [0u8; 8];
+        nonce.copy_from_slice(&bytes[4..12]);
+        Self::from_start(chunk, chunk_length, start, u64::from_le_bytes(nonce))
+    }
+
+    /// The answer to this challenge given the chunk's data, or none if the
+    /// range doesn't fit in it.
+    pub fn prove(&self, data: &[u8]) -> Option<Hash>
+    {
+        let range = data.get(self.start as usize..self.end as usize)?;
+
+        let mut hasher = Sha256::default();
+        hasher.update(self.nonce.to_le_bytes());
+        hasher.update(self.chunk.data());
+        hasher.update(range);
+        Some(Hash::from(&hasher.finalize()))
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+    use crate::data_store::page::content_hash;
+
+    #[test]
+    fn test_storage_challenge()
+    {
+        let data = (0..10_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let chunk = content_hash(&data);
+
+        let challenge = StorageChallenge::random(chunk, data.len());
+        assert!(challenge.start < challenge.end);
+        assert!(challenge.end as usize <= data.len());
+
+        let proof = challenge.prove(&data).unwrap();
+        let mut altered = data.clone();
+        altered[challenge.start as usize] ^= 1;
+        assert_ne!(challenge.prove(&altered), Some(proof));
+
+        // The same range with another nonce gives a different answer
+        let other = StorageChallenge::new(chunk, challenge.start, challenge.end, challenge.nonce + 1);
+        assert_ne!(other.prove(&data), Some(proof));
+
+        // Seeded challenges are the same every time
+        let seed = Hash::empty();
+        let seeded = StorageChallenge::from_seed(&seed, chunk, data.len());
+        assert_eq!(seeded, StorageChallenge::from_seed(&seed, chunk, data.len()));
+        assert!(seeded.end as usize <= data.len());
+
+        // Ranges outside the data can't be answered
+        let outside = StorageChallenge::new(chunk, 0, data.len() as u32 + 1, 0);
+        assert_eq!(outside.prove(&data), None);
+    }
+
+}
@@ -0,0 +1,179 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::data_unit::DataUnit;
+use crate::hash::Hash;
+use crate::error::ErrorMessage;
+use std::collections::BTreeMap;
+use std::error::Error;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SiteFile
+{
+    pub data: Vec<u8>,
+
+    /// The MIME type the file was published with, if any
+    pub content_type: Option<String>,
+
+    /// Chunk root of the page update that last changed the file's data,
+    /// so it changes whenever the file does
+    pub chunk_root: Hash,
+}
+
+/// The files of a site, as left by replaying its page updates in order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SiteTree
+{
+    files: BTreeMap<String, SiteFile>,
+}
+
+impl SiteTree
+{
+
+    pub fn new() -> Self
+    {
+        Self::default()
+    }
+
+    /// Build a tree from page updates, each with its page's chunk root.
+    pub fn from_updates<'a, I>(updates: I) -> Self
+        where I: IntoIterator<Item = &'a (DataUnit, Hash)>
+    {
+        let mut tree = Self::new();
+        for (data_unit, chunk_root) in updates
+        {
+            if let Err(err) = tree.apply(data_unit, chunk_root) {
+                warn!("Skipping page update: {}", err);
+            }
+        }
+
+        tree
+    }
+
+    pub fn apply(&mut self, data_unit: &DataUnit, chunk_root: &Hash) -> Result<(), Box<dyn Error>>
+    {
+        match data_unit
+        {
+            DataUnit::CreatePage(create_page) =>
+            {
+                let file = SiteFile
+                {
+                    data: create_page.page.clone(),
+                    content_type: create_page.content_type.clone(),
+                    chunk_root: *chunk_root,
+                };
+                self.files.insert(create_page.name.clone(), file);
+            },
+
+            DataUnit::SiteBundle(bundle) =>
+            {
+                self.files.clear();
+                for bundle_file in &bundle.files
+                {
+                    let file = SiteFile
+                    {
+                        data: bundle_file.data.clone(),
+                        content_type: bundle_file.content_type.clone(),
+                        chunk_root: *chunk_root,
+                    };
+                    self.files.insert(bundle_file.path.clone(), file);
+                }
+            },
+
+            DataUnit::DeletePage(delete_page) =>
+            {
+                if self.files.remove(&delete_page.name).is_none() {
+                    return Err(ErrorMessage::new(&format!("No page '{}' to delete", delete_page.name)));
+                }
+            },
+
+            DataUnit::RenamePage(rename_page) =>
+            {
+                let file_or_none = self.files.remove(&rename_page.from);
+                if file_or_none.is_none() {
+                    return Err(ErrorMessage::new(&format!("No page '{}' to rename", rename_page.from)));
+                }
+                self.files.insert(rename_page.to.clone(), file_or_none.unwrap());
+            },
+
+            DataUnit::PatchPage(patch_page) =>
+            {
+                let file_or_none = self.files.get_mut(&patch_page.name);
+                if file_or_none.is_none() {
+                    return Err(ErrorMessage::new(&format!("No page '{}' to patch", patch_page.name)));
+                }
+
+                let file = file_or_none.unwrap();
+                file.data = patch_page.apply(&file.data)?;
+                file.chunk_root = *chunk_root;
+            },
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, path: &str) -> Option<&SiteFile>
+    {
+        self.files.get(path)
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &String>
+    {
+        self.files.keys()
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+    use super::super::page::*;
+
+    #[test]
+    fn test_site_tree()
+    {
+        let mut bundle = SiteBundleData::new();
+        bundle.add_file("index.html".to_owned(), Some("text/html".to_owned()), b"<h1>Hello, world!</h1>".to_vec());
+        bundle.add_file("old.html".to_owned(), None, b"old".to_vec());
+
+        let base = b"<h1>Hello, world!</h1>".to_vec();
+        let new = b"<h1>Hello, there world!</h1>".to_vec();
+        let patch = PatchPageData::diff("index.html".to_owned(), &base, &new);
+        assert_eq!(patch.operations.len(), 3);
+        assert_eq!(patch.apply(&base).unwrap(), new);
+        assert_eq!(patch.apply(b"something else").is_err(), true);
+
+        let data_units = vec![
+            DataUnit::CreatePage(CreatePageData::new("stale.html".to_owned(), Vec::new())),
+            DataUnit::SiteBundle(bundle),
+            DataUnit::RenamePage(RenamePageData { from: "old.html".to_owned(), to: "new.html".to_owned() }),
+            DataUnit::PatchPage(patch.clone()),
+            DataUnit::CreatePage(CreatePageData::new("temp.html".to_owned(), Vec::new())),
+            DataUnit::DeletePage(DeletePageData { name: "temp.html".to_owned() }),
+
+            // Applying the same patch again no longer matches the base
+            DataUnit::PatchPage(patch),
+        ];
+
+        let updates = data_units.into_iter()
+            .enumerate()
+            .map(|(i, data_unit)| (data_unit, Hash::from(&[i as u8; 32])))
+            .collect::<Vec<_>>();
+
+        let tree = SiteTree::from_updates(&updates);
+        assert_eq!(tree.paths().collect::<Vec<_>>(), vec!["index.html", "new.html"]);
+        assert_eq!(tree.get("index.html").unwrap().data, new);
+        assert_eq!(tree.get("index.html").unwrap().content_type, Some("text/html".to_owned()));
+        assert_eq!(tree.get("new.html").unwrap().data, b"old".to_vec());
+
+        // Files are versioned by the update that last changed them
+        assert_eq!(tree.get("index.html").unwrap().chunk_root, updates[3].1);
+        assert_eq!(tree.get("new.html").unwrap().chunk_root, updates[1].1);
+    }
+
+}
@@ -8,6 +8,7 @@ extern crate tcp_channel;
 extern crate serde_json;
 extern crate sha2;
 extern crate bincode;
+extern crate flate2;
 extern crate base_62;
 extern crate rsa;
 extern crate rand;
@@ -5,11 +5,82 @@
  */
 
 use crate::hash::Hash;
+use crate::config::MERKLE_V2_HEIGHT;
 use sha2::{Sha256, Digest};
+use serde::{Serialize, Deserialize};
 
 type Node = Vec<u8>;
 
-fn reduce_nodes(nodes: Vec<Node>) -> Vec<Node>
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// How the tree is hashed. Changing this changes the roots in block headers,
+/// so each version is only used from the block height it activates at.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum MerkleVersion
+{
+    /// Leaves and nodes are hashed the same way, so an internal node can be
+    /// passed off as a leaf and different lists can share a root
+    V1,
+
+    /// Leaves and nodes are prefixed so they can never be mistaken for each
+    /// other. An unpaired last node is moved up a level as it is, which
+    /// gives one tree shape for each number of leaves (the same as RFC 6962)
+    V2,
+}
+
+impl MerkleVersion
+{
+
+    pub fn for_block(block_id: u64) -> Self
+    {
+        if block_id >= MERKLE_V2_HEIGHT {
+            MerkleVersion::V2
+        } else {
+            MerkleVersion::V1
+        }
+    }
+
+}
+
+/// Which side of the path a sibling node is on.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Side
+{
+    Left,
+    Right,
+}
+
+/// The siblings needed to work out the root from one item, from the leaves up.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MerkleProof
+{
+    pub version: MerkleVersion,
+    pub path: Vec<(Side, Hash)>,
+}
+
+fn hash_leaf(data: &[u8], version: MerkleVersion) -> Node
+{
+    let mut hasher = Sha256::default();
+    if version == MerkleVersion::V2 {
+        hasher.update([LEAF_PREFIX]);
+    }
+    hasher.update(data);
+    hasher.finalize().to_vec()
+}
+
+fn hash_pair(left: &[u8], right: &[u8], version: MerkleVersion) -> Node
+{
+    let mut hasher = Sha256::default();
+    if version == MerkleVersion::V2 {
+        hasher.update([NODE_PREFIX]);
+    }
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().to_vec()
+}
+
+fn reduce_nodes(nodes: Vec<Node>, version: MerkleVersion) -> Vec<Node>
 {
     nodes
         .chunks(2)
@@ -19,39 +90,200 @@ fn reduce_nodes(nodes: Vec<Node>) -> Vec<Node>
                 return pair[0].clone();
             }
 
-            let mut hasher = Sha256::default();
-            hasher.update(&pair[0]);
-            hasher.update(&pair[1]);
-            hasher.finalize().to_vec()
+            hash_pair(&pair[0], &pair[1], version)
         })
         .collect::<Vec<_>>()
 }
 
-pub fn calculate_merkle_root<H>(data: &[H]) -> Hash
+fn leaves<H>(data: &[H], version: MerkleVersion) -> Vec<Node>
+    where H: AsRef<[u8]>
+{
+    data
+        .iter()
+        .map(|x| hash_leaf(x.as_ref(), version))
+        .collect::<Vec<_>>()
+}
+
+pub fn calculate_merkle_root<H>(data: &[H], version: MerkleVersion) -> Hash
     where H: AsRef<[u8]>
 {
     if data.len() == 0 {
         return Hash::empty();
     }
 
-    let mut nodes = data
-        .iter()
-        .map(|x| 
-        {
-            let mut hasher = Sha256::default();
-            hasher.update(&x);
-            hasher.finalize().to_vec()
-        })
-        .collect::<Vec<_>>();
-
+    let mut nodes = leaves(data, version);
     while nodes.len() != 1 {
-        nodes = reduce_nodes(nodes);
+        nodes = reduce_nodes(nodes, version);
     }
 
     let root = &nodes[0];
     Hash::from(root)
 }
 
+/// Proof that the item at `index` is in the tree built from `data`.
+pub fn calculate_merkle_proof<H>(data: &[H], mut index: usize, version: MerkleVersion) 
+        -> Option<MerkleProof>
+    where H: AsRef<[u8]>
+{
+    if index >= data.len() {
+        return None;
+    }
+
+    let mut path = Vec::new();
+    let mut nodes = leaves(data, version);
+    while nodes.len() != 1
+    {
+        // NOTE: An unpaired last node is moved up as it is, so has no sibling
+        let sibling = index ^ 1;
+        if sibling < nodes.len()
+        {
+            let side = if sibling < index { Side::Left } else { Side::Right };
+            path.push((side, Hash::from(&nodes[sibling])));
+        }
+
+        nodes = reduce_nodes(nodes, version);
+        index /= 2;
+    }
+
+    Some(MerkleProof { version, path })
+}
+
+impl MerkleProof
+{
+
+    pub fn root(&self, item: &[u8]) -> Hash
+    {
+        let mut node = hash_leaf(item, self.version);
+        for (side, sibling) in &self.path
+        {
+            node = match side
+            {
+                Side::Left => hash_pair(sibling.data(), &node, self.version),
+                Side::Right => hash_pair(&node, sibling.data(), self.version),
+            };
+        }
+
+        Hash::from(&node)
+    }
+
+    pub fn verify(&self, item: &[u8], root: &Hash) -> bool
+    {
+        &self.root(item) == root
+    }
+
+    /// Also check the item is at `index` in a tree of `count` items, by
+    /// which sides its siblings are on at each level.
+    pub fn verify_at(&self, item: &[u8], mut index: usize, mut count: usize, root: &Hash) -> bool
+    {
+        if index >= count {
+            return false;
+        }
+
+        let mut sides = Vec::new();
+        while count > 1
+        {
+            let sibling = index ^ 1;
+            if sibling < count {
+                sides.push(if sibling < index { Side::Left } else { Side::Right });
+            }
+
+            index /= 2;
+            count = count.div_ceil(2);
+        }
+
+        let path_sides = self.path.iter().map(|(side, _)| *side).collect::<Vec<_>>();
+        path_sides == sides && self.verify(item, root)
+    }
+
+}
+
+/// The siblings needed to work out the root from a run of items next to 
+/// each other, from the leaves up. Which levels need a sibling on which 
+/// side follows from where the run is, so only the hashes are kept.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MerkleRangeProof
+{
+    pub version: MerkleVersion,
+    pub siblings: Vec<Hash>,
+}
+
+/// Proof that the items from `start` up to `end` are in the tree built 
+/// from `data`.
+pub fn calculate_merkle_range_proof<H>(data: &[H], mut start: usize, mut end: usize, 
+                                       version: MerkleVersion) 
+        -> Option<MerkleRangeProof>
+    where H: AsRef<[u8]>
+{
+    if start >= end || end > data.len() {
+        return None;
+    }
+
+    let mut siblings = Vec::new();
+    let mut nodes = leaves(data, version);
+    while nodes.len() != 1
+    {
+        if start % 2 == 1 {
+            siblings.push(Hash::from(&nodes[start - 1]));
+        }
+        if end % 2 == 1 && end < nodes.len() {
+            siblings.push(Hash::from(&nodes[end]));
+        }
+
+        nodes = reduce_nodes(nodes, version);
+        start /= 2;
+        end = end.div_ceil(2);
+    }
+
+    Some(MerkleRangeProof { version, siblings })
+}
+
+impl MerkleRangeProof
+{
+
+    /// Check the items are at `start` in a tree of `count` items.
+    pub fn verify_at<H>(&self, items: &[H], mut start: usize, mut count: usize, root: &Hash) -> bool
+        where H: AsRef<[u8]>
+    {
+        let mut end = start + items.len();
+        if items.is_empty() || end > count {
+            return false;
+        }
+
+        let mut siblings = self.siblings.iter();
+        let mut nodes = leaves(items, self.version);
+        while count > 1
+        {
+            if start % 2 == 1
+            {
+                match siblings.next()
+                {
+                    Some(sibling) => nodes.insert(0, sibling.data().to_vec()),
+                    None => return false,
+                }
+                start -= 1;
+            }
+
+            if end % 2 == 1 && end < count
+            {
+                match siblings.next()
+                {
+                    Some(sibling) => nodes.push(sibling.data().to_vec()),
+                    None => return false,
+                }
+                end += 1;
+            }
+
+            nodes = reduce_nodes(nodes, self.version);
+            start /= 2;
+            end = end.div_ceil(2);
+            count = count.div_ceil(2);
+        }
+
+        siblings.next().is_none() && &Hash::from(&nodes[0]) == root
+    }
+
+}
+
 #[cfg(test)]
 mod tests
 {
@@ -71,16 +303,107 @@ mod tests
             247, 132, 161, 187, 217, 199, 158, 253, 81, 231]);
 
         {
-            let merkle_root = calculate_merkle_root(&[&a, &b, &c]);
+            let merkle_root = calculate_merkle_root(&[&a, &b, &c], MerkleVersion::V1);
             assert_eq!(merkle_root, expected_hash);
         }
 
         {
-            let merkle_root = calculate_merkle_root(&[&a, &c, &b]);
+            let merkle_root = calculate_merkle_root(&[&a, &c, &b], MerkleVersion::V1);
             assert_ne!(merkle_root, expected_hash);
         }
 
-        assert_eq!(calculate_merkle_root::<Vec<u8>>(&[]), Hash::empty());
+        assert_eq!(calculate_merkle_root::<Vec<u8>>(&[], MerkleVersion::V1), Hash::empty());
+    }
+
+    #[test]
+    fn test_merkle_proof()
+    {
+        for version in [MerkleVersion::V1, MerkleVersion::V2]
+        {
+            for count in 1..10u8
+            {
+                let data = (0..count).map(|i| vec![i; i as usize + 1]).collect::<Vec<_>>();
+                let root = calculate_merkle_root(&data, version);
+                for (index, item) in data.iter().enumerate()
+                {
+                    let proof = calculate_merkle_proof(&data, index, version).unwrap();
+                    assert!(proof.verify(item, &root));
+                    assert!(proof.verify_at(item, index, data.len(), &root));
+                    assert!(!proof.verify(&[0xFF], &root));
+                }
+
+                assert_eq!(calculate_merkle_proof(&data, data.len(), version), None);
+            }
+        }
+
+        // A proof for one item doesn't work for another
+        let data = vec![vec![1], vec![2], vec![3]];
+        let root = calculate_merkle_root(&data, MerkleVersion::V2);
+        let proof = calculate_merkle_proof(&data, 0, MerkleVersion::V2).unwrap();
+        assert!(!proof.verify(&data[1], &root));
+        assert!(!proof.verify_at(&data[0], 1, data.len(), &root));
+        assert_eq!(proof.path[1].0, Side::Right);
+
+        // The last item is promoted, so its proof is only valid for this count
+        let proof = calculate_merkle_proof(&data, 2, MerkleVersion::V2).unwrap();
+        assert!(proof.verify_at(&data[2], 2, data.len(), &root));
+        assert!(!proof.verify_at(&data[2], 2, data.len() + 1, &root));
+    }
+
+    #[test]
+    fn test_merkle_domain_separation()
+    {
+        let (a, b, c) = (vec![1u8], vec![2u8], vec![3u8]);
+        let node = |version| [hash_leaf(&a, version), hash_leaf(&b, version)].concat();
+        let root = |data: &[Vec<u8>], version| calculate_merkle_root(data, version);
+
+        // An internal node passed off as a leaf
+        let (v1, v2) = (MerkleVersion::V1, MerkleVersion::V2);
+        assert_eq!(root(&[a.clone(), b.clone()], v1), root(&[node(v1)], v1));
+        assert_ne!(root(&[a.clone(), b.clone()], v2), root(&[node(v2)], v2));
+
+        // The same, with the unpaired last node moved up a level
+        assert_eq!(root(&[a.clone(), b.clone(), c.clone()], v1), root(&[node(v1), c.clone()], v1));
+        assert_ne!(root(&[a.clone(), b.clone(), c.clone()], v2), root(&[node(v2), c.clone()], v2));
+
+        // Repeating the last item doesn't give the same root
+        assert_ne!(root(&[a.clone(), b.clone(), c.clone()], v2), root(&[a.clone(), b.clone(), c.clone(), c.clone()], v2));
+
+        assert_eq!(MerkleVersion::for_block(MERKLE_V2_HEIGHT - 1), v1);
+        assert_eq!(MerkleVersion::for_block(MERKLE_V2_HEIGHT), v2);
+    }
+
+    #[test]
+    fn test_merkle_range_proof()
+    {
+        for count in 1..12u8
+        {
+            let data = (0..count).map(|i| vec![i; i as usize + 1]).collect::<Vec<_>>();
+            let root = calculate_merkle_root(&data, MerkleVersion::V2);
+            for start in 0..data.len()
+            {
+                for end in start + 1..=data.len()
+                {
+                    let proof = calculate_merkle_range_proof(&data, start, end, MerkleVersion::V2).unwrap();
+                    assert!(proof.verify_at(&data[start..end], start, data.len(), &root));
+                    assert!(!proof.verify_at(&data[start..end - 1], start, data.len(), &root));
+                    if start > 0 {
+                        assert!(!proof.verify_at(&data[start - 1..end - 1], start - 1, data.len(), &root));
+                    }
+                }
+            }
+
+            assert_eq!(calculate_merkle_range_proof(&data, 0, 0, MerkleVersion::V2), None);
+            assert_eq!(calculate_merkle_range_proof(&data, 0, data.len() + 1, MerkleVersion::V2), None);
+        }
+
+        // The whole tree needs no siblings, and a single item matches its 
+        // usual proof
+        let data = (0..5u8).map(|i| vec![i]).collect::<Vec<_>>();
+        assert_eq!(calculate_merkle_range_proof(&data, 0, 5, MerkleVersion::V2).unwrap().siblings.len(), 0);
+        let single = calculate_merkle_range_proof(&data, 2, 3, MerkleVersion::V2).unwrap();
+        let proof = calculate_merkle_proof(&data, 2, MerkleVersion::V2).unwrap();
+        assert_eq!(single.siblings, proof.path.iter().map(|(_, hash)| *hash).collect::<Vec<_>>());
     }
 
 }
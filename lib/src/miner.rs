@@ -6,6 +6,8 @@
 
 use crate::block::Block;
 use crate::block::validate::BlockValidationResult;
+use crate::block::storage_proof::{ChunkPosition, StorageSegment};
+use crate::hash::Hash;
 
 pub fn mine_block(mut block: Block) -> Block
 {
@@ -16,3 +18,16 @@ pub fn mine_block(mut block: Block) -> Block
     block
 }
 
+
+/// Mine a block, proving it stores chunks from `page_chunks` for each nonce
+/// where it can, so less work is needed.
+pub fn mine_block_with_storage_proof<F>(mut block: Block, page_chunks: &[(Hash, u32)], 
+                                        mut get_segment: F) -> Block
+    where F: FnMut(&ChunkPosition) -> Option<StorageSegment>
+{
+    while block.validate_pow_with_storage_proof(page_chunks, &mut get_segment).unwrap() != BlockValidationResult::Ok {
+        block.header.pow += 1;
+    }
+
+    block
+}
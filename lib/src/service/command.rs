@@ -7,8 +7,14 @@
 use crate::wallet::WalletStatus;
 use crate::transaction::{Transaction, TransactionVariant};
 use crate::transaction::page::Page;
-use crate::block::Block;
+use crate::transaction::transfer::Transfer;
+use crate::transaction::name::NameRecord;
+use crate::transaction::unsigned::UnsignedTransaction;
+use crate::wallet::public_wallet::PublicWallet;
+use crate::block::{Block, BlockHeader};
+use crate::merkle_tree::MerkleProof;
 use crate::data_store::data_unit::DataUnit;
+use crate::hash::Hash;
 use serde::{Serialize, Deserialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -17,14 +23,58 @@ pub enum Command
     Exit,
     Balance(Vec<u8>),
     Send(Vec<(Vec<u8>, f32)>, Vec<(Vec<u8>, f32)>, f32),
-    UpdatePage(Vec<u8>, String, Vec<u8>),
+    CreateTransfer(Vec<(PublicWallet, f32)>, Vec<(Vec<u8>, f32)>, f32),
+    SubmitTransfer(Transaction<Transfer>),
+    UpdatePage(Vec<u8>, Option<Vec<u8>>, String, Vec<u8>),
+    PublishData(Vec<u8>, Option<Vec<u8>>, DataUnit),
+    TransferSite(Vec<u8>, Vec<u8>, Vec<u8>, f32),
+    SitesOwned(Vec<u8>),
+    RegisterName(Vec<u8>, String, Vec<u8>, Vec<u8>, f32),
+    ResolveName(String),
     TransactionInfo(Vec<u8>),
     TransactionHistory(Vec<u8>),
+    TransactionProof(Vec<u8>),
     Blocks(u64, u64),
     TopBlock,
     PageUpdates(Vec<u8>),
     PageData(Vec<u8>),
+    SiteFile(Vec<u8>, String),
     Statistics,
+    ExportSnapshot(String),
+    VerifySnapshot(String),
+    Bans,
+    Unban(Option<String>),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ReplicationAction
+{
+    Fetch,
+    Drop,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplicationDecision
+{
+    pub chunk: Hash,
+    pub action: ReplicationAction,
+
+    /// Nodes known to store the chunk when the decision was made
+    pub holders: usize,
+    pub timestamp: u128,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SyncProgress
+{
+    pub peer: String,
+
+    /// Where our chain and the one being synced split
+    pub fork_block: u64,
+    pub top_block: u64,
+    pub headers: u64,
+    pub blocks: u64,
+    pub requests_in_flight: usize,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -33,6 +83,18 @@ pub struct Statistics
     pub hash_rate: f64,
     pub known_chunks: usize,
     pub replication: f64,
+
+    pub replication_target: usize,
+    pub under_replicated: usize,
+    pub over_replicated: usize,
+    pub disk_usage: u64,
+    pub disk_quota: u64,
+
+    /// Most recent first
+    pub replication_decisions: Vec<ReplicationDecision>,
+
+    /// Set while catching up with a longer chain
+    pub sync: Option<SyncProgress>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -41,12 +103,24 @@ pub enum Response
     Exit,
     WalletStatus(WalletStatus),
     Sent(Vec<u8>),
+    UnsignedTransfer(UnsignedTransaction<Transfer>),
     TransactionInfo(TransactionVariant, Option<Block>),
     TransactionHistory(Vec<(TransactionVariant, Option<Block>)>),
+    TransactionProof(BlockHeader, MerkleProof),
     Blocks(Vec<Block>),
     PageUpdates(Vec<Transaction<Page>>),
     PageData(DataUnit),
+    SiteFile(Vec<u8>, Option<String>, Hash),
+    Sites(Vec<Hash>),
+    Name(NameRecord),
     Statistics(Statistics),
+    Snapshot(u64, Hash),
+
+    /// Banned nodes, with when their ban ends in milliseconds
+    Bans(Vec<(String, u128)>),
+
+    /// No other node answered in time
+    TimedOut,
     Failed,
 }
 
@@ -5,18 +5,19 @@
  */
 
 use super::{Input, Transaction, TransactionHeader, TransactionContent};
+use super::unsigned::UnsignedTransaction;
 use crate::wallet::Wallet;
 use crate::wallet::private_wallet::PrivateWallet;
-use crate::hash::Signature;
+use crate::wallet::public_wallet::PublicWallet;
+use crate::error::ErrorMessage;
 use serde::Serialize;
-use std::collections::HashMap;
 use std::error::Error;
 
 pub struct TransactionBuilder<'a, C>
     where C: TransactionContent
 {
     content: C,
-    inputs: Vec<(&'a PrivateWallet, Input)>,
+    inputs: Vec<(Option<&'a PrivateWallet>, Input)>,
 }
 
 impl<'a, C> TransactionBuilder<'a, C>
@@ -40,12 +41,31 @@ impl<'a, C> TransactionBuilder<'a, C>
             e: wallet.get_e(),
             amount,
         };
-        
-        self.inputs.push((wallet, input));
+
+        self.inputs.push((Some(wallet), input));
         self
     }
 
-    pub fn build(self) -> Result<Transaction<C>, Box<dyn Error>>
+    pub fn add_unsigned_input(mut self, wallet: &PublicWallet, amount: f32)
+        -> Result<Self, Box<dyn Error>>
+    {
+        let e_or_none = wallet.get_e();
+        if e_or_none.is_none() {
+            return Err(ErrorMessage::new("Public wallet has no exponent"));
+        }
+
+        let input = Input
+        {
+            from: wallet.get_public_key(),
+            e: e_or_none.unwrap(),
+            amount,
+        };
+
+        self.inputs.push((None, input));
+        Ok(self)
+    }
+
+    pub fn build_unsigned(self) -> Result<UnsignedTransaction<C>, Box<dyn Error>>
     {
         let header = TransactionHeader
         {
@@ -53,16 +73,20 @@ impl<'a, C> TransactionBuilder<'a, C>
             inputs: self.inputs.iter().map(|x| x.1.clone()).collect::<Vec<_>>(),
         };
 
-        let header_hash = header.hash()?;
-        let mut signatures = HashMap::new();
-        for (wallet, input) in &self.inputs 
+        let mut unsigned = UnsignedTransaction::new(header);
+        for (wallet_or_none, _) in &self.inputs
         {
-            let signature = Signature::from(&wallet.sign(header_hash.data())?);
-            signatures.insert(input.get_address(), signature);
+            if let Some(wallet) = wallet_or_none {
+                unsigned.sign(wallet)?;
+            }
         }
 
-        Ok(Transaction::new(header, signatures))
+        Ok(unsigned)
     }
 
-}
+    pub fn build(self) -> Result<Transaction<C>, Box<dyn Error>>
+    {
+        self.build_unsigned()?.into_transaction()
+    }
 
+}
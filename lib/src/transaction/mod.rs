@@ -6,9 +6,14 @@
 
 pub mod transfer;
 pub mod page;
+pub mod site_transfer;
+pub mod name;
 pub mod builder;
+pub mod unsigned;
 use transfer::Transfer;
 use page::Page;
+use site_transfer::SiteTransfer;
+use name::NameRegistration;
 use crate::wallet::WalletStatus;
 use crate::wallet::public_wallet::{PublicWallet, WalletValidationResult};
 use crate::hash::{Hash, Signature};
@@ -25,6 +30,8 @@ pub enum TransactionValidationResult
 {
     Ok,
     Negative,
+    Fee,
+    Name,
     Wallet(WalletValidationResult),
 }
 
@@ -44,6 +51,20 @@ pub trait TransactionContent
 
     fn get_id(&self) -> u32;
 
+    /// The site this transaction acts on, which requires it to be signed
+    /// by the site's current owner.
+    fn get_site(&self) -> Option<Hash>
+    {
+        None
+    }
+
+    /// The name this transaction registers, which requires it to be signed
+    /// by the name's current owner, if it has one.
+    fn get_name(&self) -> Option<String>
+    {
+        None
+    }
+
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -75,6 +96,8 @@ pub enum TransactionVariant
 {
     Transfer(Transaction<Transfer>),
     Page(Transaction<Page>),
+    SiteTransfer(Transaction<SiteTransfer>),
+    NameRegistration(Transaction<NameRegistration>),
 }
 
 impl std::fmt::Display for TransactionValidationResult
@@ -86,6 +109,8 @@ impl std::fmt::Display for TransactionValidationResult
         {
             TransactionValidationResult::Ok => write!(f, "Ok"),
             TransactionValidationResult::Negative => write!(f, "Can't have negitive transfer amounts"),
+            TransactionValidationResult::Fee => write!(f, "Fee is below the minimum"),
+            TransactionValidationResult::Name => write!(f, "Invalid name"),
             TransactionValidationResult::Wallet(wallet) => write!(f, "{}", wallet),
         }
     }
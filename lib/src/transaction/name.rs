@@ -0,0 +1,148 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::{Input, TransactionContent, TransactionValidationResult};
+use crate::wallet::WalletStatus;
+use crate::config::{NAME_MAX_LEN, NAME_REGISTRATION_FEE};
+use crate::error::ErrorMessage;
+use crate::hash::Hash;
+
+use serde::{Serialize, Deserialize};
+use std::error::Error;
+
+/// Claim a name for a site until it expires. Registering a name again
+/// before then renews it, and can only be done by its current owner, who
+/// may also point it at a new site or hand it to a new owner.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct NameRegistration
+{
+    pub id: u32,
+    pub name: String,
+    pub site: Hash,
+    pub owner: Hash,
+    pub fee: f32,
+}
+
+/// What a name currently resolves to.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct NameRecord
+{
+    pub site: Hash,
+    pub owner: Hash,
+    pub expires: u64,
+}
+
+pub fn is_valid_name(name: &str) -> bool
+{
+    !name.is_empty() && 
+        name.len() <= NAME_MAX_LEN &&
+        !name.starts_with('-') &&
+        name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+impl NameRegistration
+{
+
+    pub fn new(id: u32, name: String, site: Hash, owner: Hash, fee: f32) -> Self
+    {
+        Self
+        {
+            id,
+            name,
+            site,
+            owner,
+            fee,
+        }
+    }
+
+}
+
+impl TransactionContent for NameRegistration
+{
+
+    fn get_fee(&self) -> f32
+    {
+        self.fee
+    }
+
+    fn validate(&self, inputs: &Vec<Input>)
+        -> Result<TransactionValidationResult, Box<dyn Error>>
+    {
+        if !is_valid_name(&self.name) {
+            return Ok(TransactionValidationResult::Name);
+        }
+
+        if self.fee < NAME_REGISTRATION_FEE {
+            return Ok(TransactionValidationResult::Fee);
+        }
+
+        let total_input = inputs.iter().fold(0.0, |acc, x| acc + x.amount);
+        if total_input != self.fee {
+            return Ok(TransactionValidationResult::Negative);
+        }
+
+        Ok(TransactionValidationResult::Ok)
+    }
+
+    fn update_wallet_status(&self, _address: &Hash, mut status: WalletStatus,
+                            from_amount: f32, is_block_winner: bool)
+        -> Result<WalletStatus, Box<dyn Error>>
+    {
+        if from_amount > 0.0
+        {
+            status.balance -= from_amount;
+            if self.id <= status.max_id 
+            {
+                return Err(ErrorMessage::new(
+                    &format!("Id is not incremental ({} -> {})",
+                        status.max_id, self.id)));
+            }
+            status.max_id = self.id;
+        }
+
+        if is_block_winner {
+            status.balance += self.fee;
+        }
+
+        Ok(status)
+    }
+
+    fn get_to_addresses(&self) -> Vec<Hash>
+    {
+        vec![self.site, self.owner]
+    }
+
+    fn get_id(&self) -> u32
+    {
+        self.id
+    }
+
+    fn get_name(&self) -> Option<String>
+    {
+        Some( self.name.clone() )
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+
+    #[test]
+    fn test_name()
+    {
+        assert_eq!(is_valid_name("my-site"), true);
+        assert_eq!(is_valid_name("site2"), true);
+        assert_eq!(is_valid_name(""), false);
+        assert_eq!(is_valid_name("-site"), false);
+        assert_eq!(is_valid_name("My-Site"), false);
+        assert_eq!(is_valid_name("my.site"), false);
+        assert_eq!(is_valid_name(&"a".repeat(NAME_MAX_LEN + 1)), false);
+    }
+
+}
@@ -7,81 +7,276 @@
 use super::{Input, TransactionContent, TransactionValidationResult};
 use crate::wallet::WalletStatus;
 use crate::data_store::data_unit::DataUnit;
+use crate::data_store::page::content_hash;
+use crate::merkle_tree::{calculate_merkle_root, calculate_merkle_range_proof, MerkleProof, MerkleRangeProof, MerkleVersion};
 use crate::error::ErrorMessage;
 use crate::hash::Hash;
-use crate::config::PAGE_CHUNK_SIZE;
+use crate::config::{PAGE_CHUNK_SIZE, PAGE_MAX_CHUNKS, CHUNK_MIN_SIZE, CHUNK_MAX_SIZE};
 use serde::{Serialize, Deserialize};
+use std::rc::Rc;
 use std::error::Error;
 
+/// Root of the tree over a page's chunk hashes. Pages are newer than
+/// `MerkleVersion::V2`, so always use it.
+pub fn merkle_root_for_chunks(hashes: &[Hash]) -> Hash
+{
+    calculate_merkle_root(hashes, MerkleVersion::V2)
+}
+
+fn is_in_tree(hash: &Hash, index: u32, count: u32, root: &Hash, proof: &MerkleProof) -> bool
+{
+    proof.version == MerkleVersion::V2 &&
+        proof.verify_at(hash.data(), index as usize, count as usize, root)
+}
+
+fn is_run_in_tree(reused: &ReusedChunks, index: u32, count: u32, root: &Hash, 
+                  proof: &MerkleRangeProof) -> bool
+{
+    proof.version == MerkleVersion::V2 &&
+        proof.verify_at(&reused.hashes, index as usize, count as usize, root)
+}
+
+/// Start and end of a run of reused chunks in a new page, then the root of 
+/// the earlier page, where the run starts in it and its chunk list
+type ReusedRun = (usize, usize, Hash, u32, Rc<Vec<Hash>>);
+
+/// A run of a page's chunks that's already in an earlier page on the 
+/// chain, so isn't paid for again. The hashes are only listed once, with a 
+/// proof of where the run is in each page.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct ReusedChunks
+{
+    pub index: u32,
+    pub hashes: Vec<Hash>,
+    pub proof: MerkleRangeProof,
+
+    /// Where the run starts in the earlier page
+    pub source_root: Hash,
+    pub source_index: u32,
+    pub source_proof: MerkleRangeProof,
+}
+
+impl ReusedChunks
+{
+
+    fn end(&self) -> u32
+    {
+        self.index.saturating_add(self.hashes.len() as u32)
+    }
+
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct Page
 {
     pub id: u32,
     pub site: Hash,
 
-    pub data_hashes: Vec<Hash>,
-    pub data_length: u32,
+    /// Merkle root over the hashes of the data unit's manifest, followed 
+    /// by each distinct chunk of content
+    pub chunk_root: Hash,
+    pub chunk_count: u32,
+    pub data_length: u64,
+
+    /// Bytes in chunks that aren't in `reused_chunks`, which is all that's 
+    /// paid for
+    pub new_data_length: u64,
+    pub reused_chunks: Vec<ReusedChunks>,
     pub fee: f32,
 }
 
 impl Page
 {
 
-    pub fn new(id: u32, site: Hash, 
-               data_hashes: Vec<Hash>, data_length: u32, 
-               fee: f32) -> Self
+    pub fn new(id: u32, site: Hash, chunk_root: Hash, chunk_count: u32, 
+               data_length: u64, fee: f32) -> Self
     {
         Page
         {
             id,
             site,
 
-            data_hashes,
+            chunk_root,
+            chunk_count,
             data_length,
+            new_data_length: data_length,
+            reused_chunks: Vec::new(),
             fee,
         }
     }
 
-    pub fn new_from_data(id: u32, site: Hash, data: &DataUnit, fee: f32) 
-        -> Result<Self, Box<dyn Error>>
+    /// Create a page for `data`. Chunks `find_chunk` gives the root and 
+    /// index of in an earlier page, along with that page's chunk list, are 
+    /// listed as reused, so aren't paid for. Chunks next to each other in 
+    /// both pages are listed together.
+    pub fn new_from_data<F>(id: u32, site: Hash, data: &DataUnit, 
+                            find_chunk: F, fee: f32) 
+            -> Result<Self, Box<dyn Error>>
+        where F: Fn(&Hash) -> Option<(Hash, u32, Rc<Vec<Hash>>)>
     {
-        let data_hashes = data.hashes()?;
-        let data_length = data.len()?;
-        Ok(Page
+        let chunks = data.chunks()?;
+        let hashes = chunks.iter().map(|(_, hash)| *hash).collect::<Vec<_>>();
+        let data_length = chunks.iter().map(|(chunk, _)| chunk.len() as u64).sum();
+        let mut page = Self::new(id, site, merkle_root_for_chunks(&hashes), 
+            hashes.len() as u32, data_length, fee);
+
+        let mut runs: Vec<ReusedRun> = Vec::new();
+        for (index, (chunk, hash)) in chunks.iter().enumerate()
         {
-            id,
-            site,
+            let source_or_none = find_chunk(hash);
+            if source_or_none.is_none() {
+                continue;
+            }
 
-            data_hashes,
-            data_length,
-            fee,
-        })
+            let (source_root, source_index, source_hashes) = source_or_none.unwrap();
+            page.new_data_length -= chunk.len() as u64;
+            let is_next_in_run = matches!(runs.last(), Some((start, end, root, run_source_index, _))
+                if *end == index && *root == source_root && 
+                   *run_source_index as usize + (end - start) == source_index as usize);
+            if is_next_in_run {
+                runs.last_mut().unwrap().1 += 1;
+            } else {
+                runs.push((index, index + 1, source_root, source_index, source_hashes));
+            }
+        }
+
+        for (start, end, source_root, source_index, source_hashes) in runs
+        {
+            let source_end = source_index as usize + end - start;
+            let source_proof_or_none = calculate_merkle_range_proof(&source_hashes, 
+                source_index as usize, source_end, MerkleVersion::V2);
+            if source_proof_or_none.is_none() {
+                return Err(ErrorMessage::new("Reused chunk isn't in its page"));
+            }
+
+            page.reused_chunks.push(ReusedChunks
+            {
+                index: start as u32,
+                hashes: hashes[start..end].to_vec(),
+                proof: calculate_merkle_range_proof(&hashes, start, end, MerkleVersion::V2).unwrap(),
+                source_root,
+                source_index,
+                source_proof: source_proof_or_none.unwrap(),
+            });
+        }
+
+        Ok(page)
+    }
+
+    fn reused_chunk_count(&self) -> u64
+    {
+        self.reused_chunks.iter().map(|reused| reused.hashes.len() as u64).sum()
+    }
+
+    fn new_chunk_count(&self) -> u32
+    {
+        (self.chunk_count as u64).saturating_sub(self.reused_chunk_count()) as u32
     }
 
     pub fn cost(&self) -> f32
     {
+        // NOTE: Lengths are only known to nodes with the data, so each new 
+        //       chunk is paid for as at least the smallest a chunk is cut 
+        //       to. Only the last chunk of each file can be shorter.
+        let min_length = self.new_chunk_count() as u64 * CHUNK_MIN_SIZE as u64;
+        let paid_length = std::cmp::max(self.new_data_length, min_length);
+
         // Bytes used into megabytes
-        self.data_length as f32 / PAGE_CHUNK_SIZE as f32
+        paid_length as f32 / PAGE_CHUNK_SIZE as f32
+    }
+
+    /// Check the lengths could be right for the number of chunks, and each
+    /// reused chunk is in this page where it says.
+    fn is_layout_valid(&self) -> bool
+    {
+        let fits = |length: u64, count: u32|
+            length >= count as u64 && length <= count as u64 * CHUNK_MAX_SIZE as u64;
+
+        if self.chunk_count == 0 || self.chunk_count > PAGE_MAX_CHUNKS {
+            return false;
+        }
+        if self.reused_chunk_count() > self.chunk_count as u64 {
+            return false;
+        }
+        if self.new_data_length > self.data_length {
+            return false;
+        }
+        if !fits(self.data_length, self.chunk_count) || 
+           !fits(self.new_data_length, self.new_chunk_count()) ||
+           !fits(self.data_length - self.new_data_length, self.reused_chunk_count() as u32)
+        {
+            return false;
+        }
+
+        let mut last_end = 0;
+        for reused in &self.reused_chunks
+        {
+            if reused.index < last_end {
+                return false;
+            }
+            if !is_run_in_tree(reused, reused.index, self.chunk_count, &self.chunk_root, &reused.proof) {
+                return false;
+            }
+            last_end = reused.end();
+        }
+
+        true
+    }
+
+    /// Check each run of reused chunks is in the earlier page it points to, 
+    /// given the chunk count of each page on the chain by its root.
+    pub fn is_reuse_valid<F>(&self, chunk_count_of: F) -> bool
+        where F: Fn(&Hash) -> Option<u32>
+    {
+        self.reused_chunks.iter().all(|reused|
+        {
+            match chunk_count_of(&reused.source_root)
+            {
+                Some(count) => is_run_in_tree(reused, reused.source_index, 
+                    count, &reused.source_root, &reused.source_proof),
+                None => false,
+            }
+        })
     }
 
     pub fn is_data_valid(&self, data: &DataUnit) 
         -> Result<(), Box<dyn Error>>
     {
-        let hashes = data.hashes()?;
-        if hashes.len() != self.data_hashes.len() {
+        let chunks = data.chunks()?;
+        let data_length = chunks.iter().map(|(chunk, _)| chunk.len() as u64).sum::<u64>();
+        if chunks.len() != self.chunk_count as usize || data_length != self.data_length {
             return Err(ErrorMessage::new("Missmatched data length"));
         }
 
-        for i in 0..hashes.len() 
-        {
-            if hashes[i] != self.data_hashes[i] {
-                return Err(ErrorMessage::new("Incorrect data"));
-            }
+        let hashes = chunks.iter().map(|(_, hash)| *hash).collect::<Vec<_>>();
+        if !self.is_chunk_list_valid(&hashes) {
+            return Err(ErrorMessage::new("Incorrect data"));
+        }
+
+        let reused_length = self.reused_chunks.iter()
+            .flat_map(|reused| chunks.get(reused.index as usize..reused.end() as usize))
+            .flatten()
+            .map(|(chunk, _)| chunk.len() as u64)
+            .sum::<u64>();
+        if data_length - reused_length != self.new_data_length {
+            return Err(ErrorMessage::new("Missmatched new data length"));
         }
 
         Ok(())
     }
 
+    pub fn is_chunk_list_valid(&self, hashes: &[Hash]) -> bool
+    {
+        hashes.len() == self.chunk_count as usize && 
+            merkle_root_for_chunks(hashes) == self.chunk_root
+    }
+
+    /// Check a single chunk is the one at `index` in this page.
+    pub fn is_chunk_valid(&self, index: u32, chunk: &[u8], proof: &MerkleProof) -> bool
+    {
+        is_in_tree(&content_hash(chunk), index, self.chunk_count, &self.chunk_root, proof)
+    }
+
 }
 
 impl TransactionContent for Page
@@ -95,17 +290,12 @@ impl TransactionContent for Page
     fn validate(&self, inputs: &Vec<Input>) 
         -> Result<TransactionValidationResult, Box<dyn Error>>
     {
-        if !inputs.iter().any(|x| x.get_address() == self.site) {
-            return Ok(TransactionValidationResult::Negative);
-        }
-
         let total_input = inputs.iter().fold(0.0, |acc, x| acc + x.amount);
         if total_input != self.cost() + self.fee {
             return Ok(TransactionValidationResult::Negative);
         }
 
-        let expected_hash_count = self.cost().ceil() as usize;
-        if self.data_hashes.len() != expected_hash_count {
+        if !self.is_layout_valid() {
             return Ok(TransactionValidationResult::Negative);
         }
 
@@ -145,5 +335,10 @@ impl TransactionContent for Page
         self.id
     }
 
+    fn get_site(&self) -> Option<Hash>
+    {
+        Some( self.site )
+    }
+
 }
 
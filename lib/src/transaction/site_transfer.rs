@@ -0,0 +1,105 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::{Input, TransactionContent, TransactionValidationResult};
+use crate::wallet::WalletStatus;
+use crate::error::ErrorMessage;
+use crate::hash::Hash;
+
+use serde::{Serialize, Deserialize};
+use std::error::Error;
+
+/// Hand ownership of a site to a new address. The site keeps its address,
+/// but from then on only the new owner may update it.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct SiteTransfer
+{
+    pub id: u32,
+    pub site: Hash,
+    pub new_owner: Hash,
+    pub fee: f32,
+}
+
+impl SiteTransfer
+{
+
+    pub fn new(id: u32, site: Hash, new_owner: Hash, fee: f32) -> Self
+    {
+        Self
+        {
+            id,
+            site,
+            new_owner,
+            fee,
+        }
+    }
+
+}
+
+impl TransactionContent for SiteTransfer
+{
+
+    fn get_fee(&self) -> f32
+    {
+        self.fee
+    }
+
+    fn validate(&self, inputs: &Vec<Input>)
+        -> Result<TransactionValidationResult, Box<dyn Error>>
+    {
+        // NOTE: Ids are only tracked for inputs that spend something, so a 
+        //       fee is required to stop this being replayed.
+        if self.fee <= 0.0 {
+            return Ok(TransactionValidationResult::Negative);
+        }
+
+        let total_input = inputs.iter().fold(0.0, |acc, x| acc + x.amount);
+        if total_input != self.fee {
+            return Ok(TransactionValidationResult::Negative);
+        }
+
+        Ok(TransactionValidationResult::Ok)
+    }
+
+    fn update_wallet_status(&self, _address: &Hash, mut status: WalletStatus,
+                            from_amount: f32, is_block_winner: bool)
+        -> Result<WalletStatus, Box<dyn Error>>
+    {
+        if from_amount > 0.0
+        {
+            status.balance -= from_amount;
+            if self.id <= status.max_id 
+            {
+                return Err(ErrorMessage::new(
+                    &format!("Id is not incremental ({} -> {})",
+                        status.max_id, self.id)));
+            }
+            status.max_id = self.id;
+        }
+
+        if is_block_winner {
+            status.balance += self.fee;
+        }
+
+        Ok(status)
+    }
+
+    fn get_to_addresses(&self) -> Vec<Hash>
+    {
+        vec![self.site, self.new_owner]
+    }
+
+    fn get_id(&self) -> u32
+    {
+        self.id
+    }
+
+    fn get_site(&self) -> Option<Hash>
+    {
+        Some( self.site )
+    }
+
+}
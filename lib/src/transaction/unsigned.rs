@@ -0,0 +1,150 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::{Transaction, TransactionHeader, TransactionContent};
+use crate::wallet::Wallet;
+use crate::wallet::private_wallet::PrivateWallet;
+use crate::error::ErrorMessage;
+use crate::hash::{Hash, Signature};
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::fs::File;
+use std::error::Error;
+
+/// A transaction header along with any signatures collected so far. This
+/// can be written to a file, carried to another machine to be signed, and
+/// then turned into a full transaction once every input has signed it.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct UnsignedTransaction<C>
+    where C: TransactionContent
+{
+    pub header: TransactionHeader<C>,
+    pub signatures: HashMap<Hash, Signature>,
+}
+
+impl<C> UnsignedTransaction<C>
+    where C: TransactionContent + Serialize
+{
+
+    pub fn new(header: TransactionHeader<C>) -> Self
+    {
+        Self
+        {
+            header,
+            signatures: HashMap::new(),
+        }
+    }
+
+    pub fn hash(&self) -> Result<Hash, Box<dyn Error>>
+    {
+        self.header.hash()
+    }
+
+    pub fn sign(&mut self, wallet: &PrivateWallet) -> Result<(), Box<dyn Error>>
+    {
+        let address = wallet.get_address();
+        if !self.header.inputs.iter().any(|x| x.get_address() == address) {
+            return Err(ErrorMessage::new("Wallet is not an input of this transaction"));
+        }
+
+        let header_hash = self.header.hash()?;
+        let signature = Signature::from(&wallet.sign(header_hash.data())?);
+        self.signatures.insert(address, signature);
+        Ok(())
+    }
+
+    pub fn missing_signatures(&self) -> Vec<Hash>
+    {
+        self.header.inputs
+            .iter()
+            .map(|x| x.get_address())
+            .filter(|x| !self.signatures.contains_key(x))
+            .collect()
+    }
+
+    pub fn is_complete(&self) -> bool
+    {
+        self.missing_signatures().is_empty()
+    }
+
+    pub fn into_transaction(self) -> Result<Transaction<C>, Box<dyn Error>>
+    {
+        if !self.is_complete() {
+            return Err(ErrorMessage::new("Transaction is missing signatures"));
+        }
+
+        Ok(Transaction::new(self.header, self.signatures))
+    }
+
+}
+
+impl<C> UnsignedTransaction<C>
+    where C: TransactionContent + Serialize + DeserializeOwned
+{
+
+    pub fn write_to_file(&self, path: &PathBuf) -> Result<(), Box<dyn Error>>
+    {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    pub fn read_from_file(path: &PathBuf) -> Result<Self, Box<dyn Error>>
+    {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+    use super::super::TransactionValidationResult;
+    use super::super::builder::TransactionBuilder;
+    use super::super::transfer::{Transfer, TransferBuilder};
+
+    #[test]
+    fn test_unsigned_transaction()
+    {
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let other = PrivateWallet::open_temp(1).unwrap();
+
+        let mut unsigned =
+            TransactionBuilder::new(
+                TransferBuilder::new(1, 1.0)
+                    .add_output(other.get_address(), 4.0)
+                    .build())
+                .add_unsigned_input(&wallet.as_public(), 2.0).unwrap()
+                .add_unsigned_input(&other.as_public(), 3.0).unwrap()
+                .build_unsigned().unwrap();
+        assert_eq!(unsigned.missing_signatures().len(), 2);
+        assert_eq!(unsigned.clone().into_transaction().is_err(), true);
+
+        // Round trip through a file, as if carried to an offline machine
+        let path = std::env::temp_dir().join(format!("{}.tx", rand::random::<u32>()));
+        unsigned.write_to_file(&path).unwrap();
+        let mut loaded = UnsignedTransaction::<Transfer>::read_from_file(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(loaded, unsigned);
+
+        loaded.sign(&wallet).unwrap();
+        assert_eq!(loaded.missing_signatures(), vec![other.get_address()]);
+
+        loaded.sign(&other).unwrap();
+        let transaction = loaded.into_transaction().unwrap();
+        assert_eq!(transaction.validate_content().unwrap(), TransactionValidationResult::Ok);
+
+        // Signing with a wallet that isn't an input should fail
+        let stranger = PrivateWallet::open_temp(2).unwrap();
+        assert_eq!(unsigned.sign(&stranger).is_err(), true);
+    }
+
+}
@@ -0,0 +1,125 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use crate::config::{ADDRESS_PREFIX, ADDRESS_VERSION, ADDRESS_CHECKSUM_LEN, HASH_LEN};
+use crate::hash::Hash;
+use crate::error::ErrorMessage;
+use sha2::{Sha256, Digest};
+use std::error::Error;
+
+fn checksum(version: u8, address: &Hash) -> Vec<u8>
+{
+    let mut hasher = Sha256::default();
+    hasher.update(ADDRESS_PREFIX.as_bytes());
+    hasher.update(&[version]);
+    hasher.update(address);
+    let first = hasher.finalize();
+
+    let mut hasher = Sha256::default();
+    hasher.update(&first);
+    hasher.finalize()[0..ADDRESS_CHECKSUM_LEN].to_vec()
+}
+
+/// Encode an address as the network prefix followed by the base 62 of
+/// its version byte, hash and checksum.
+pub fn encode_address(address: &Hash) -> String
+{
+    let mut payload = Vec::with_capacity(1 + HASH_LEN + ADDRESS_CHECKSUM_LEN);
+    payload.push(ADDRESS_VERSION);
+    payload.extend_from_slice(address.data());
+    payload.extend(checksum(ADDRESS_VERSION, address));
+    format!("{}{}", ADDRESS_PREFIX, base_62::encode(&payload))
+}
+
+pub fn decode_address(address_str: &str) -> Result<Hash, Box<dyn Error>>
+{
+    if !address_str.starts_with(ADDRESS_PREFIX)
+    {
+        return Err(ErrorMessage::new(&format!(
+            "Address '{}' is not for this network, expected the '{}' prefix",
+            address_str, ADDRESS_PREFIX)));
+    }
+
+    let payload_or_error = base_62::decode(&address_str[ADDRESS_PREFIX.len()..]);
+    if payload_or_error.is_err() {
+        return Err(ErrorMessage::new(&format!("Address '{}' is not valid base 62", address_str)));
+    }
+
+    let payload = payload_or_error.unwrap();
+    if payload.len() != 1 + HASH_LEN + ADDRESS_CHECKSUM_LEN {
+        return Err(ErrorMessage::new(&format!("Address '{}' has the wrong length", address_str)));
+    }
+
+    let version = payload[0];
+    if version != ADDRESS_VERSION
+    {
+        return Err(ErrorMessage::new(&format!(
+            "Address '{}' has unsupported version {}", address_str, version)));
+    }
+
+    let address = Hash::from(&payload[1..1 + HASH_LEN]);
+    if payload[1 + HASH_LEN..] != checksum(version, &address)[..]
+    {
+        return Err(ErrorMessage::new(&format!(
+            "Address '{}' has an invalid checksum, check it for typos", address_str)));
+    }
+
+    Ok(address)
+}
+
+/// Serde helpers for storing an address in its encoded form, for use
+/// with `#[serde(with = "address::serde_address")]` in human edited files.
+pub mod serde_address
+{
+
+    use super::*;
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+    use serde::de;
+
+    pub fn serialize<S>(address: &Hash, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        encode_address(address).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, De>(deserializer: De) -> Result<Hash, De::Error>
+        where De: Deserializer<'de>
+    {
+        let address_str = String::deserialize(deserializer)?;
+        decode_address(&address_str).map_err(de::Error::custom)
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+    use crate::wallet::Wallet;
+    use crate::wallet::private_wallet::PrivateWallet;
+
+    #[test]
+    fn test_address()
+    {
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let address = wallet.get_address();
+        let address_str = encode_address(&address);
+        assert_eq!(address_str.starts_with(ADDRESS_PREFIX), true);
+        assert_eq!(decode_address(&address_str).unwrap(), address);
+
+        // Raw base 62 hashes and other networks are rejected
+        assert_eq!(decode_address(&address.to_string()).is_err(), true);
+        assert_eq!(decode_address(&format!("xx{}", &address_str[ADDRESS_PREFIX.len()..])).is_err(), true);
+
+        // A single character typo should be caught by the checksum
+        let mut typo = address_str.clone().into_bytes();
+        let last = typo.len() - 5;
+        typo[last] = if typo[last] == b'a' { b'b' } else { b'a' };
+        assert_eq!(decode_address(&String::from_utf8(typo).unwrap()).is_err(), true);
+    }
+
+}
@@ -0,0 +1,153 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::address::serde_address;
+use crate::hash::Hash;
+use crate::error::ErrorMessage;
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::fs::File;
+use std::error::Error;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AddressBookEntry
+{
+    #[serde(with = "serde_address")]
+    pub address: Hash,
+
+    /// Watched entries are included when summarising all balances
+    pub watch: bool,
+}
+
+/// Labelled addresses, stored as a json file so it can be edited by hand.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AddressBook
+{
+    entries: BTreeMap<String, AddressBookEntry>,
+
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl AddressBook
+{
+
+    pub fn open(path: &PathBuf) -> Result<Self, Box<dyn Error>>
+    {
+        if !path.exists()
+        {
+            return Ok(Self
+            {
+                entries: BTreeMap::new(),
+                path: path.clone(),
+            });
+        }
+
+        let file = File::open(path)?;
+        let mut address_book: Self = serde_json::from_reader(file)?;
+        address_book.path = path.clone();
+        Ok(address_book)
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>>
+    {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let file = File::create(&self.path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    pub fn add(&mut self, label: &str, address: Hash, watch: bool)
+        -> Result<(), Box<dyn Error>>
+    {
+        if label.is_empty() {
+            return Err(ErrorMessage::new("Label must not be empty"));
+        }
+
+        self.entries.insert(label.to_owned(), AddressBookEntry { address, watch });
+        Ok(())
+    }
+
+    pub fn remove(&mut self, label: &str) -> Option<AddressBookEntry>
+    {
+        self.entries.remove(label)
+    }
+
+    pub fn get(&self, label: &str) -> Option<&AddressBookEntry>
+    {
+        self.entries.get(label)
+    }
+
+    pub fn label_for(&self, address: &Hash) -> Option<&str>
+    {
+        self.entries
+            .iter()
+            .find(|(_, entry)| &entry.address == address)
+            .map(|(label, _)| label.as_str())
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &AddressBookEntry)>
+    {
+        self.entries.iter()
+    }
+
+    pub fn watched(&self) -> impl Iterator<Item = (&String, &AddressBookEntry)>
+    {
+        self.entries.iter().filter(|(_, entry)| entry.watch)
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+    use crate::wallet::Wallet;
+    use crate::wallet::private_wallet::PrivateWallet;
+    use crate::wallet::watch_wallet::WatchWallet;
+
+    #[test]
+    fn test_address_book()
+    {
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let other = PrivateWallet::open_temp(1).unwrap();
+        let path = std::env::temp_dir()
+            .join(format!("{}", rand::random::<u32>()))
+            .join("address_book.json");
+
+        let mut address_book = AddressBook::open(&path).unwrap();
+        address_book.add("cold", wallet.get_address(), true).unwrap();
+        address_book.add("friend", other.get_address(), false).unwrap();
+        assert_eq!(address_book.add("", other.get_address(), false).is_err(), true);
+        address_book.save().unwrap();
+
+        let loaded = AddressBook::open(&path).unwrap();
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+        assert_eq!(loaded.get("cold").unwrap().address, wallet.get_address());
+        assert_eq!(loaded.label_for(&other.get_address()), Some("friend"));
+        assert_eq!(loaded.watched().map(|(label, _)| label.as_str()).collect::<Vec<_>>(), vec!["cold"]);
+
+        // Watch only wallets keep the address, but never the private key
+        let watch_path = std::env::temp_dir().join(format!("{}.watch", rand::random::<u32>()));
+        WatchWallet::from_public(wallet.as_public()).write_to_file(&watch_path).unwrap();
+        let watch = WatchWallet::read_from_file(&watch_path).unwrap();
+        assert_eq!(watch.get_address(), wallet.get_address());
+        assert_eq!(watch.get_public_wallet().unwrap().get_public_key(), wallet.get_public_key());
+
+        // The public key has to be for the address being watched
+        let mut tampered = serde_json::to_value(&watch).unwrap();
+        tampered["address"] = serde_json::to_value(WatchWallet::from_address(other.get_address())).unwrap()["address"].clone();
+        serde_json::to_writer(File::create(&watch_path).unwrap(), &tampered).unwrap();
+        assert_eq!(WatchWallet::read_from_file(&watch_path).is_err(), true);
+        let _ = std::fs::remove_file(&watch_path);
+    }
+
+}
@@ -0,0 +1,120 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::Wallet;
+use super::private_wallet::PrivateWallet;
+use super::public_wallet::{PublicWallet, WalletValidationResult};
+use crate::hash::{Hash, Signature};
+use crate::error::ErrorMessage;
+use sha2::{Sha256, Digest};
+use serde::{Serialize, Deserialize};
+use std::error::Error;
+
+// Transaction hashes are taken over a bincode header, which would read these
+// bytes as an id followed by a vector length in the billions of billions. So
+// no real transaction can share a digest with a signed message.
+const MESSAGE_DOMAIN: &[u8] = b"Hyperchain Signed Message:\n";
+
+pub fn message_digest(message: &str) -> Hash
+{
+    let mut hasher = Sha256::default();
+    hasher.update(MESSAGE_DOMAIN);
+    hasher.update(&(message.len() as u64).to_le_bytes());
+    hasher.update(message.as_bytes());
+    Hash::from(&hasher.finalize())
+}
+
+/// A signature over a message, along with the public key needed to check
+/// it against an address.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MessageSignature
+{
+    pub public_wallet: PublicWallet,
+    pub signature: Signature,
+}
+
+impl MessageSignature
+{
+
+    pub fn encode(&self) -> Result<String, Box<dyn Error>>
+    {
+        Ok(base_62::encode(&bincode::serialize(self)?))
+    }
+
+    pub fn decode(signature_str: &str) -> Result<Self, Box<dyn Error>>
+    {
+        let bytes_or_error = base_62::decode(signature_str.trim());
+        if bytes_or_error.is_err() {
+            return Err(ErrorMessage::new("Signature is not valid base 62"));
+        }
+
+        Ok(bincode::deserialize(&bytes_or_error.unwrap())?)
+    }
+
+}
+
+pub fn sign_message(wallet: &PrivateWallet, message: &str)
+    -> Result<MessageSignature, Box<dyn Error>>
+{
+    let digest = message_digest(message);
+    let signature = wallet.sign(digest.data())?;
+    Ok(MessageSignature
+    {
+        public_wallet: wallet.as_public(),
+        signature: Signature::from(&signature),
+    })
+}
+
+pub fn verify_message(address: &Hash, message: &str, signature: &MessageSignature)
+    -> Result<WalletValidationResult, Box<dyn Error>>
+{
+    if signature.public_wallet.get_address() != *address {
+        return Err(ErrorMessage::new("Signature was made by a different address"));
+    }
+
+    if signature.public_wallet.get_e().is_none() {
+        return Err(ErrorMessage::new("Signature is missing the public exponent"));
+    }
+
+    let digest = message_digest(message);
+    signature.public_wallet.verify(digest.data(), signature.signature.data())
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+    use crate::transaction::builder::TransactionBuilder;
+    use crate::transaction::transfer::TransferBuilder;
+
+    #[test]
+    fn test_message()
+    {
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let other = PrivateWallet::open_temp(1).unwrap();
+        let address = wallet.get_address();
+
+        let signature = sign_message(&wallet, "I own this address").unwrap();
+        let decoded = MessageSignature::decode(&signature.encode().unwrap()).unwrap();
+        assert_eq!(verify_message(&address, "I own this address", &decoded).unwrap(), WalletValidationResult::Ok);
+        assert_eq!(verify_message(&address, "I own that address", &decoded).unwrap(), WalletValidationResult::Signature);
+        assert_eq!(verify_message(&other.get_address(), "I own this address", &decoded).is_err(), true);
+
+        // A message signature over the same bytes is never a transaction signature
+        let transfer = TransactionBuilder::new(
+                TransferBuilder::new(1, 1.0)
+                    .add_output(other.get_address(), 1.0)
+                    .build())
+            .add_input(&wallet, 2.0)
+            .build().unwrap();
+        let header_hash = transfer.hash().unwrap();
+        assert_ne!(message_digest(&format!("{}", header_hash)), header_hash);
+        assert_ne!(sign_message(&wallet, &format!("{}", header_hash)).unwrap().signature,
+                   transfer.signatures[&address]);
+    }
+
+}
@@ -6,6 +6,10 @@
 
 pub mod private_wallet;
 pub mod public_wallet;
+pub mod address;
+pub mod address_book;
+pub mod watch_wallet;
+pub mod message;
 use crate::chain::BlockChain;
 use crate::hash::{Hash, Signature};
 use sha2::{Sha256, Digest};
@@ -44,6 +48,11 @@ pub trait Wallet
         Hash::from(&hasher.finalize())
     }
 
+    fn get_address_string(&self) -> String
+    {
+        address::encode_address(&self.get_address())
+    }
+
     fn get_status(&self, chain: &mut BlockChain) -> WalletStatus
         where Self: Sized
     {
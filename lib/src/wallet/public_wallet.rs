@@ -8,6 +8,8 @@ use super::Wallet;
 use crate::hash::Signature;
 use serde::{Serialize, Deserialize};
 use rsa::{RSAPublicKey, PublicKey, PaddingScheme, BigUint};
+use std::path::PathBuf;
+use std::fs::File;
 use std::error::Error;
 
 big_array! { BigArray; }
@@ -71,6 +73,24 @@ impl PublicWallet
         }
     }
 
+    pub fn get_e(&self) -> Option<[u8; 3]>
+    {
+        self.e
+    }
+
+    pub fn write_to_file(&self, path: &PathBuf) -> Result<(), Box<dyn Error>>
+    {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    pub fn read_from_file(path: &PathBuf) -> Result<Self, Box<dyn Error>>
+    {
+        let file = File::open(path)?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
     pub fn verify(&self, hash: &[u8], signature: &[u8]) -> Result<WalletValidationResult, Box<dyn Error>>
     {
         assert_eq!(self.e.is_none(), false);
@@ -0,0 +1,81 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::Wallet;
+use super::address::serde_address;
+use super::public_wallet::PublicWallet;
+use crate::hash::Hash;
+use crate::error::ErrorMessage;
+use serde::{Serialize, Deserialize};
+use std::path::PathBuf;
+use std::fs::File;
+use std::error::Error;
+
+/// A wallet that can only be watched, holding an address and optionally
+/// its public key, but never the private key.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WatchWallet
+{
+    #[serde(with = "serde_address")]
+    address: Hash,
+    public_wallet: Option<PublicWallet>,
+}
+
+impl WatchWallet
+{
+
+    pub fn from_address(address: Hash) -> Self
+    {
+        Self
+        {
+            address,
+            public_wallet: None,
+        }
+    }
+
+    pub fn from_public(public_wallet: PublicWallet) -> Self
+    {
+        Self
+        {
+            address: public_wallet.get_address(),
+            public_wallet: Some( public_wallet ),
+        }
+    }
+
+    pub fn get_address(&self) -> Hash
+    {
+        self.address
+    }
+
+    pub fn get_public_wallet(&self) -> Option<&PublicWallet>
+    {
+        self.public_wallet.as_ref()
+    }
+
+    pub fn write_to_file(&self, path: &PathBuf) -> Result<(), Box<dyn Error>>
+    {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    /// Read a wallet back, checking its public key is for the address it
+    /// says it's watching.
+    pub fn read_from_file(path: &PathBuf) -> Result<Self, Box<dyn Error>>
+    {
+        let file = File::open(path)?;
+        let wallet: Self = serde_json::from_reader(file)?;
+        if let Some(public_wallet) = &wallet.public_wallet
+        {
+            if public_wallet.get_address() != wallet.address {
+                return Err(ErrorMessage::new("Watch wallet's public key doesn't match its address"));
+            }
+        }
+
+        Ok(wallet)
+    }
+
+}
@@ -9,17 +9,88 @@ use crate::node::packet_handler::NodePacketHandler;
 
 use libhyperchain::service::command::Response;
 use libhyperchain::hash::Hash;
+use std::time::{Duration, Instant};
+
+const WATCH_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub fn balance(connection: &mut NetworkConnection<NodePacketHandler>,
                address_vec: Vec<u8>) -> Response
 {
-    let mut node = connection.handler().node();
-    let chain = node.chain();
-
     // TODO: Varify this is a valid hash
     let address = Hash::from(&address_vec);
 
+    let handler = connection.handler().clone();
+    if handler.node().light_client().is_some() {
+        return light_balance(connection, &address);
+    }
+
+    let mut node = handler.node();
+    let chain = node.chain();
+
     let status = chain.get_wallet_status(&address);
     Response::WalletStatus(status)
 }
 
+/// Fetch the blocks using this address if we haven't already, and work out
+/// its balance from them.
+fn light_balance(connection: &mut NetworkConnection<NodePacketHandler>,
+                 address: &Hash) -> Response
+{
+    let handler = connection.handler().clone();
+    let deadline = Instant::now() + WATCH_TIMEOUT;
+    loop
+    {
+        let updated =
+        {
+            let mut node = handler.node();
+            if let Err(err) = node.watch_address(connection.manager(), address)
+            {
+                warn!("Unable to watch address: {}", err);
+                return Response::Failed;
+            }
+
+            let light_client = node.light_client().unwrap();
+            match light_client.wallet_status(address)
+            {
+                Ok(Some(status)) => return Response::WalletStatus(status),
+                Ok(None) => light_client.listen_for_address(address),
+                Err(_) => return Response::Failed,
+            }
+        };
+
+        // NOTE: New headers may have arrived by the time the blocks have,
+        //       in which case they're fetched again
+        let time_left = deadline.saturating_duration_since(Instant::now());
+        if updated.recv_timeout(time_left).is_err()
+        {
+            warn!("Timed out waiting for blocks for {}", address);
+            return Response::TimedOut;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+    use crate::node::tests::create_node;
+    use libhyperchain::wallet::Wallet;
+    use libhyperchain::wallet::private_wallet::PrivateWallet;
+
+    #[test]
+    fn test_light_balance_timeout()
+    {
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let mut connection = create_node(8074);
+        {
+            let path = std::env::temp_dir().join(format!("{}8074-headers", libhyperchain::block::current_timestamp()));
+            connection.handler().node().enable_light_client(&path).unwrap();
+        }
+
+        // No node to ask, so it gives up
+        let address = wallet.get_address().data().to_vec();
+        assert_eq!(balance(&mut connection, address), Response::TimedOut);
+    }
+
+}
@@ -0,0 +1,25 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use crate::network::NetworkConnection;
+use crate::node::packet_handler::NodePacketHandler;
+
+use libhyperchain::service::command::Response;
+
+pub fn bans(connection: &mut NetworkConnection<NodePacketHandler>)
+    -> Response
+{
+    Response::Bans(connection.manager().bans())
+}
+
+pub fn unban(connection: &mut NetworkConnection<NodePacketHandler>,
+             address: Option<String>)
+    -> Response
+{
+    let manager = connection.manager();
+    manager.unban(address.as_deref());
+    Response::Bans(manager.bans())
+}
@@ -18,8 +18,25 @@ pub fn build<W>(chain: &mut BlockChain, wallet: &W) -> Result<Block, Box<dyn Err
     for transfer in chain.get_next_transfers_in_queue(10) {
         block_builder = block_builder.add_transfer(transfer.clone());
     }
-    for page in chain.get_next_pages_in_queue(10) {
-        block_builder = block_builder.add_page(page.clone());
+
+    // NOTE: Pages are checked against the site owner at the start of the 
+    //       block, so leave any for sites changing hands until the next one.
+    chain.remove_pages_from_old_site_owners();
+    let mut sites_transferred = Vec::new();
+    for site_transfer in chain.get_next_site_transfers_in_queue(10)
+    {
+        sites_transferred.push(site_transfer.header.content.site);
+        block_builder = block_builder.add_site_transfer(site_transfer.clone());
+    }
+    let pages = chain.get_next_pages_in_queue(10).cloned().collect::<Vec<_>>();
+    for page in pages
+    {
+        if !sites_transferred.contains(&page.header.content.site) && chain.is_page_from_site_owner(&page) {
+            block_builder = block_builder.add_page(page);
+        }
+    }
+    for name in chain.get_next_names_in_queue(10) {
+        block_builder = block_builder.add_name(name.clone());
     }
 
     Ok(block_builder.build(chain)?)
@@ -19,7 +19,10 @@ mod network;
 mod block_builder;
 mod miner;
 mod send;
+mod offline_transfer;
 mod update_page;
+mod site_transfer;
+mod name;
 mod balance;
 mod transaction_info;
 mod transaction_history;
@@ -27,23 +30,38 @@ mod page;
 mod blocks;
 mod statistics;
 mod report;
+mod replication;
+mod snapshot;
+mod bans;
 
 use miner::start_miner_thread;
+use replication::start_replication_thread;
 use send::send;
-use update_page::update_page;
+use offline_transfer::{create_transfer, submit_transfer};
+use update_page::{update_page, publish_data};
+use site_transfer::{transfer_site, sites_owned};
+use name::{register_name, resolve_name};
 use balance::balance;
 use transaction_history::transaction_history;
-use transaction_info::transaction_info;
+use transaction_info::{transaction_info, transaction_proof};
 use page::page_updates;
 use page::page_data;
+use page::site_file;
 use blocks::{blocks, top_block};
 use statistics::statistics;
+use snapshot::{export_snapshot, verify_snapshot};
+use bans::{bans, unban};
 use crate::network::NetworkConnection;
 use crate::node::Node;
 use crate::node::packet_handler::NodePacketHandler;
+use crate::node::replication::ReplicationConfig;
 
 use libhyperchain::service::server;
 use libhyperchain::service::command::{Command, Response};
+use libhyperchain::chain::snapshot::ChainSnapshot;
+use libhyperchain::config::HASH_LEN;
+use libhyperchain::error::ErrorMessage;
+use libhyperchain::hash::Hash;
 use clap::{App, Arg};
 use std::error::Error;
 use std::path::PathBuf;
@@ -74,16 +92,88 @@ fn main() -> Result<(), Box<dyn Error>>
             .takes_value(false)
             .required(false)
             .help("Disable mining"))
+        .arg(Arg::with_name("replication")
+            .short("r")
+            .long("replication")
+            .takes_value(true)
+            .required(false)
+            .help("Number of nodes each chunk should be stored on"))
+        .arg(Arg::with_name("disk-quota")
+            .short("q")
+            .long("disk-quota")
+            .takes_value(true)
+            .required(false)
+            .help("Megabytes of page data to store at most"))
+        .arg(Arg::with_name("light")
+            .long("light")
+            .takes_value(false)
+            .required(false)
+            .help("Only sync block headers, fetching blocks for watched addresses when needed"))
+        .arg(Arg::with_name("prune")
+            .long("prune")
+            .takes_value(true)
+            .required(false)
+            .help("Only keep this many of the most recent full blocks"))
+        .arg(Arg::with_name("snapshot")
+            .long("snapshot")
+            .takes_value(true)
+            .required(false)
+            .requires("snapshot-commitment")
+            .help("Start from a chain snapshot instead of syncing every block"))
+        .arg(Arg::with_name("snapshot-commitment")
+            .long("snapshot-commitment")
+            .takes_value(true)
+            .required(false)
+            .help("Commitment of the snapshot, from a node you trust"))
+        .arg(Arg::with_name("network")
+            .long("network")
+            .takes_value(true)
+            .required(false)
+            .help("Name of the network to join, nodes on other networks are rejected"))
         .get_matches();
 
     // Crate logger and read port from command line
     let port = matches.value_of("port").unwrap_or("8001").parse::<u16>().unwrap();
     let disable_local_server = matches.is_present("local-server");
-    let disable_mining = matches.is_present("mining");
+    let is_light = matches.is_present("light");
+    let disable_mining = matches.is_present("mining") || is_light;
+
+    let mut replication_config = ReplicationConfig::default();
+    if let Some(target) = matches.value_of("replication") {
+        replication_config.target = target.parse::<usize>()?;
+    }
+    if let Some(disk_quota) = matches.value_of("disk-quota") {
+        replication_config.disk_quota = disk_quota.parse::<u64>()? * 1000 * 1000;
+    }
 
     // Create and open node
     let data_directory = PathBuf::from("hyperchain");
     let node = Node::new(port, &data_directory)?;
+    node.lock().unwrap().replication().config = replication_config;
+    if is_light {
+        node.lock().unwrap().enable_light_client(&data_directory.join("headers"))?;
+    }
+    if let Some(network_id) = matches.value_of("network") {
+        node.lock().unwrap().set_network_id(network_id);
+    }
+    if let Some(prune_depth) = matches.value_of("prune") {
+        node.lock().unwrap().chain().set_prune_depth(Some(prune_depth.parse::<u64>()?));
+    }
+    if let Some(path) = matches.value_of("snapshot")
+    {
+        let commitment = base_62::decode(matches.value_of("snapshot-commitment").unwrap())
+            .ok()
+            .filter(|commitment| commitment.len() == HASH_LEN)
+            .ok_or_else(|| ErrorMessage::new("Invalid snapshot commitment"))?;
+
+        let snapshot = ChainSnapshot::load(&PathBuf::from(path))?;
+        let mut node = node.lock().unwrap();
+        if node.chain().top().is_none() {
+            node.chain().import_snapshot(&snapshot, &Hash::from(&commitment))?;
+        } else {
+            warn!("Chain already has blocks, not importing snapshot");
+        }
+    }
     let packet_handler = NodePacketHandler::new(node);
 
     let mut miner_thread = None;
@@ -91,6 +181,7 @@ fn main() -> Result<(), Box<dyn Error>>
         // Register a common node to connect to
         let mut network_connection = NetworkConnection::open(port, &data_directory, packet_handler)?;
         network_connection.manager().register_node("192.168.0.53:8001");
+        start_replication_thread(network_connection.clone());
 
         // Start miner thread
         if !disable_mining
@@ -118,20 +209,47 @@ fn main() -> Result<(), Box<dyn Error>>
                 Command::Send(inputs, outputs, fee) =>
                     send(&mut connection, inputs, outputs, fee),
 
-                Command::UpdatePage(from, name, data) =>
-                    update_page(&mut connection, from, name, data),
+                Command::CreateTransfer(inputs, outputs, fee) =>
+                    create_transfer(&mut connection, inputs, outputs, fee),
+
+                Command::SubmitTransfer(transfer) =>
+                    submit_transfer(&mut connection, transfer),
+
+                Command::UpdatePage(from, site, name, data) =>
+                    update_page(&mut connection, from, site, name, data),
+
+                Command::PublishData(from, site, data_unit) =>
+                    publish_data(&mut connection, from, site, data_unit),
+
+                Command::TransferSite(from, site, new_owner, fee) =>
+                    transfer_site(&mut connection, from, site, new_owner, fee),
+
+                Command::SitesOwned(address) =>
+                    sites_owned(&mut connection, address),
+
+                Command::RegisterName(from, name, site, owner, fee) =>
+                    register_name(&mut connection, from, name, site, owner, fee),
+
+                Command::ResolveName(name) =>
+                    resolve_name(&mut connection, name),
 
                 Command::TransactionInfo(id) =>
                     transaction_info(&mut connection, id),
                 
                 Command::TransactionHistory(address) =>
                     transaction_history(&mut connection, address),
+
+                Command::TransactionProof(id) =>
+                    transaction_proof(&mut connection, id),
                 
                 Command::PageUpdates(address) =>
                     page_updates(&mut connection, address),
 
                 Command::PageData(transaction_id) =>
                     page_data(&mut connection, transaction_id),
+
+                Command::SiteFile(site, path) =>
+                    site_file(&mut connection, site, path),
                 
                 Command::Blocks(from, to) =>
                     blocks(&mut connection, from, to),
@@ -142,6 +260,18 @@ fn main() -> Result<(), Box<dyn Error>>
                 Command::Statistics =>
                     statistics(&mut connection),
 
+                Command::ExportSnapshot(path) =>
+                    export_snapshot(&mut connection, path),
+
+                Command::VerifySnapshot(path) =>
+                    verify_snapshot(&mut connection, path),
+
+                Command::Bans =>
+                    bans(&mut connection),
+
+                Command::Unban(address) =>
+                    unban(&mut connection, address),
+
             }
         })?;
     }
@@ -11,23 +11,112 @@ use crate::block_builder;
 
 use libhyperchain::block::Block;
 use libhyperchain::block::validate::BlockValidationResult;
+use libhyperchain::block::storage_proof::{ChunkPosition, StorageSegment};
 use libhyperchain::block;
 use libhyperchain::chain::BlockChainAddResult;
 use libhyperchain::wallet::private_wallet::PrivateWallet;
 use libhyperchain::block::target::difficulty;
+use libhyperchain::data_store::DataStore;
+use libhyperchain::merkle_tree::{calculate_merkle_proof, MerkleProof, MerkleVersion};
+use libhyperchain::hash::Hash;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::thread::JoinHandle;
 use std::error::Error;
 
+/// Most chunks kept in memory while mining, so at most a few MB
+const MAX_CACHED_CHUNKS: usize = 16;
+
+struct CachedChunk
+{
+    data: Vec<u8>,
+
+    /// When the chunk was last read, counted in reads
+    last_used: u64,
+}
+
+/// Chunks read while mining a block, as each nonce picks its own to prove
+/// storage of. Where each picked chunk is kept for the whole block, but only
+/// the most recently used chunks' data is.
+struct StoredChunks
+{
+    data_store: DataStore,
+    positions: HashMap<(Hash, u32), Option<(Hash, MerkleProof)>>,
+    chunks: HashMap<Hash, CachedChunk>,
+    reads: u64,
+}
+
+impl StoredChunks
+{
+
+    fn new(data_store: DataStore) -> Self
+    {
+        Self
+        {
+            data_store,
+            positions: HashMap::new(),
+            chunks: HashMap::new(),
+            reads: 0,
+        }
+    }
+
+    fn data(&mut self, hash: &Hash) -> Option<&Vec<u8>>
+    {
+        self.reads += 1;
+        if !self.chunks.contains_key(hash)
+        {
+            let data = self.data_store.get(hash).ok()?;
+            if self.chunks.len() >= MAX_CACHED_CHUNKS
+            {
+                let least_used_or_none = self.chunks.iter()
+                    .min_by_key(|(_, cached)| cached.last_used)
+                    .map(|(hash, _)| *hash);
+
+                if let Some(least_used) = least_used_or_none {
+                    self.chunks.remove(&least_used);
+                }
+            }
+
+            self.chunks.insert(*hash, CachedChunk { data, last_used: 0 });
+        }
+
+        let cached = self.chunks.get_mut(hash).unwrap();
+        cached.last_used = self.reads;
+        Some(&cached.data)
+    }
+
+    fn get(&mut self, position: &ChunkPosition) -> Option<StorageSegment>
+    {
+        let data_store = &self.data_store;
+        let (hash, proof) = self.positions
+            .entry((position.chunk_root, position.index))
+            .or_insert_with(||
+            {
+                let hashes = data_store.chunk_list(&position.chunk_root).ok()?;
+                let hash = *hashes.get(position.index as usize).filter(|hash| data_store.has_chunk(hash))?;
+                let proof = calculate_merkle_proof(&hashes, position.index as usize, MerkleVersion::V2)?;
+                Some((hash, proof))
+            })
+            .clone()?;
+
+        StorageSegment::new(position, self.data(&hash)?, proof)
+    }
+
+}
+
 pub fn mine_block_unless_found(connection: &NetworkConnection<NodePacketHandler>, 
-                               mut block: Block) 
+                               mut block: Block,
+                               page_chunks: &[(Hash, u32)],
+                               data_store: DataStore) 
     -> Result<Block, Box<dyn Error>>
 {
     info!("Started mining block {} with difficulty {}", 
           block.header.block_id, 
           difficulty(&block.header.target));
 
-    while block.validate_pow()? != BlockValidationResult::Ok
+    // Blocks proving we store chunks from the chain need less work
+    let mut stored_chunks = StoredChunks::new(data_store);
+    while block.validate_pow_with_storage_proof(page_chunks, |position| stored_chunks.get(position))? != BlockValidationResult::Ok
     { 
         block.header.pow += 1;
 
@@ -42,10 +131,16 @@ pub fn mine_block_unless_found(connection: &NetworkConnection<NodePacketHandler>
             }
 
             let mut node = connection.handler().node();
+            if node.sync_progress().is_some()
+            {
+                info!("Syncing a longer chain, stopped mining block {}", block.header.block_id);
+                break;
+            }
+
             let chain = node.chain();
             if chain.block(block.header.block_id).is_some() 
             {
-                info!("Block {} already found, stoped mining", block.header.block_id);
+                info!("Block {} already found, stopped mining", block.header.block_id);
                 break;
             }
         }
@@ -58,18 +153,33 @@ fn mine_next_block(connection: &mut NetworkConnection<NodePacketHandler>,
                    wallet: &PrivateWallet) -> Result<(), Box<dyn Error>>
 {
     let mut block;
+    let page_chunks;
+    let data_store;
     {
-        // Create the next block
+        // Don't build on a chain we're about to replace
         let mut node = connection.handler().node();
+        if node.sync_progress().is_some()
+        {
+            drop(node);
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            return Ok(());
+        }
+
+        // Create the next block
         let chain = &mut node.chain();
         block = block_builder::build(chain, wallet)?;
+        page_chunks = chain.get_page_chunks();
+        data_store = node.data_store().clone();
     }
 
     // Do the mining work
-    block = mine_block_unless_found(connection, block)?;
-    if block.validate_pow()? != BlockValidationResult::Ok {
+    block = mine_block_unless_found(connection, block, &page_chunks, data_store)?;
+    if block.header.validate_pow_with_discount(block.header.storage_proof.is_some())? != BlockValidationResult::Ok {
         return Ok(());
     }
+    if block.header.storage_proof.is_some() {
+        info!("Proved storage for block {}", block.header.block_id);
+    }
 
     // Add it to the chain if it's still the top
     let handler = connection.handler().clone();
@@ -111,3 +221,48 @@ pub fn start_miner_thread(mut connection: NetworkConnection<NodePacketHandler>)
     })
 }
 
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+    use libhyperchain::data_store::data_unit::DataUnit;
+    use libhyperchain::data_store::page::CreatePageData;
+    use libhyperchain::transaction::page::Page;
+
+    #[test]
+    fn test_stored_chunks()
+    {
+        let path = std::env::temp_dir().join(format!("stored_chunks_{}", rand::random::<u32>()));
+        let data_store = DataStore::open(&path).unwrap();
+        let page_chunks = (0..2 * MAX_CACHED_CHUNKS as u32)
+            .map(|i| DataUnit::CreatePage(CreatePageData::new(format!("{}.html", i), i.to_le_bytes().repeat(10_000))))
+            .map(|data_unit|
+            {
+                data_store.store_data_unit(&data_unit).unwrap();
+                let page = Page::new_from_data(1, Hash::empty(), &data_unit, |_| None, 0.0).unwrap();
+                (page.chunk_root, page.chunk_count)
+            })
+            .collect::<Vec<_>>();
+
+        // Only the most recently used chunks are kept in memory
+        let mut stored_chunks = StoredChunks::new(data_store.clone());
+        for (chunk_root, chunk_count) in &page_chunks
+        {
+            let hashes = data_store.chunk_list(chunk_root).unwrap();
+            for index in 0..*chunk_count
+            {
+                let position = ChunkPosition { chunk_root: *chunk_root, chunk_count: *chunk_count, index, segment_seed: 0 };
+                assert_eq!(stored_chunks.get(&position).unwrap().chunk, hashes[index as usize]);
+            }
+        }
+        assert_eq!(stored_chunks.chunks.len(), MAX_CACHED_CHUNKS);
+
+        // Chunks we don't have can't be proven
+        let missing = ChunkPosition { chunk_root: Hash::empty(), chunk_count: 1, index: 0, segment_seed: 0 };
+        assert_eq!(stored_chunks.get(&missing), None);
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+}
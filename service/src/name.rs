@@ -0,0 +1,72 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use crate::network::NetworkConnection;
+use crate::network::packet::Packet;
+use crate::node::packet_handler::NodePacketHandler;
+
+use libhyperchain::service::command::Response;
+use libhyperchain::wallet::private_wallet::PrivateWallet;
+use libhyperchain::config::HASH_LEN;
+use libhyperchain::hash::Hash;
+
+pub fn register_name(connection: &mut NetworkConnection<NodePacketHandler>,
+                     from: Vec<u8>, name: String, site: Vec<u8>, owner: Vec<u8>, 
+                     fee: f32)
+    -> Response
+{
+    let registration;
+    let registration_id;
+
+    {
+        let from_wallet_or_error = PrivateWallet::deserialize(from);
+        if from_wallet_or_error.is_err() {
+            return Response::Failed;
+        }
+
+        if site.len() != HASH_LEN || owner.len() != HASH_LEN {
+            return Response::Failed;
+        }
+
+        let from_wallet = from_wallet_or_error.unwrap();
+        let site = Hash::from(&site);
+        let owner = Hash::from(&owner);
+
+        let mut node = connection.handler().node();
+        let chain = &mut node.chain();
+        let registration_or_error = chain.new_name_registration(&from_wallet, &name, &site, &owner, fee);
+        if registration_or_error.is_err()
+        {
+            warn!("Error in register name: {}", registration_or_error.unwrap_err());
+            return Response::Failed;
+        }
+
+        registration = registration_or_error.unwrap();
+        registration_id = registration.hash().unwrap();
+        let result = chain.push_name_queue(registration.clone());
+        if result.is_err()
+        {
+            warn!("Error in register name: {}", result.unwrap_err());
+            return Response::Failed;
+        }
+    }
+
+    connection.manager().send(Packet::NameRegistration(registration)).unwrap();
+    Response::Sent(registration_id.data().to_vec())
+}
+
+pub fn resolve_name(connection: &mut NetworkConnection<NodePacketHandler>,
+                    name: String) -> Response
+{
+    let mut node = connection.handler().node();
+    let chain = node.chain();
+
+    match chain.get_name(&name)
+    {
+        Some(record) => Response::Name(record),
+        None => Response::Failed,
+    }
+}
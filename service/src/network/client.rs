@@ -7,7 +7,9 @@
 use super::packet::{Packet, PacketHandler};
 use super::packet::{Message, MessageSender, MessageReceiver};
 use super::client_manager::ClientManager;
+use super::peer_score::Misbehaviour;
 
+use libhyperchain::error::ErrorMessage;
 use tcp_channel::LittleEndian;
 use tcp_channel::{SenderBuilder, ChannelSend};
 use tcp_channel::{ReceiverBuilder, ChannelRecv};
@@ -24,27 +26,49 @@ fn handle_command<H>(packet: Packet, command_handler: &H,
     let _ = command_handler.handle(address, packet, manager);
 }
 
-fn request_client_address(
+/// Swap handshakes with a newly connected node, returning its address if
+/// we can talk to it.
+fn exchange_handshakes<H>(
         mut sender: MessageSender, receiver: &mut MessageReceiver,
-        ip: &str, manager: &mut ClientManager)
+        stream: TcpStream, ip: &str, packet_handler: &H, manager: &mut ClientManager)
     -> Result<String, Box<dyn Error>>
+    where H: PacketHandler
 {
-    sender.send(&Message::OnConnected(manager.port()))?;
+    let handshake = packet_handler.handshake();
+    sender.send(&Message::Handshake(handshake.clone()))?;
     sender.flush()?;
     
     match receiver.recv()
     {
-        Ok(Message::OnConnected(port)) =>
+        Ok(Message::Handshake(theirs)) =>
         {
-            let address = format!("{}:{}", ip, port);
+            let address = format!("{}:{}", ip, theirs.port);
+            let reason_or_none =
+                if manager.is_banned(&address) {
+                    Some("Banned".to_owned())
+                } else {
+                    handshake.incompatibility(&theirs)
+                };
+
+            if let Some(reason) = reason_or_none
+            {
+                let _ = sender.send(&Message::Rejected(reason.clone()));
+                let _ = sender.flush();
+                return Err(ErrorMessage::new(&format!("Rejected {}: {}", address, reason)));
+            }
+
+            debug!("[{}] {} is running version {}", manager.port(), address, theirs.software_version);
             sender.send(&Message::Packet(Packet::OnConnected))?;
             sender.flush()?;
 
-            manager.register_client_sender(address.clone(), sender)?;
+            manager.register_client_sender(address.clone(), sender, stream, theirs)?;
             Ok(address)
-        }
+        },
+
+        Ok(Message::Rejected(reason)) =>
+            Err(ErrorMessage::new(&format!("Rejected by {}: {}", ip, reason))),
 
-        _ => panic!(),
+        _ => Err(ErrorMessage::new("Expected a handshake")),
     }
 }
 
@@ -65,19 +89,42 @@ pub fn client_handler_thread<H>(packet_handler: H, mut manager: ClientManager,
 
     Ok(std::thread::spawn(move ||
     {
-        let address = request_client_address(
-            sender, &mut receiver, &ip, &mut manager).unwrap();
+        let address_or_error = exchange_handshakes(sender, &mut receiver,
+            stream.try_clone().unwrap(), &ip, &packet_handler, &mut manager);
+        if let Err(err) = address_or_error
+        {
+            info!("[{}] Not connecting to {}: {}", manager.port(), ip, err);
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+            return;
+        }
+
+        let address = address_or_error.unwrap();
 
         info!("[{}] Connected to {}", manager.port(), address);
         loop
         {
             match receiver.recv()
             {
-                // NOTE: We shouldn't be sending an `OnConnected` 
+                // NOTE: We shouldn't be sending a `Handshake` 
                 //       message more then once, do disconnect the 
                 //       client, just to be sure.
-                Ok(Message::OnConnected(_port)) =>
-                    panic!(),
+                Ok(Message::Handshake(_)) =>
+                {
+                    warn!("[{}] {} sent a second handshake", manager.port(), address);
+                    manager.report_misbehaviour(&address, Misbehaviour::ProtocolViolation);
+                    manager.register_disconnect(&address);
+                    let _ = stream.shutdown(std::net::Shutdown::Both);
+                    break;
+                },
+
+                // They didn't accept our handshake
+                Ok(Message::Rejected(reason)) =>
+                {
+                    info!("[{}] Rejected by {}: {}", manager.port(), address, reason);
+                    manager.register_disconnect(&address);
+                    let _ = stream.shutdown(std::net::Shutdown::Both);
+                    break;
+                },
 
                 Ok(Message::KnownNode(node)) => 
                 {
@@ -7,6 +7,8 @@
 use super::packet::{Packet, PacketHandler};
 use super::packet::{Message, MessageSender};
 use super::client::client_handler_thread;
+use super::peer_score::{PeerScores, Misbehaviour, ip_of};
+use super::handshake::{Handshake, Capabilities};
 
 use serde_json;
 use serde::{Serialize, Deserialize};
@@ -25,6 +27,8 @@ struct ClientSender
 {
     address: String,
     sender: MessageSender,
+    stream: TcpStream,
+    handshake: Handshake,
 }
 
 struct ClientReceiver
@@ -83,6 +87,7 @@ struct ConnectionData
     data_directory: PathBuf,
     known_nodes: HashMap<String, NodeConnectionInformation>,
     connected_nodes: HashSet<String>,
+    scores: PeerScores,
 }
 
 impl ConnectionData
@@ -101,6 +106,7 @@ impl ConnectionData
             data_directory: data_directory.clone(),
             known_nodes,
             connected_nodes: HashSet::new(),
+            scores: PeerScores::open(data_directory),
         }))
     }
 
@@ -164,7 +170,7 @@ impl ClientManager
         }
         
         let mut data = self.data.lock().unwrap();
-        if data.known_nodes.contains_key(address) {
+        if data.known_nodes.contains_key(address) || data.scores.is_banned(address) {
             return false;
         }
 
@@ -178,6 +184,30 @@ impl ClientManager
         true
     }
 
+    pub fn connected_nodes(&self) -> Vec<String>
+    {
+        let data = self.data.lock().unwrap();
+        data.connected_nodes.iter().cloned().collect()
+    }
+
+    pub fn connected_nodes_with(&self, capabilities: Capabilities) -> Vec<String>
+    {
+        let data = self.data.lock().unwrap();
+        data.client_senders.iter()
+            .filter(|x| x.handshake.capabilities.contains(capabilities))
+            .map(|x| x.address.clone())
+            .collect()
+    }
+
+    /// The handshake a connected node sent us.
+    pub fn peer(&self, address: &str) -> Option<Handshake>
+    {
+        let data = self.data.lock().unwrap();
+        data.client_senders.iter()
+            .find(|x| x.address == address)
+            .map(|x| x.handshake.clone())
+    }
+
     pub fn pending_connections(&self) -> Vec<String>
     {
         let data = self.data.lock().unwrap();
@@ -190,7 +220,7 @@ impl ClientManager
 
         let mut all_unconnected = data.known_nodes
             .iter()
-            .filter(|(x, _)| !data.connected_nodes.contains(*x))
+            .filter(|(x, _)| !data.connected_nodes.contains(*x) && !data.scores.is_banned(x))
             .collect::<Vec<_>>();
 
         all_unconnected.sort_by_key(|(_, info)| info.average_ping_time());
@@ -226,7 +256,8 @@ impl ClientManager
     }
 
     pub fn register_client_sender(&mut self, address: String, 
-                                  mut sender: MessageSender)
+                                  mut sender: MessageSender, stream: TcpStream,
+                                  handshake: Handshake)
         -> Result<(), Box<dyn Error>>
     {
         let mut data = self.data.lock().unwrap();
@@ -254,6 +285,8 @@ impl ClientManager
         {
             address,
             sender,
+            stream,
+            handshake,
         });
 
         Ok(())
@@ -286,16 +319,57 @@ impl ClientManager
         data.client_senders.retain(|x| x.address != address);
     }
 
+    pub fn is_banned(&self, address: &str) -> bool
+    {
+        self.data.lock().unwrap().scores.is_banned(address)
+    }
+
+    /// Count misbehaviour against a node, disconnecting it if it's now
+    /// banned.
+    pub fn report_misbehaviour(&mut self, address: &str, misbehaviour: Misbehaviour)
+    {
+        let mut data = self.data.lock().unwrap();
+        if !data.scores.penalise(address, misbehaviour) {
+            return;
+        }
+
+        info!("[{}] Disconnecting banned node {}", self.port, address);
+        let ip = ip_of(address);
+        for client in data.client_senders.iter().filter(|x| ip_of(&x.address) == ip) {
+            let _ = client.stream.shutdown(std::net::Shutdown::Both);
+        }
+    }
+
+    pub fn bans(&mut self) -> Vec<(String, u128)>
+    {
+        self.data.lock().unwrap().scores.bans()
+    }
+
+    /// Lift the ban on a node, or every node if none is given.
+    pub fn unban(&mut self, address_or_none: Option<&str>) -> Vec<String>
+    {
+        self.data.lock().unwrap().scores.unban(address_or_none)
+    }
+
     pub fn send_message_to<F>(&mut self, message: Message, mut predicate: F)
         -> Result<(), Box<dyn Error>>
         where F: FnMut(&str) -> bool
     {
+        // NOTE: Nodes aren't sent packets they've said they can't handle
+        let required_capabilities = match &message
+        {
+            Message::Packet(packet) => packet.required_capabilities(),
+            _ => Capabilities::NONE,
+        };
+
         let mut disconnected_clients = Vec::new();
         {
             let mut data = self.data.lock().unwrap();
             for connection in &mut data.client_senders
             {
-                if !predicate(&connection.address) {
+                if !connection.handshake.capabilities.contains(required_capabilities) ||
+                   !predicate(&connection.address)
+                {
                     continue;
                 }
 
@@ -0,0 +1,142 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use libhyperchain::hash::Hash;
+
+use serde::{Serialize, Deserialize};
+use std::ops::BitOr;
+
+/// Bumped whenever the messages or packets change in a way older nodes
+/// can't read.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+pub const DEFAULT_NETWORK_ID: &str = "main";
+
+/// What optional packets a node can answer.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities(u32);
+
+impl Capabilities
+{
+
+    pub const NONE: Self = Self(0);
+
+    /// Has the full blocks, rather than just headers
+    pub const FULL_BLOCKS: Self = Self(1 << 0);
+
+    /// Stores page data and serves its chunks
+    pub const PAGE_DATA: Self = Self(1 << 1);
+
+    /// Answers storage challenges for the chunks it stores
+    pub const STORAGE_PROOFS: Self = Self(1 << 2);
+
+    pub fn contains(&self, other: Self) -> bool
+    {
+        self.0 & other.0 == other.0
+    }
+
+}
+
+impl BitOr for Capabilities
+{
+
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self
+    {
+        Self(self.0 | other.0)
+    }
+
+}
+
+/// Sent by both sides when they connect, before anything else.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Handshake
+{
+    pub protocol_version: u32,
+    pub software_version: String,
+
+    /// Port the node accepts connections on
+    pub port: u16,
+
+    /// Nodes each start with their own first block until they join up, so
+    /// networks are told apart by name rather than by their genesis block
+    pub network_id: String,
+
+    /// Id and hash of the node's top block. Chains are compared by length,
+    /// so the id is also the work behind it.
+    pub top: Option<(u64, Hash)>,
+
+    pub capabilities: Capabilities,
+}
+
+impl Handshake
+{
+
+    pub fn new(port: u16, network_id: &str, top: Option<(u64, Hash)>,
+               capabilities: Capabilities)
+        -> Self
+    {
+        Self
+        {
+            protocol_version: PROTOCOL_VERSION,
+            software_version: env!("CARGO_PKG_VERSION").to_owned(),
+            port,
+            network_id: network_id.to_owned(),
+            top,
+            capabilities,
+        }
+    }
+
+    /// Why we can't talk to the node that sent `other`, if we can't.
+    pub fn incompatibility(&self, other: &Handshake) -> Option<String>
+    {
+        if other.protocol_version != self.protocol_version
+        {
+            return Some(format!("Protocol version {} (from {}) isn't supported, expected {}",
+                other.protocol_version, other.software_version, self.protocol_version));
+        }
+
+        if other.network_id != self.network_id {
+            return Some(format!("On network '{}', expected '{}'", other.network_id, self.network_id));
+        }
+
+        None
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+
+    #[test]
+    fn test_handshake()
+    {
+        let top = Hash::from(&[1u8; 32]);
+        let ours = Handshake::new(8000, DEFAULT_NETWORK_ID, Some((3, top)), Capabilities::FULL_BLOCKS);
+
+        let new_node = Handshake::new(8001, DEFAULT_NETWORK_ID, None, Capabilities::NONE);
+        assert_eq!(ours.incompatibility(&new_node), None);
+        assert_eq!(new_node.incompatibility(&ours), None);
+
+        let other_network = Handshake::new(8001, "test", None, Capabilities::NONE);
+        assert!(ours.incompatibility(&other_network).is_some());
+        assert!(other_network.incompatibility(&ours).is_some());
+
+        let mut old_node = new_node.clone();
+        old_node.protocol_version = PROTOCOL_VERSION - 1;
+        assert!(ours.incompatibility(&old_node).is_some());
+
+        let capabilities = Capabilities::FULL_BLOCKS | Capabilities::PAGE_DATA;
+        assert_eq!(capabilities.contains(Capabilities::PAGE_DATA), true);
+        assert_eq!(capabilities.contains(Capabilities::FULL_BLOCKS | Capabilities::STORAGE_PROOFS), false);
+        assert_eq!(Capabilities::NONE.contains(Capabilities::NONE), true);
+    }
+
+}
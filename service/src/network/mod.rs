@@ -8,6 +8,8 @@ pub mod packet;
 pub mod client;
 pub mod server;
 pub mod client_manager;
+pub mod peer_score;
+pub mod handshake;
 mod node_discovery;
 mod report_manager;
 use packet::PacketHandler;
@@ -124,7 +126,9 @@ mod tests
 {
 
     use super::*;
-    use super::packet::Packet;
+    use super::packet::{Packet, Message};
+    use super::peer_score::Misbehaviour;
+    use super::handshake::{Handshake, Capabilities, DEFAULT_NETWORK_ID};
 
     use std::sync::mpsc::{Sender, Receiver, channel};
     use std::error::Error;
@@ -133,6 +137,7 @@ mod tests
     struct TestCommandHandler
     {
         test_sender: Arc<Mutex<Sender<Packet>>>,
+        handshake: Handshake,
     }
 
     impl PacketHandler for TestCommandHandler
@@ -149,6 +154,11 @@ mod tests
         {
         }
 
+        fn handshake(&self) -> Handshake
+        {
+            self.handshake.clone()
+        }
+
     }
 
     impl<H> NetworkConnection<H>
@@ -164,15 +174,25 @@ mod tests
 
     }
 
-    fn create_connection(port: u16) -> (NetworkConnection<TestCommandHandler>, Receiver<Packet>)
+    fn create_connection_with(port: u16, network_id: &str, capabilities: Capabilities)
+        -> (NetworkConnection<TestCommandHandler>, Receiver<Packet>)
     {
         let (send, recv) = channel();
-        let command_handler = TestCommandHandler { test_sender: Arc::from(Mutex::from(send)) };
+        let command_handler = TestCommandHandler
+        {
+            test_sender: Arc::from(Mutex::from(send)),
+            handshake: Handshake::new(port, network_id, None, capabilities),
+        };
         let connection = NetworkConnection::open_temp(port, command_handler).unwrap();
 
         (connection, recv)
     }
 
+    fn create_connection(port: u16) -> (NetworkConnection<TestCommandHandler>, Receiver<Packet>)
+    {
+        create_connection_with(port, DEFAULT_NETWORK_ID, Capabilities::FULL_BLOCKS)
+    }
+
     #[test]
     fn test_network_disconnect()
     {
@@ -199,6 +219,87 @@ mod tests
         assert!(matches!(recv_b.recv().unwrap(), Packet::Ping {..}));
     }
 
+    #[test]
+    fn test_network_ban()
+    {
+        let _ = pretty_env_logger::try_init();
+
+        let (mut connection_a, recv_a) = create_connection(8090);
+        let (mut connection_b, recv_b) = create_connection(8091);
+        connection_b.manager().register_node("127.0.0.1:8090");
+        assert_eq!(recv_a.recv().unwrap(), Packet::OnConnected);
+        assert_eq!(recv_b.recv().unwrap(), Packet::OnConnected);
+
+        // Disconnected once banned, and kept out
+        connection_a.manager().report_misbehaviour("127.0.0.1:8091", Misbehaviour::InvalidBlock);
+        assert_eq!(connection_a.manager().is_banned("127.0.0.1:8091"), false);
+        connection_a.manager().report_misbehaviour("127.0.0.1:8091", Misbehaviour::InvalidBlock);
+        assert_eq!(connection_a.manager().is_banned("127.0.0.1:8091"), true);
+
+        std::thread::sleep(std::time::Duration::from_millis(3000));
+        assert_eq!(connection_a.manager().connected_nodes(), Vec::<String>::new());
+        assert!(recv_a.try_recv().is_err());
+        assert_eq!(connection_a.manager().bans().len(), 1);
+
+        // Can connect again once the ban is lifted
+        connection_a.manager().unban(None);
+        assert_eq!(recv_a.recv_timeout(std::time::Duration::from_secs(10)).unwrap(), Packet::OnConnected);
+    }
+
+    #[test]
+    fn test_network_second_handshake()
+    {
+        let _ = pretty_env_logger::try_init();
+
+        let (mut connection_a, recv_a) = create_connection(8182);
+        let (mut connection_b, recv_b) = create_connection(8183);
+        connection_b.manager().register_node("127.0.0.1:8182");
+
+        // Each one gets the node disconnected, then it's banned
+        let timeout = std::time::Duration::from_secs(10);
+        for _ in 0..2
+        {
+            assert_eq!(recv_a.recv_timeout(timeout).unwrap(), Packet::OnConnected);
+            assert_eq!(recv_b.recv_timeout(timeout).unwrap(), Packet::OnConnected);
+
+            let handshake = Handshake::new(8183, DEFAULT_NETWORK_ID, None, Capabilities::FULL_BLOCKS);
+            connection_b.manager().send_message_to(Message::Handshake(handshake), |x| x == "127.0.0.1:8182").unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(500));
+        }
+
+        assert_eq!(connection_a.manager().is_banned("127.0.0.1:8183"), true);
+    }
+
+    #[test]
+    fn test_network_handshake()
+    {
+        let _ = pretty_env_logger::try_init();
+
+        // Nodes on another network aren't connected to
+        let (mut connection_a, recv_a) = create_connection_with(8092, DEFAULT_NETWORK_ID, Capabilities::FULL_BLOCKS);
+        let (mut connection_b, recv_b) = create_connection_with(8093, "test", Capabilities::FULL_BLOCKS);
+        connection_b.manager().register_node("127.0.0.1:8092");
+        std::thread::sleep(std::time::Duration::from_millis(2000));
+        assert_eq!(connection_a.manager().connected_nodes(), Vec::<String>::new());
+        assert_eq!(connection_b.manager().connected_nodes(), Vec::<String>::new());
+        assert!(recv_a.try_recv().is_err());
+        assert!(recv_b.try_recv().is_err());
+
+        // Nodes are only sent packets they can handle
+        let (_connection_c, recv_c) = create_connection_with(8094, DEFAULT_NETWORK_ID, Capabilities::NONE);
+        connection_a.manager().register_node("127.0.0.1:8094");
+        assert_eq!(recv_a.recv().unwrap(), Packet::OnConnected);
+        assert_eq!(recv_c.recv().unwrap(), Packet::OnConnected);
+
+        let peer = connection_a.manager().peer("127.0.0.1:8094").unwrap();
+        assert_eq!(peer.capabilities, Capabilities::NONE);
+        assert_eq!(connection_a.manager().connected_nodes_with(Capabilities::FULL_BLOCKS), Vec::<String>::new());
+
+        connection_a.manager().send(Packet::BlockRequest(0)).unwrap();
+        connection_a.manager().send(Packet::Ping(0)).unwrap();
+        assert!(matches!(recv_c.recv().unwrap(), Packet::Ping {..}));
+    }
+
     #[test]
     fn test_network()
     {
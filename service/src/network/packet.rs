@@ -5,13 +5,19 @@
  */
 
 use super::client_manager::ClientManager;
+use super::handshake::{Handshake, Capabilities};
 use crate::report::NodeReport;
 
-use libhyperchain::block::Block;
-use libhyperchain::data_store::data_unit::DataUnit;
+use libhyperchain::block::{Block, BlockHeader};
+use libhyperchain::data_store::compression::CompressedChunk;
+use libhyperchain::merkle_tree::MerkleProof;
+use libhyperchain::data_store::proof::StorageChallenge;
 use libhyperchain::transaction::Transaction;
 use libhyperchain::transaction::transfer::Transfer;
 use libhyperchain::transaction::page::Page;
+use libhyperchain::transaction::site_transfer::SiteTransfer;
+use libhyperchain::transaction::name::NameRegistration;
+use libhyperchain::hash::Hash;
 use serde::{Serialize, Deserialize};
 use std::error::Error;
 
@@ -25,15 +31,55 @@ pub enum Packet
     Block(Block),
     BlockRequest(u64),
     Transfer(Transaction<Transfer>),
-    Page(Transaction<Page>, DataUnit),
+    Page(Transaction<Page>, Vec<CompressedChunk>),
+    SiteTransfer(Transaction<SiteTransfer>),
+    NameRegistration(Transaction<NameRegistration>),
     Report(Option<String>, NodeReport),
+    ChunkRequest(Hash),
+    Chunk(Hash, CompressedChunk),
+    PageChunkRequest(Hash, u32),
+    PageChunk(Hash, u32, CompressedChunk, MerkleProof),
+    StorageChallenge(StorageChallenge),
+    StorageProof(u64, Option<Hash>),
+    GetHeaders(Vec<(u64, Hash)>),
+    Headers(Vec<BlockHeader>),
+    GetBlocks(u64, u64),
+    Blocks(u64, Vec<Block>),
+    AddressBlocksRequest(Hash),
+    AddressBlocks(Hash, Vec<Block>),
     Ping(u128),
 }
 
+impl Packet
+{
+
+    /// What a node needs to be able to do to be sent this packet.
+    pub fn required_capabilities(&self) -> Capabilities
+    {
+        match self
+        {
+            Packet::BlockRequest(_) |
+            Packet::GetBlocks(_, _) |
+            Packet::AddressBlocksRequest(_) => Capabilities::FULL_BLOCKS,
+
+            Packet::ChunkRequest(_) |
+            Packet::PageChunkRequest(_, _) => Capabilities::PAGE_DATA,
+
+            Packet::StorageChallenge(_) => Capabilities::STORAGE_PROOFS,
+
+            _ => Capabilities::NONE,
+        }
+    }
+
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub enum Message
 {
-    OnConnected(u16),
+    Handshake(Handshake),
+
+    /// Sent in place of a handshake, with the reason, when we won't connect
+    Rejected(String),
     KnownNode(String),
     Packet(Packet),
 }
@@ -46,5 +92,8 @@ pub trait PacketHandler
 
     fn update_reports(&self, manager: &mut ClientManager);
 
+    /// What to tell nodes about us when they connect.
+    fn handshake(&self) -> Handshake;
+
 }
 
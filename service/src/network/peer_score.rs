@@ -0,0 +1,321 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use libhyperchain::block::current_timestamp;
+
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Misbehaviour
+{
+    /// Blocks or headers that don't have their work, or can't be added
+    InvalidBlock,
+
+    /// Pages or chunks that don't match what they claim to be
+    InvalidData,
+
+    /// The same block sent to us more then once
+    DuplicateRelay,
+
+    /// Messages the protocol doesn't allow, like a second handshake
+    ProtocolViolation,
+
+    /// More costly requests than we answer in a short time
+    RequestFlood,
+}
+
+/// How much each kind of misbehaviour counts against a node, read from
+/// `peer_scoring.json` in the data directory. Any field left out keeps its
+/// default.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct PeerScoreConfig
+{
+    pub invalid_block: u32,
+    pub invalid_data: u32,
+    pub duplicate_relay: u32,
+    pub protocol_violation: u32,
+    pub request_flood: u32,
+
+    /// Nodes are banned once their score reaches this
+    pub ban_threshold: u32,
+
+    /// Seconds a node stays banned for
+    pub ban_time: u64,
+
+    /// Seconds it takes for one point to be forgiven
+    pub forgive_time: u64,
+}
+
+impl Default for PeerScoreConfig
+{
+
+    fn default() -> Self
+    {
+        Self
+        {
+            invalid_block: 50,
+            invalid_data: 25,
+            duplicate_relay: 5,
+            protocol_violation: 50,
+            request_flood: 10,
+            ban_threshold: 100,
+            ban_time: 24 * 60 * 60,
+            forgive_time: 60,
+        }
+    }
+
+}
+
+impl PeerScoreConfig
+{
+
+    pub fn penalty(&self, misbehaviour: Misbehaviour) -> u32
+    {
+        match misbehaviour
+        {
+            Misbehaviour::InvalidBlock => self.invalid_block,
+            Misbehaviour::InvalidData => self.invalid_data,
+            Misbehaviour::DuplicateRelay => self.duplicate_relay,
+            Misbehaviour::ProtocolViolation => self.protocol_violation,
+            Misbehaviour::RequestFlood => self.request_flood,
+        }
+    }
+
+}
+
+struct PeerScore
+{
+    score: u32,
+    last_forgiven: Instant,
+}
+
+impl PeerScore
+{
+
+    fn forgive(&mut self, forgive_time: Duration)
+    {
+        if forgive_time.is_zero() {
+            return;
+        }
+
+        let forgiven = (self.last_forgiven.elapsed().as_secs() / forgive_time.as_secs()) as u32;
+        if forgiven > 0
+        {
+            self.score = self.score.saturating_sub(forgiven);
+            self.last_forgiven = Instant::now();
+        }
+    }
+
+}
+
+/// The IP part of a node's address. Nodes pick the port they tell us, so
+/// scores and bans go by IP to stop a node dodging them with a new port.
+pub fn ip_of(address: &str) -> &str
+{
+    match address.rsplit_once(':')
+    {
+        Some((ip, _)) => ip,
+        None => address,
+    }
+}
+
+/// Misbehaviour scores for the nodes we talk to, and the ones banned for
+/// going over the threshold, by IP. Bans are kept in `banned_nodes.json` so
+/// they outlast a restart.
+pub struct PeerScores
+{
+    config: PeerScoreConfig,
+    scores: HashMap<String, PeerScore>,
+
+    /// Banned IPs, with the time their ban ends in milliseconds
+    bans: HashMap<String, u128>,
+    bans_path: PathBuf,
+}
+
+fn load_json<T>(path: &Path) -> Result<T, Box<dyn Error>>
+    where T: for<'a> Deserialize<'a>
+{
+    let file = File::open(path)?;
+    Ok(serde_json::from_reader(file)?)
+}
+
+impl PeerScores
+{
+
+    pub fn open(data_directory: &Path) -> Self
+    {
+        let config_path = data_directory.join("peer_scoring.json");
+        let config = match load_json(&config_path)
+        {
+            Ok(config) => config,
+            Err(err) =>
+            {
+                if config_path.exists() {
+                    warn!("Unable to read {:?}, using default scoring: {}", config_path, err);
+                }
+                PeerScoreConfig::default()
+            },
+        };
+
+        let bans_path = data_directory.join("banned_nodes.json");
+        Self
+        {
+            config,
+            scores: HashMap::new(),
+            bans: load_json(&bans_path).unwrap_or_default(),
+            bans_path,
+        }
+    }
+
+    fn flush_bans(&self)
+    {
+        let result = File::create(&self.bans_path)
+            .map_err(|err| Box::new(err) as Box<dyn Error>)
+            .and_then(|file| Ok(serde_json::to_writer_pretty(file, &self.bans)?));
+
+        if let Err(err) = result {
+            warn!("Unable to save banned nodes: {}", err);
+        }
+    }
+
+    /// Add to a node's score, banning it if that takes it over the
+    /// threshold. Returns true if it was banned.
+    pub fn penalise(&mut self, address: &str, misbehaviour: Misbehaviour) -> bool
+    {
+        if self.is_banned(address) {
+            return false;
+        }
+
+        let ip = ip_of(address);
+        let forgive_time = Duration::from_secs(self.config.forgive_time);
+        let peer = self.scores
+            .entry(ip.to_owned())
+            .or_insert(PeerScore { score: 0, last_forgiven: Instant::now() });
+
+        peer.forgive(forgive_time);
+        peer.score = peer.score.saturating_add(self.config.penalty(misbehaviour));
+        debug!("Node {} scored {} for {:?}", address, peer.score, misbehaviour);
+        if peer.score < self.config.ban_threshold {
+            return false;
+        }
+
+        warn!("Banning {} for {} second(s)", ip, self.config.ban_time);
+        self.scores.remove(ip);
+        self.bans.insert(ip.to_owned(),
+            current_timestamp() + self.config.ban_time as u128 * 1000);
+        self.flush_bans();
+        true
+    }
+
+    pub fn is_banned(&self, address: &str) -> bool
+    {
+        matches!(self.bans.get(ip_of(address)), Some(until) if *until > current_timestamp())
+    }
+
+    /// Current bans, and when they end.
+    pub fn bans(&mut self) -> Vec<(String, u128)>
+    {
+        let now = current_timestamp();
+        let ban_count = self.bans.len();
+        self.bans.retain(|_, until| *until > now);
+        if self.bans.len() != ban_count {
+            self.flush_bans();
+        }
+
+        let mut bans = self.bans.iter()
+            .map(|(address, until)| (address.clone(), *until))
+            .collect::<Vec<_>>();
+        bans.sort();
+        bans
+    }
+
+    /// Lift the ban on one node, or all of them. Returns the ones lifted.
+    pub fn unban(&mut self, address_or_none: Option<&str>) -> Vec<String>
+    {
+        let unbanned = match address_or_none
+        {
+            Some(address) =>
+                self.bans.remove_entry(ip_of(address)).map(|(address, _)| address).into_iter().collect(),
+            None =>
+                self.bans.drain().map(|(address, _)| address).collect::<Vec<_>>(),
+        };
+
+        for address in &unbanned {
+            info!("Lifted ban on {}", address);
+        }
+        self.flush_bans();
+        unbanned
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+
+    #[test]
+    fn test_peer_scores()
+    {
+        let path = std::env::temp_dir().join(format!("{}peer_scores", rand::random::<u32>()));
+        std::fs::create_dir_all(&path).unwrap();
+        let mut scores = PeerScores::open(&path);
+        assert_eq!(scores.config, PeerScoreConfig::default());
+
+        // Banned once over the threshold
+        assert_eq!(scores.penalise("a", Misbehaviour::InvalidData), false);
+        assert_eq!(scores.penalise("a", Misbehaviour::DuplicateRelay), false);
+        assert_eq!(scores.scores.get("a").unwrap().score, 30);
+        assert_eq!(scores.penalise("a", Misbehaviour::InvalidBlock), false);
+        assert_eq!(scores.penalise("a", Misbehaviour::InvalidBlock), true);
+        assert_eq!(scores.is_banned("a"), true);
+        assert_eq!(scores.is_banned("b"), false);
+
+        // Bans are kept between restarts
+        scores.penalise("b", Misbehaviour::InvalidBlock);
+        scores.penalise("b", Misbehaviour::InvalidBlock);
+        let mut scores = PeerScores::open(&path);
+        assert_eq!(scores.bans().len(), 2);
+        assert_eq!(scores.is_banned("b"), true);
+
+        assert_eq!(scores.unban(Some("a")), vec!["a".to_owned()]);
+        assert_eq!(scores.is_banned("a"), false);
+        assert_eq!(scores.unban(None), vec!["b".to_owned()]);
+        assert_eq!(PeerScores::open(&path).bans(), Vec::new());
+
+        // The port is up to the node, so it's left out
+        scores.penalise("1.2.3.4:8000", Misbehaviour::InvalidBlock);
+        assert_eq!(scores.penalise("1.2.3.4:8001", Misbehaviour::InvalidBlock), true);
+        assert_eq!(scores.is_banned("1.2.3.4:9000"), true);
+        assert_eq!(scores.is_banned("1.2.3.5:8000"), false);
+        assert_eq!(scores.unban(Some("1.2.3.4:8000")), vec!["1.2.3.4".to_owned()]);
+        assert_eq!(scores.is_banned("1.2.3.4:8001"), false);
+
+        // Penalties can be configured
+        let config = File::create(path.join("peer_scoring.json")).unwrap();
+        serde_json::to_writer(config, &serde_json::json!({ "duplicate_relay": 100 })).unwrap();
+        let mut scores = PeerScores::open(&path);
+        assert_eq!(scores.config.invalid_block, PeerScoreConfig::default().invalid_block);
+        assert_eq!(scores.penalise("c", Misbehaviour::DuplicateRelay), true);
+
+        // Scores go down over time
+        let mut peer = PeerScore { score: 10, last_forgiven: Instant::now() - Duration::from_secs(5) };
+        peer.forgive(Duration::from_secs(2));
+        assert_eq!(peer.score, 8);
+        peer.forgive(Duration::from_secs(2));
+        assert_eq!(peer.score, 8);
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+}
@@ -0,0 +1,300 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use libhyperchain::block::Block;
+use libhyperchain::block::validate::BlockValidationResult;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Longer forks are synced from their headers instead.
+const MAX_BRANCH_LENGTH: usize = 64;
+const MAX_BUFFERED_BLOCKS: usize = 256;
+const BRANCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+const MAX_FAILED_BRANCHES: u32 = 3;
+const FAILED_BRANCH_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, PartialEq)]
+pub enum BranchAddResult
+{
+    /// Added, with the id of the block needed before it if there is one
+    Ok(Option<u64>),
+    DoesNotFit,
+    TooLong,
+}
+
+struct Branch
+{
+    blocks: Vec<Block>,
+    last_updated: Instant,
+}
+
+/// Blocks from other nodes that don't fit onto our chain, kept while we
+/// fetch the ones before them. How much is kept from each node, and for how
+/// long, is limited so they can't fill our memory with fake branches.
+pub struct Branches
+{
+    branches: HashMap<String, Branch>,
+
+    /// Nodes who's branches couldn't be merged, and when they last sent one
+    failures: HashMap<String, (u32, Instant)>,
+}
+
+fn follows_on(block: &Block, prev: &Block) -> bool
+{
+    // NOTE: The first block passes `validate_next` whatever it's given
+    block.header.block_id == prev.header.block_id + 1 &&
+        matches!(block.validate_next(prev), Ok(BlockValidationResult::Ok))
+}
+
+impl Branches
+{
+
+    pub fn new() -> Self
+    {
+        Self
+        {
+            branches: HashMap::new(),
+            failures: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, from: &str) -> Option<&[Block]>
+    {
+        self.branches.get(from).map(|branch| branch.blocks.as_slice())
+    }
+
+    pub fn take(&mut self, from: &str) -> Option<Vec<Block>>
+    {
+        self.branches.remove(from).map(|branch| branch.blocks)
+    }
+
+    fn buffered_blocks(&self) -> usize
+    {
+        self.branches.values().map(|branch| branch.blocks.len()).sum()
+    }
+
+    fn try_insert_block_into_branch(branch: &mut Vec<Block>, block: Block)
+        -> bool
+    {
+        // Is start of new branch
+        if branch.is_empty()
+        {
+            branch.push(block);
+            return true;
+        }
+
+        // Can be added to the bottom
+        let bottom = branch.first().unwrap();
+        if follows_on(bottom, &block)
+        {
+            branch.insert(0, block);
+            return true;
+        }
+
+        // Can be added to the top
+        let top = branch.last().unwrap();
+        if follows_on(&block, top) {
+            branch.push(block);
+            return true;
+        }
+
+        // Already exists
+        let bottom_id = bottom.header.block_id;
+        let top_id = top.header.block_id;
+        if (bottom_id..=top_id).contains(&block.header.block_id)
+        {
+            let branch_index = (block.header.block_id - bottom_id) as usize;
+            let existing_block_in_branch = branch.get(branch_index).unwrap();
+            if &block == existing_block_in_branch {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Make room for another block by dropping the branch that's gone the
+    /// longest without a new one, other than `from`'s.
+    fn evict_oldest(&mut self, from: &str) -> bool
+    {
+        let oldest_or_none = self.branches.iter()
+            .filter(|(address, _)| address.as_str() != from)
+            .min_by_key(|(_, branch)| branch.last_updated)
+            .map(|(address, _)| address.clone());
+
+        match oldest_or_none
+        {
+            Some(oldest) =>
+            {
+                debug!("Dropping branch from {} to make room", oldest);
+                self.branches.remove(&oldest);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn add(&mut self, from: &str, block: Block) -> BranchAddResult
+    {
+        while self.buffered_blocks() >= MAX_BUFFERED_BLOCKS
+        {
+            if !self.evict_oldest(from) {
+                break;
+            }
+        }
+
+        let mut branch = self.branches.remove(from)
+            .map(|branch| branch.blocks)
+            .unwrap_or_default();
+
+        if branch.len() >= MAX_BRANCH_LENGTH || self.buffered_blocks() + branch.len() >= MAX_BUFFERED_BLOCKS {
+            return BranchAddResult::TooLong;
+        }
+
+        if !Self::try_insert_block_into_branch(&mut branch, block) {
+            return BranchAddResult::DoesNotFit;
+        }
+
+        let bottom_id = branch.first().unwrap().header.block_id;
+        self.branches.insert(from.to_owned(), Branch
+        {
+            blocks: branch,
+            last_updated: Instant::now(),
+        });
+
+        if bottom_id == 0 {
+            BranchAddResult::Ok(None)
+        } else {
+            BranchAddResult::Ok(Some(bottom_id - 1))
+        }
+    }
+
+    /// Record a branch from this node that couldn't be merged.
+    pub fn penalise(&mut self, from: &str)
+    {
+        let failures = self.failures
+            .entry(from.to_owned())
+            .or_insert((0, Instant::now()));
+
+        failures.0 += 1;
+        failures.1 = Instant::now();
+        if failures.0 >= MAX_FAILED_BRANCHES {
+            warn!("Ignoring branches from {} for a while", from);
+        }
+    }
+
+    /// Whether to stop keeping branches from a node, after too many of them
+    /// couldn't be merged.
+    pub fn is_penalised(&self, from: &str) -> bool
+    {
+        matches!(self.failures.get(from), Some((count, _)) if *count >= MAX_FAILED_BRANCHES)
+    }
+
+    fn remove_older_than(&mut self, branch_timeout: Duration, failure_timeout: Duration)
+    {
+        self.branches.retain(|address, branch|
+        {
+            let has_expired = branch.last_updated.elapsed() > branch_timeout;
+            if has_expired {
+                debug!("Branch from {} expired", address);
+            }
+            !has_expired
+        });
+
+        self.failures.retain(|_, (_, last_failure)| last_failure.elapsed() <= failure_timeout);
+    }
+
+    /// Drop branches that haven't been added to in a while, and forgive
+    /// nodes that haven't sent a bad one recently.
+    pub fn expire(&mut self)
+    {
+        self.remove_older_than(BRANCH_TIMEOUT, FAILED_BRANCH_TIMEOUT);
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+    use libhyperchain::chain::BlockChain;
+    use libhyperchain::wallet::private_wallet::PrivateWallet;
+    use libhyperchain::miner;
+
+    fn mine_blocks(count: usize) -> Vec<Block>
+    {
+        let time = libhyperchain::block::current_timestamp();
+        let path = std::env::temp_dir().join(format!("{}branches", time));
+        let mut chain = BlockChain::open(&path).unwrap();
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+
+        let blocks = (0..count)
+            .map(|_|
+            {
+                let block = miner::mine_block(Block::new_blank(&mut chain, &wallet).unwrap());
+                chain.add(&block).unwrap();
+                block
+            })
+            .collect();
+
+        let _ = std::fs::remove_dir_all(path);
+        blocks
+    }
+
+    #[test]
+    fn test_branches()
+    {
+        let blocks = mine_blocks(4);
+        let mut branches = Branches::new();
+
+        // Built backwards from the top
+        assert_eq!(branches.add("a", blocks[3].clone()), BranchAddResult::Ok(Some(2)));
+        assert_eq!(branches.add("a", blocks[2].clone()), BranchAddResult::Ok(Some(1)));
+        assert_eq!(branches.get("a").unwrap().len(), 2);
+
+        // Starting again if the node sends something else
+        assert_eq!(branches.add("a", blocks[0].clone()), BranchAddResult::DoesNotFit);
+        assert_eq!(branches.get("a"), None);
+        assert_eq!(branches.add("a", blocks[3].clone()), BranchAddResult::Ok(Some(2)));
+        assert_eq!(branches.add("a", blocks[2].clone()), BranchAddResult::Ok(Some(1)));
+        assert_eq!(branches.add("b", blocks[1].clone()), BranchAddResult::Ok(Some(0)));
+
+        // Each node's branch is capped
+        let mut long_branch = Branch { blocks: Vec::new(), last_updated: Instant::now() };
+        long_branch.blocks.resize(MAX_BRANCH_LENGTH, blocks[0].clone());
+        branches.branches.insert("c".to_owned(), long_branch);
+        assert_eq!(branches.add("c", blocks[1].clone()), BranchAddResult::TooLong);
+        assert_eq!(branches.get("c"), None);
+
+        // As is the total, dropping the oldest to make room
+        for (i, address) in ["d", "e", "f", "g"].iter().enumerate()
+        {
+            let mut branch = Branch { blocks: Vec::new(), last_updated: Instant::now() };
+            branch.blocks.resize(MAX_BRANCH_LENGTH, blocks[i].clone());
+            branches.branches.insert(address.to_string(), branch);
+        }
+        assert!(branches.buffered_blocks() >= MAX_BUFFERED_BLOCKS);
+        assert_eq!(branches.add("h", blocks[3].clone()), BranchAddResult::Ok(Some(2)));
+        assert_eq!(branches.get("a"), None);
+        assert!(branches.buffered_blocks() <= MAX_BUFFERED_BLOCKS);
+
+        // Bad branches are remembered for a while
+        for _ in 0..MAX_FAILED_BRANCHES {
+            branches.penalise("b");
+        }
+        assert_eq!(branches.is_penalised("b"), true);
+        branches.expire();
+        assert_eq!(branches.is_penalised("b"), true);
+        assert_eq!(branches.get("h").is_some(), true);
+
+        branches.remove_older_than(Duration::ZERO, Duration::ZERO);
+        assert_eq!(branches.is_penalised("b"), false);
+        assert_eq!(branches.get("h"), None);
+    }
+
+}
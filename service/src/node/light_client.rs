@@ -0,0 +1,623 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::Node;
+use super::sync::HEADER_BATCH_SIZE;
+use crate::network::client_manager::ClientManager;
+use crate::network::packet::Packet;
+use crate::network::peer_score::Misbehaviour;
+use crate::network::handshake::Capabilities;
+
+use libhyperchain::chain::headers::{HeaderChain, HeaderChainAddResult};
+use libhyperchain::block::{Block, BlockHeader};
+use libhyperchain::block::validate::BlockValidationResult;
+use libhyperchain::wallet::WalletStatus;
+use libhyperchain::hash::Hash;
+use rand::seq::SliceRandom;
+use std::collections::{HashMap, HashSet, BTreeMap};
+use std::sync::mpsc::{Sender, Receiver, channel};
+use std::time::{Duration, Instant};
+use std::path::PathBuf;
+use std::error::Error;
+
+/// How many nodes are asked for an address's blocks, so one can't hide
+/// blocks by leaving them out.
+const ADDRESS_PEERS: usize = 3;
+
+/// How long to wait for every node asked about an address to answer.
+const ADDRESS_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Address requests a node can make in each window, as answering one means
+/// going through the whole chain.
+const MAX_ADDRESS_REQUESTS: usize = 30;
+const ADDRESS_REQUEST_WINDOW: Duration = Duration::from_secs(60);
+
+/// The blocks fetched for a watched address.
+struct WatchedAddress
+{
+    /// Blocks using the address, by block id
+    blocks: BTreeMap<u64, Block>,
+
+    /// Id of the first block we don't know if it uses the address
+    synced_to: u64,
+}
+
+/// An address whose blocks have been asked for from several nodes.
+struct PendingAddress
+{
+    /// Nodes that haven't answered yet
+    waiting_on: HashSet<String>,
+    answers: usize,
+
+    /// Every block any of them sent that uses the address
+    blocks: BTreeMap<u64, Block>,
+    sent: Instant,
+}
+
+/// State for nodes that only follow the chain's headers, and fetch the
+/// blocks they need for the addresses being watched.
+///
+/// NOTE: Blocks are checked against the headers, and the answers from each
+///       node asked are merged, so a block is only missed if every one of
+///       them leaves it out.
+pub struct LightClient
+{
+    headers: HeaderChain,
+    watched: HashMap<Hash, WatchedAddress>,
+
+    /// Addresses asked for that haven't been answered yet
+    pending: HashMap<Hash, PendingAddress>,
+
+    /// Told when the blocks for an address have been fetched
+    listeners: HashMap<Hash, Vec<Sender<()>>>,
+}
+
+impl LightClient
+{
+
+    pub fn open(path: &PathBuf) -> Result<Self, Box<dyn Error>>
+    {
+        Ok(Self
+        {
+            headers: HeaderChain::open(path)?,
+            watched: HashMap::new(),
+            pending: HashMap::new(),
+            listeners: HashMap::new(),
+        })
+    }
+
+    /// The wallet's status from the blocks fetched for it, or none if they
+    /// haven't arrived yet or are behind our headers.
+    pub fn wallet_status(&self, address: &Hash)
+        -> Result<Option<WalletStatus>, Box<dyn Error>>
+    {
+        let watched_or_none = self.watched.get(address);
+        if watched_or_none.is_none() {
+            return Ok(None);
+        }
+
+        let watched = watched_or_none.unwrap();
+        if watched.synced_to < self.headers.next_top() {
+            return Ok(None);
+        }
+
+        let mut status = WalletStatus::default();
+        for block in watched.blocks.values() {
+            status = block.update_wallet_status(address, status)?;
+        }
+
+        Ok(Some(status))
+    }
+
+    /// Get told when the blocks for an address have been fetched.
+    pub fn listen_for_address(&mut self, address: &Hash) -> Receiver<()>
+    {
+        let (sender, receiver) = channel();
+        self.listeners.entry(*address).or_default().push(sender);
+        receiver
+    }
+
+    fn is_synced(&self, address: &Hash) -> bool
+    {
+        matches!(self.watched.get(address), Some(watched) if watched.synced_to >= self.headers.next_top())
+    }
+
+    pub fn block_with_transaction(&self, transaction_id: &Hash) -> Option<Block>
+    {
+        self.watched.values()
+            .flat_map(|watched| watched.blocks.values())
+            .find(|block| matches!(block.transaction_proof(transaction_id), Ok(Some(_))))
+            .cloned()
+    }
+
+    /// Whether the block is in our headers and uses the address.
+    fn is_block_for(&mut self, address: &Hash, block: &Block)
+        -> Result<bool, Box<dyn Error>>
+    {
+        let header_or_none = self.headers.header(block.header.block_id);
+        if header_or_none.is_none() {
+            return Ok(false);
+        }
+
+        Ok(block.validate_against_header(&header_or_none.unwrap())? == BlockValidationResult::Ok &&
+           block.get_addresses_used().contains(address))
+    }
+
+    /// Start watching an address with the blocks the nodes asked sent.
+    ///
+    /// NOTE: Their answers are taken to cover every block we have the
+    ///       header for
+    fn finish_pending(&mut self, address: &Hash)
+    {
+        if let Some(pending) = self.pending.remove(address)
+        {
+            let synced_to = self.headers.next_top();
+            self.watched.insert(*address, WatchedAddress { blocks: pending.blocks, synced_to });
+        }
+
+        for listener in self.listeners.remove(address).unwrap_or_default() {
+            let _ = listener.send(());
+        }
+    }
+
+    /// Give up on nodes that haven't answered in time. Addresses at least
+    /// one node answered for are watched with what they sent, the rest can
+    /// be asked for again.
+    fn expire_pending(&mut self) -> Vec<Hash>
+    {
+        let expired = self.pending.iter()
+            .filter(|(_, pending)| pending.sent.elapsed() > ADDRESS_REQUEST_TIMEOUT)
+            .map(|(address, pending)| (*address, pending.answers))
+            .collect::<Vec<_>>();
+
+        for (address, answers) in &expired
+        {
+            if *answers > 0 {
+                self.finish_pending(address);
+            } else {
+                self.pending.remove(address);
+            }
+        }
+
+        expired.into_iter().map(|(address, _)| address).collect()
+    }
+
+    /// Follow the block just added to our headers for the addresses that
+    /// were synced up to it, so they don't need fetching again.
+    fn add_next_block(&mut self, block: &Block) -> Result<(), Box<dyn Error>>
+    {
+        let block_id = block.header.block_id;
+        if self.headers.header(block_id).as_ref() != Some(&block.header) ||
+           block.validate_against_header(&block.header)? != BlockValidationResult::Ok
+        {
+            return Ok(());
+        }
+
+        let addresses_used = block.get_addresses_used();
+        for (address, watched) in &mut self.watched
+        {
+            if watched.synced_to != block_id {
+                continue;
+            }
+
+            if addresses_used.contains(address) {
+                watched.blocks.insert(block_id, block.clone());
+            }
+            watched.synced_to = block_id + 1;
+        }
+
+        Ok(())
+    }
+
+    /// Addresses whose blocks are behind our headers, for example after
+    /// catching up on a batch of them, and aren't being fetched already.
+    fn stale_addresses(&self) -> Vec<Hash>
+    {
+        let next_top = self.headers.next_top();
+        self.watched.iter()
+            .filter(|(address, watched)| watched.synced_to < next_top && !self.pending.contains_key(address))
+            .map(|(address, _)| *address)
+            .collect()
+    }
+
+    /// Stop watching addresses with blocks no longer in the chain after a
+    /// fork, returning them so they can be fetched again from the new
+    /// branch.
+    fn remove_forked_addresses(&mut self) -> Vec<Hash>
+    {
+        let headers = &mut self.headers;
+        let forked = self.watched.iter()
+            .filter(|(_, watched)| watched.blocks.iter()
+                .any(|(block_id, block)| headers.header(*block_id).as_ref() != Some(&block.header)))
+            .map(|(address, _)| *address)
+            .collect::<Vec<_>>();
+
+        for address in &forked {
+            self.watched.remove(address);
+        }
+        forked
+    }
+
+    pub(super) fn header(&mut self, block_id: u64) -> Option<BlockHeader>
+    {
+        self.headers.header(block_id)
+    }
+
+    pub(super) fn top(&mut self) -> Option<BlockHeader>
+    {
+        match self.headers.next_top()
+        {
+            0 => None,
+            next_top => self.headers.header(next_top - 1),
+        }
+    }
+
+    pub(super) fn locator(&mut self) -> Vec<(u64, Hash)>
+    {
+        self.headers.locator()
+    }
+
+}
+
+impl Node
+{
+
+    pub fn enable_light_client(&mut self, path: &PathBuf) -> Result<(), Box<dyn Error>>
+    {
+        self.light_client = Some(LightClient::open(path)?);
+        Ok(())
+    }
+
+    pub fn light_client(&mut self) -> Option<&mut LightClient>
+    {
+        self.light_client.as_mut()
+    }
+
+    /// Ask for every block using this address, if we aren't already
+    /// watching it or its blocks are behind our headers.
+    pub fn watch_address(&mut self, manager: &mut ClientManager, address: &Hash)
+        -> Result<(), Box<dyn Error>>
+    {
+        let light_client = self.light_client.as_mut().unwrap();
+        if light_client.is_synced(address) || light_client.pending.contains_key(address) {
+            return Ok(());
+        }
+
+        if !light_client.watched.contains_key(address) {
+            info!("[{}] Watching address {}", self.port, address);
+        }
+        self.request_address_blocks(manager, address)
+    }
+
+    /// Ask a few nodes with full blocks for the blocks using an address.
+    fn request_address_blocks(&mut self, manager: &mut ClientManager, address: &Hash)
+        -> Result<(), Box<dyn Error>>
+    {
+        let peers = manager.connected_nodes_with(Capabilities::FULL_BLOCKS);
+        let asked = peers
+            .choose_multiple(&mut rand::thread_rng(), ADDRESS_PEERS)
+            .cloned()
+            .collect::<HashSet<_>>();
+        if asked.is_empty() {
+            return Ok(());
+        }
+
+        for peer in &asked {
+            manager.send_to(Packet::AddressBlocksRequest(*address), |x| x == peer)?;
+        }
+
+        let light_client = self.light_client.as_mut().unwrap();
+        light_client.pending.insert(*address, PendingAddress
+        {
+            waiting_on: asked,
+            answers: 0,
+            blocks: BTreeMap::new(),
+            sent: Instant::now(),
+        });
+        Ok(())
+    }
+
+    pub fn handle_headers(&mut self, manager: &mut ClientManager,
+                          from: &str, headers: Vec<BlockHeader>)
+        -> Result<(), Box<dyn Error>>
+    {
+        self.add_headers(manager, from, headers, None)
+    }
+
+    /// Add headers to the light client's chain, following `block_or_none`
+    /// for watched addresses if it's the block of the only header.
+    fn add_headers(&mut self, manager: &mut ClientManager, from: &str,
+                   headers: Vec<BlockHeader>, block_or_none: Option<&Block>)
+        -> Result<(), Box<dyn Error>>
+    {
+        if self.light_client.is_none() || headers.is_empty() {
+            return Ok(());
+        }
+
+        let light_client = self.light_client.as_mut().unwrap();
+        let bottom_id = headers.first().unwrap().block_id;
+        match light_client.headers.add(&headers)?
+        {
+            HeaderChainAddResult::Ok =>
+            {
+                info!("[{}] Added headers {} to {}", self.port, bottom_id,
+                    light_client.headers.next_top() - 1);
+
+                if let Some(block) = block_or_none {
+                    light_client.add_next_block(block)?;
+                }
+
+                // Watched addresses may have blocks we don't have after
+                // a fork or catching up, so fetch them again
+                let stale = light_client.stale_addresses();
+                for address in light_client.remove_forked_addresses() {
+                    self.watch_address(manager, &address)?;
+                }
+                for address in stale {
+                    self.request_address_blocks(manager, &address)?;
+                }
+
+                // There may be more after a full batch
+                if headers.len() as u64 == HEADER_BATCH_SIZE {
+                    self.request_sync_headers(manager, from)?;
+                }
+            },
+
+            HeaderChainAddResult::MoreNeeded =>
+                self.request_sync_headers(manager, from)?,
+
+            HeaderChainAddResult::Short => {},
+            HeaderChainAddResult::Invalid(result) =>
+                warn!("[{}] Got invalid headers from {}: {}", self.port, from, result),
+        }
+
+        Ok(())
+    }
+
+    /// New blocks are only followed by their header, unless they use an
+    /// address we're watching.
+    pub fn handle_light_block(&mut self, manager: &mut ClientManager,
+                              from: &str, block: Block)
+        -> Result<(), Box<dyn Error>>
+    {
+        self.add_headers(manager, from, vec![block.header.clone()], Some(&block))
+    }
+
+    /// Count an address request from a node, returning false if it's made
+    /// too many recently.
+    fn allow_address_request(&mut self, from: &str) -> bool
+    {
+        let requests = self.address_requests.entry(from.to_owned()).or_default();
+        while matches!(requests.front(), Some(sent) if sent.elapsed() > ADDRESS_REQUEST_WINDOW) {
+            requests.pop_front();
+        }
+
+        if requests.len() >= MAX_ADDRESS_REQUESTS {
+            return false;
+        }
+
+        requests.push_back(Instant::now());
+        true
+    }
+
+    pub fn handle_address_blocks_request(&mut self, manager: &mut ClientManager,
+                                         from: &str, address: Hash)
+        -> Result<(), Box<dyn Error>>
+    {
+        if self.light_client.is_some() {
+            return Ok(());
+        }
+
+        if !self.allow_address_request(from)
+        {
+            warn!("[{}] {} is asking for too many addresses", self.port, from);
+            manager.report_misbehaviour(from, Misbehaviour::RequestFlood);
+            return Ok(());
+        }
+
+        // Only load the blocks the metadata says use the address
+        let mut blocks = Vec::new();
+        for block_id in 0..self.chain.next_top()
+        {
+            if !self.chain.block_metadata(block_id).wallets.contains_key(&address) {
+                continue;
+            }
+
+            if let Some(block) = self.chain.block(block_id) {
+                blocks.push(block);
+            }
+        }
+
+        manager.send_to(Packet::AddressBlocks(address, blocks), |x| x == from)?;
+        Ok(())
+    }
+
+    /// Merge a node's answer into the blocks for an address, and start
+    /// watching it once every node asked has answered.
+    pub fn handle_address_blocks(&mut self, from: &str, address: Hash, blocks: Vec<Block>)
+        -> Result<(), Box<dyn Error>>
+    {
+        let port = self.port;
+        let light_client_or_none = self.light_client.as_mut();
+        if light_client_or_none.is_none() {
+            return Ok(());
+        }
+
+        let light_client = light_client_or_none.unwrap();
+        let was_asked = light_client.pending.get_mut(&address)
+            .map_or(false, |pending| pending.waiting_on.remove(from));
+        if !was_asked {
+            return Ok(());
+        }
+
+        let mut valid_blocks = Vec::new();
+        for block in blocks
+        {
+            if light_client.is_block_for(&address, &block)? {
+                valid_blocks.push(block);
+            } else {
+                warn!("[{}] Block {} from {} doesn't match our headers", port, block.header.block_id, from);
+            }
+        }
+
+        let pending = light_client.pending.get_mut(&address).unwrap();
+        pending.answers += 1;
+        for block in valid_blocks {
+            pending.blocks.insert(block.header.block_id, block);
+        }
+
+        if pending.waiting_on.is_empty() {
+            light_client.finish_pending(&address);
+        }
+        Ok(())
+    }
+
+    pub(super) fn update_light_client(&mut self)
+    {
+        if let Some(light_client) = &mut self.light_client
+        {
+            for address in light_client.expire_pending() {
+                info!("[{}] Not every node answered for address {}", self.port, address);
+            }
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::{PendingAddress, MAX_ADDRESS_REQUESTS};
+    use crate::node::tests::{create_node, mine_block, wait_for_block};
+    use libhyperchain::block::Block;
+    use libhyperchain::miner;
+    use libhyperchain::wallet::Wallet;
+    use libhyperchain::wallet::private_wallet::PrivateWallet;
+    use std::collections::{HashSet, BTreeMap};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_light_client()
+    {
+        let _ = pretty_env_logger::try_init();
+
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let other = PrivateWallet::open_temp(1).unwrap();
+        let mut connection_a = create_node(8070);
+        mine_block(&mut connection_a, &wallet);
+        mine_block(&mut connection_a, &other);
+        mine_block(&mut connection_a, &wallet);
+
+        let mut connection_b = create_node(8071);
+        {
+            let path = std::env::temp_dir().join(format!("{}8071-headers", libhyperchain::block::current_timestamp()));
+            connection_b.handler().node().enable_light_client(&path).unwrap();
+        }
+        connection_b.manager().register_node("127.0.0.1:8070");
+
+        // B follows A's headers without storing the blocks
+        let next_top = || connection_b.handler().node().light_client().unwrap().headers.next_top();
+        while next_top() < 3 {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        mine_block(&mut connection_a, &wallet);
+        while next_top() < 4 {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert_eq!(connection_b.handler().node().chain().top(), None);
+        let top = wait_for_block(&connection_a, 3);
+        assert_eq!(connection_b.handler().node().light_client().unwrap().headers.top(), Some(top.header));
+
+        // Only the blocks for watched addresses are fetched
+        let address = wallet.get_address();
+        {
+            let handler = connection_b.handler().clone();
+            handler.node().watch_address(connection_b.manager(), &address).unwrap();
+        }
+        let status = || connection_b.handler().node().light_client().unwrap().wallet_status(&address).unwrap();
+        while status().is_none() {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert_eq!(status(), Some(connection_a.handler().node().chain().get_wallet_status(&address)));
+
+        // Blocks only caught up on by their header are fetched again
+        let block =
+        {
+            let mut node = connection_a.handler().node();
+            let chain = node.chain();
+            let block = miner::mine_block(Block::new_blank(chain, &wallet).unwrap());
+            chain.add(&block).unwrap();
+            block
+        };
+        {
+            let handler = connection_b.handler().clone();
+            handler.node().handle_headers(connection_b.manager(), "127.0.0.1:8070", vec![block.header]).unwrap();
+        }
+        let status = || connection_b.handler().node().light_client().unwrap().wallet_status(&address).unwrap();
+        assert_eq!(status(), None);
+        while status().is_none() {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert_eq!(status(), Some(connection_a.handler().node().chain().get_wallet_status(&address)));
+
+        // Ones that arrive as they're mined are followed without fetching
+        mine_block(&mut connection_a, &wallet);
+        let next_top = || connection_b.handler().node().light_client().unwrap().headers.next_top();
+        while next_top() < 6 {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert_eq!(status(), Some(connection_a.handler().node().chain().get_wallet_status(&address)));
+    }
+
+    #[test]
+    fn test_light_client_merges_answers()
+    {
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let other = PrivateWallet::open_temp(1).unwrap();
+        let mut connection_a = create_node(8072);
+        mine_block(&mut connection_a, &wallet);
+        mine_block(&mut connection_a, &other);
+        mine_block(&mut connection_a, &wallet);
+
+        let mut connection_b = create_node(8073);
+        let path = std::env::temp_dir().join(format!("{}8073-headers", libhyperchain::block::current_timestamp()));
+        let blocks = (0..3)
+            .map(|block_id| connection_a.handler().node().chain().block(block_id).unwrap())
+            .collect::<Vec<_>>();
+        let handler = connection_b.handler().clone();
+        let mut node_b = handler.node();
+        node_b.enable_light_client(&path).unwrap();
+        node_b.handle_headers(connection_b.manager(), "127.0.0.1:8072",
+            blocks.iter().map(|block| block.header.clone()).collect()).unwrap();
+
+        // Each node leaves out a block, but between them they have both
+        let address = wallet.get_address();
+        node_b.light_client().unwrap().pending.insert(address, PendingAddress
+        {
+            waiting_on: HashSet::from(["x".to_owned(), "y".to_owned()]),
+            answers: 0,
+            blocks: BTreeMap::new(),
+            sent: Instant::now(),
+        });
+        node_b.handle_address_blocks("x", address, vec![blocks[0].clone()]).unwrap();
+        node_b.handle_address_blocks("z", address, Vec::new()).unwrap();
+        assert_eq!(node_b.light_client().unwrap().wallet_status(&address).unwrap(), None);
+
+        node_b.handle_address_blocks("y", address, vec![blocks[2].clone()]).unwrap();
+        let status = node_b.light_client().unwrap().wallet_status(&address).unwrap();
+        assert_eq!(status, Some(connection_a.handler().node().chain().get_wallet_status(&address)));
+
+        // Nodes can only ask about so many addresses at once
+        let mut node_a = connection_a.handler().node();
+        for _ in 0..MAX_ADDRESS_REQUESTS {
+            assert_eq!(node_a.allow_address_request("10.0.0.1:8000"), true);
+        }
+        assert_eq!(node_a.allow_address_request("10.0.0.1:8000"), false);
+        assert_eq!(node_a.allow_address_request("10.0.0.2:8000"), true);
+    }
+
+}
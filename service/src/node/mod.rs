@@ -5,31 +5,91 @@
  */
 
 pub mod packet_handler;
+pub mod site_cache;
+pub mod replication;
+pub mod storage_proof;
+pub mod light_client;
+pub mod sync;
+pub mod branches;
+use site_cache::SiteCache;
+use replication::Replication;
+use storage_proof::StorageProofs;
+use light_client::LightClient;
+use sync::BlockSync;
+use branches::{Branches, BranchAddResult};
 use crate::network::packet::Packet;
 use crate::network::client_manager::ClientManager;
-use crate::report::{Report, NodeReport};
+use crate::network::peer_score::Misbehaviour;
+use crate::network::handshake::{Handshake, Capabilities, DEFAULT_NETWORK_ID};
+use crate::report::{Report, NodeReport, StorageUsage};
 
 use libhyperchain::chain::{BlockChain, BlockChainAddResult};
 use libhyperchain::chain::branch::BlockChainCanMergeResult;
 use libhyperchain::block::Block;
+use libhyperchain::block::validate::BlockValidationResult;
 use libhyperchain::data_store::DataStore;
 use libhyperchain::data_store::data_unit::DataUnit;
+use libhyperchain::data_store::compression::CompressedChunk;
+use libhyperchain::data_store::page::content_hash;
+use libhyperchain::data_store::site_tree::SiteFile;
 use libhyperchain::transaction::Transaction;
 use libhyperchain::transaction::transfer::Transfer;
-use libhyperchain::transaction::page::Page;
+use libhyperchain::transaction::page::{Page, merkle_root_for_chunks};
+use libhyperchain::transaction::site_transfer::SiteTransfer;
+use libhyperchain::transaction::name::NameRegistration;
+use libhyperchain::merkle_tree::{MerkleProof, MerkleVersion};
+use libhyperchain::config::PAGE_MAX_CHUNKS;
 use libhyperchain::hash::Hash;
+use libhyperchain::error::ErrorMessage;
 use std::path::PathBuf;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use std::error::Error;
 
+/// How many recent blocks to remember from each node, to notice ones it
+/// sends again.
+const RECENT_BLOCKS_PER_NODE: usize = 32;
+
+/// How long to wait for a requested chunk before asking for it again.
+const CHUNK_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub struct Node
 {
     port: u16,
     chain: BlockChain,
     data_store: DataStore,
     report: Report,
-    branches: HashMap<String, Vec<Block>>,
+    site_cache: SiteCache,
+    replication: Replication,
+    storage_proofs: StorageProofs,
+    branches: Branches,
+
+    /// Hashes of the last few blocks each node has sent us
+    recent_blocks: HashMap<String, VecDeque<Hash>>,
+
+    /// Blocks asked for from each node, which it may have sent already
+    requested_blocks: HashMap<String, HashSet<u64>>,
+
+    /// When each node recently asked for an address's blocks
+    address_requests: HashMap<String, VecDeque<Instant>>,
+
+    /// Chunks asked for from other nodes that haven't arrived yet, with
+    /// when they were asked for
+    requested_chunks: HashMap<Hash, Instant>,
+
+    /// Chunks asked for by their place in a page, with the page's chunk
+    /// count and when they were asked for
+    requested_page_chunks: HashMap<(Hash, u32), (u32, Instant)>,
+
+    /// Set when only following headers
+    light_client: Option<LightClient>,
+
+    /// Set while catching up with a longer chain
+    sync: Option<BlockSync>,
+
+    /// Nodes on other networks aren't connected to
+    network_id: String,
 }
 
 impl Node
@@ -47,7 +107,18 @@ impl Node
             chain,
             data_store,
             report,
-            branches: HashMap::new()
+            site_cache: SiteCache::new(),
+            replication: Replication::open(&path.join("pinned.json")),
+            storage_proofs: StorageProofs::new(),
+            branches: Branches::new(),
+            recent_blocks: HashMap::new(),
+            requested_blocks: HashMap::new(),
+            address_requests: HashMap::new(),
+            requested_chunks: HashMap::new(),
+            requested_page_chunks: HashMap::new(),
+            light_client: None,
+            sync: None,
+            network_id: DEFAULT_NETWORK_ID.to_owned(),
         })))
     }
 
@@ -61,103 +132,185 @@ impl Node
         &mut self.data_store
     }
 
+    pub fn site_file(&mut self, site: &Hash, path: &str)
+        -> Result<Option<SiteFile>, Box<dyn Error>>
+    {
+        let tree = self.site_cache.site(&mut self.chain, &self.data_store, site)?;
+        Ok(tree.get(path).cloned())
+    }
+
     pub fn our_report(&self) -> Result<NodeReport, Box<dyn Error>>
     {
         Ok(NodeReport::new(self.data_store.report()?))
     }
 
-    pub fn storage_usage(&self) -> Result<HashMap<Hash, usize>, Box<dyn Error>>
+    /// What we tell other nodes about ourselves when they connect. Pruned
+    /// nodes can't give out old blocks, so don't say they have full blocks.
+    pub fn handshake(&mut self) -> Handshake
     {
-        let mut usage = self.report.storage_usage();
-        for chunk in self.data_store.report()?
+        let (top_or_none, capabilities) = match &mut self.light_client
         {
-            if !usage.contains_key(&chunk) {
-                usage.insert(chunk.clone(), 0);
-            }
-            *usage.get_mut(&chunk).unwrap() += 1;
-        }
+            Some(light_client) => (light_client.top(), Capabilities::NONE),
+            None if self.chain.has_all_blocks() => (self.chain.top().map(|block| block.header),
+                Capabilities::FULL_BLOCKS | Capabilities::PAGE_DATA | Capabilities::STORAGE_PROOFS),
+            None => (self.chain.top().map(|block| block.header),
+                Capabilities::PAGE_DATA | Capabilities::STORAGE_PROOFS),
+        };
 
-        Ok(usage)
+        let top = top_or_none
+            .and_then(|top| top.hash().ok().map(|hash| (top.block_id, hash)));
+        Handshake::new(self.port, &self.network_id, top, capabilities)
     }
 
-    fn try_insert_block_into_branch(branch: &mut Vec<Block>, block: Block) 
-        -> bool
+    pub fn set_network_id(&mut self, network_id: &str)
     {
-        // Is start of new branch
-        if branch.is_empty() 
-        {
-            branch.push(block);
-            return true;
-        }
+        self.network_id = network_id.to_owned();
+    }
 
-        // Can be added to the bottom
-        let bottom = branch.first().unwrap();
-        if bottom.validate_next(&block).is_ok() 
+    /// Request any of these chunks we don't have, and aren't already waiting
+    /// for, from the nodes that have reported storing them. Returns the
+    /// chunks no known node has.
+    pub fn request_chunks(&mut self, manager: &mut ClientManager, chunks: &[Hash])
+        -> Result<Vec<Hash>, Box<dyn Error>>
+    {
+        let mut unavailable = Vec::new();
+        for chunk in chunks
         {
-            branch.insert(0, block);
-            return true;
+            if self.data_store.has_chunk(chunk) || self.requested_chunks.contains_key(chunk) {
+                continue;
+            }
+
+            let nodes = self.report.nodes_storing(chunk);
+            if nodes.is_empty() 
+            {
+                unavailable.push(*chunk);
+                continue;
+            }
+
+            info!("[{}] Requesting chunk {}", self.port, chunk);
+            self.requested_chunks.insert(*chunk, Instant::now());
+            manager.send_to(Packet::ChunkRequest(*chunk), |addr| nodes.contains(addr))?;
         }
 
-        // Can be added to the top
-        let top = branch.last().unwrap();
-        if block.validate_next(top).is_ok() {
-            branch.push(block);
-            return true;
+        Ok(unavailable)
+    }
+
+    /// Request the chunks of a page we don't have from every connected node.
+    /// If we don't know which chunks make up the page yet, only its manifest
+    /// is requested, and the rest once it arrives and lists them.
+    pub fn request_page_chunks(&mut self, manager: &mut ClientManager,
+                               chunk_root: &Hash, chunk_count: u32)
+        -> Result<(), Box<dyn Error>>
+    {
+        if chunk_count == 0 || chunk_count > PAGE_MAX_CHUNKS {
+            return Err(ErrorMessage::new(&format!("Page {} has {} chunks", chunk_root, chunk_count)));
         }
 
-        // Already exists
-        let bottom_id = bottom.header.block_id;
-        let top_id = top.header.block_id;
-        if (bottom_id..=top_id).contains(&block.header.block_id) 
+        let indices = match self.data_store.chunk_list(chunk_root)
         {
-            let branch_index = (block.header.block_id - bottom_id) as usize;
-            let existing_block_in_branch = branch.get(branch_index).unwrap();
-            if &block == existing_block_in_branch {
-                return true;
+            Ok(hashes) =>
+            {
+                let data_store = &self.data_store;
+                hashes.iter()
+                    .enumerate()
+                    .filter(|(_, hash)| !data_store.has_chunk(hash))
+                    .map(|(index, _)| index as u32)
+                    .collect::<Vec<_>>()
+            },
+            Err(_) => vec![0],
+        };
+
+        for index in indices
+        {
+            if self.requested_page_chunks.contains_key(&(*chunk_root, index)) {
+                continue;
             }
+
+            debug!("[{}] Requesting chunk {} of page {}", self.port, index, chunk_root);
+            self.requested_page_chunks.insert((*chunk_root, index), (chunk_count, Instant::now()));
+            manager.send(Packet::PageChunkRequest(*chunk_root, index))?;
         }
 
-        false
+        Ok(())
     }
 
-    fn add_to_branch(&mut self, from: &str, block: Block)
-        -> Option<u64>
+    pub fn storage_usage(&self) -> Result<StorageUsage, Box<dyn Error>>
     {
-        if !self.branches.contains_key(from) {
-            self.branches.insert(from.to_owned(), Vec::new());
+        let data_store = &self.data_store;
+        let mut usage = self.report.storage_usage(|chunk| data_store.has_chunk(chunk));
+        for chunk in self.data_store.report()? {
+            *usage.holders.entry(chunk).or_insert(0) += 1;
         }
 
-        let mut branch = self.branches.remove(from).unwrap();
-        if !Self::try_insert_block_into_branch(&mut branch, block) {
-            return None;
-        }
-        
-        let bottom = branch.first().unwrap();
-        let bottom_id = bottom.header.block_id;
-        self.branches.insert(from.to_owned(), branch);
-
-        if bottom_id == 0 {
-            None
-        } else {
-            Some(bottom_id - 1)
-        }
+        Ok(usage)
     }
 
-    fn complete_branch(&mut self, from: &str) -> Result<(), Box<dyn Error>>
+    fn complete_branch(&mut self, manager: &mut ClientManager, from: &str)
+        -> Result<(), Box<dyn Error>>
     {
-        if !self.branches.contains_key(from) {
+        let branch_or_none = self.branches.take(from);
+        if branch_or_none.is_none() {
             return Ok(());
         }
 
-        let branch = self.branches.remove(from).unwrap();
-        if self.chain.can_merge_branch(&branch)? == BlockChainCanMergeResult::Ok
+        let branch = branch_or_none.unwrap();
+        match self.chain.can_merge_branch(&branch)?
         {
-            info!("[{}] Merge longer branch", self.port);
-            self.chain.merge_branch(branch);
+            BlockChainCanMergeResult::Ok =>
+            {
+                info!("[{}] Merge longer branch", self.port);
+                self.site_cache.invalidate_from(branch.first().unwrap().header.block_id);
+                self.chain.merge_branch(branch);
+            },
+
+            BlockChainCanMergeResult::Invalid(result) =>
+            {
+                warn!("[{}] Branch from {} is invalid: {}", self.port, from, result);
+                self.branches.penalise(from);
+                manager.report_misbehaviour(from, Misbehaviour::InvalidBlock);
+            },
+
+            _ => {},
         }
         Ok(())
     }
 
+    /// Remember this node sent us the block, returning true if it already
+    /// had recently.
+    fn record_recent_block(&mut self, from: &str, block: &Block)
+        -> Result<bool, Box<dyn Error>>
+    {
+        let hash = block.hash()?;
+        let recent = self.recent_blocks.entry(from.to_owned()).or_default();
+        if recent.contains(&hash) {
+            return Ok(true);
+        }
+
+        recent.push_back(hash);
+        if recent.len() > RECENT_BLOCKS_PER_NODE {
+            recent.pop_front();
+        }
+        Ok(false)
+    }
+
+    fn request_block(&mut self, manager: &mut ClientManager, from: &str, block_id: u64)
+        -> Result<(), Box<dyn Error>>
+    {
+        self.requested_blocks.entry(from.to_owned()).or_default().insert(block_id);
+        manager.send_to(Packet::BlockRequest(block_id), |x| x == from)?;
+        Ok(())
+    }
+
+    /// Whether we asked this node for the block, forgetting the request.
+    fn take_block_request(&mut self, from: &str, block: &Block) -> bool
+    {
+        match self.requested_blocks.get_mut(from)
+        {
+            Some(requested) => requested.remove(&block.header.block_id),
+            None => false,
+        }
+    }
+
     fn should_ignore_block(&mut self, from: &str, block: &Block)
         -> bool
     {
@@ -184,6 +337,10 @@ impl Node
                     block: Block) 
         -> Result<(), Box<dyn Error>>
     {
+        // Nodes are only expected not to relay the same block twice, not to
+        // keep track of what we asked for
+        let is_requested = self.take_block_request(from, &block);
+        let is_repeat = self.record_recent_block(from, &block)? && !is_requested;
         if self.should_ignore_block(from, &block) {
             return Ok(());
         }
@@ -202,21 +359,55 @@ impl Node
             {
                 info!("[{}] Invalid block {}", self.port, block.header.block_id);
 
-                // Add block to this nodes branch
-                let next_block = self.add_to_branch(from, block);
-                
-                // Request the next block. If there's no more, complete the branch
-                if next_block.is_some() {
-                    manager.send_to(Packet::BlockRequest(next_block.unwrap()), |x| x == from)?;
-                } else {
-                    self.complete_branch(from)?;
+                // We're behind, rather than this competing with our top, so
+                // catch up using the node's headers
+                if block.header.block_id > self.chain.next_top()
+                {
+                    if self.sync.is_none() {
+                        self.request_sync_headers(manager, from)?;
+                    }
+                    return Ok(());
+                }
+
+                // Only blocks with the work they claim are worth keeping
+                if self.branches.is_penalised(from) {
+                    return Ok(());
+                }
+                if block.header.validate_claimed_pow()? != BlockValidationResult::Ok
+                {
+                    self.branches.penalise(from);
+                    manager.report_misbehaviour(from, Misbehaviour::InvalidBlock);
+                    return Ok(());
+                }
+
+                // Add block to this nodes branch, and request the next block.
+                // If there's no more, complete the branch
+                match self.branches.add(from, block)
+                {
+                    BranchAddResult::Ok(Some(next_block)) =>
+                        self.request_block(manager, from, next_block)?,
+
+                    BranchAddResult::Ok(None) =>
+                        self.complete_branch(manager, from)?,
+
+                    BranchAddResult::DoesNotFit => {},
+                    BranchAddResult::TooLong =>
+                    {
+                        info!("[{}] Branch from {} is too long, syncing headers instead", self.port, from);
+                        if self.sync.is_none() {
+                            self.request_sync_headers(manager, from)?;
+                        }
+                    },
                 }
             },
 
             BlockChainAddResult::Duplicate => 
             {
                 debug!("[{}] Duplicate block {}", self.port, block.header.block_id);
-                self.complete_branch(from)?;
+                if is_repeat {
+                    manager.report_misbehaviour(from, Misbehaviour::DuplicateRelay);
+                }
+                self.complete_branch(manager, from)?;
             },
         }
 
@@ -254,23 +445,163 @@ impl Node
     }
 
     fn handle_page(&mut self, manager: &mut ClientManager, from: &str,
-                   page: Transaction<Page>, data: DataUnit)
+                   page: Transaction<Page>, chunks: Vec<CompressedChunk>)
         -> Result<(), Box<dyn Error>>
     {
         info!("Got page {:?}", page);
         
-        page.header.content.is_data_valid(&data)?;
+        let data = match DataUnit::from_compressed_chunks(&chunks)
+            .and_then(|data| page.header.content.is_data_valid(&data).map(|_| data))
+        {
+            Ok(data) => data,
+            Err(err) =>
+            {
+                manager.report_misbehaviour(from, Misbehaviour::InvalidData);
+                return Err(err);
+            },
+        };
         self.chain.push_page_queue(page.clone())?;
         self.data_store.store_data_unit(&data)?;
 
         manager.send(Packet::Report(None, self.our_report()?))?;
         manager.send_to(
-            Packet::Page(page, data),
+            Packet::Page(page, chunks),
+            |x| x != from)?;
+
+        Ok(())
+    }
+
+    fn handle_site_transfer(&mut self, manager: &mut ClientManager, from: &str,
+                            site_transfer: Transaction<SiteTransfer>)
+        -> Result<(), Box<dyn Error>>
+    {
+        info!("Got site transfer {:?}", site_transfer);
+
+        self.chain.push_site_transfer_queue(site_transfer.clone())?;
+        manager.send_to(
+            Packet::SiteTransfer(site_transfer), 
             |x| x != from)?;
 
         Ok(())
     }
 
+    fn handle_name_registration(&mut self, manager: &mut ClientManager, from: &str,
+                                name: Transaction<NameRegistration>)
+        -> Result<(), Box<dyn Error>>
+    {
+        info!("Got name registration {:?}", name);
+
+        self.chain.push_name_queue(name.clone())?;
+        manager.send_to(
+            Packet::NameRegistration(name), 
+            |x| x != from)?;
+
+        Ok(())
+    }
+
+    fn handle_chunk_request(&mut self, manager: &mut ClientManager, 
+                            from: &str, hash: Hash)
+        -> Result<(), Box<dyn Error>>
+    {
+        debug!("Got request for chunk {}", hash);
+
+        if self.data_store.has_chunk(&hash) 
+        {
+            let chunk = self.data_store.get_compressed(&hash)?;
+            manager.send_to(Packet::Chunk(hash, chunk), |x| x == from)?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_chunk(&mut self, manager: &mut ClientManager, from: &str,
+                    hash: Hash, chunk: CompressedChunk)
+        -> Result<(), Box<dyn Error>>
+    {
+        // NOTE: Only chunks we asked for are kept, and any other node that 
+        //       had it may answer first
+        if !self.requested_chunks.contains_key(&hash) {
+            return Ok(());
+        }
+
+        let is_valid = matches!(chunk.decompress(), Ok(data) if content_hash(&data) == hash);
+        if !is_valid
+        {
+            manager.report_misbehaviour(from, Misbehaviour::InvalidData);
+            return Err(ErrorMessage::new(&format!("Chunk from {} doesn't match its hash", from)));
+        }
+
+        info!("[{}] Got chunk {} from {}", self.port, hash, from);
+        self.requested_chunks.remove(&hash);
+        self.data_store.store_compressed(&hash, &chunk)?;
+        manager.send(Packet::Report(None, self.our_report()?))?;
+        Ok(())
+    }
+
+    fn handle_page_chunk_request(&mut self, manager: &mut ClientManager,
+                                 from: &str, chunk_root: Hash, index: u32)
+        -> Result<(), Box<dyn Error>>
+    {
+        debug!("Got request for chunk {} of page {}", index, chunk_root);
+
+        if let Ok((chunk, proof)) = self.data_store.page_chunk(&chunk_root, index) {
+            manager.send_to(Packet::PageChunk(chunk_root, index, chunk, proof), |x| x == from)?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_page_chunk(&mut self, manager: &mut ClientManager, from: &str,
+                         chunk_root: Hash, index: u32,
+                         chunk: CompressedChunk, proof: MerkleProof)
+        -> Result<(), Box<dyn Error>>
+    {
+        let chunk_count_or_none = self.requested_page_chunks.get(&(chunk_root, index));
+        if chunk_count_or_none.is_none() {
+            return Ok(());
+        }
+
+        // NOTE: The proof ties the chunk to the page's root, so it can be
+        //       checked without knowing any of the page's other chunks
+        let (chunk_count, _) = *chunk_count_or_none.unwrap();
+        let data = chunk.decompress()?;
+        let hash = content_hash(&data);
+        let is_in_page = proof.version == MerkleVersion::V2 &&
+            proof.verify_at(hash.data(), index as usize, chunk_count as usize, &chunk_root);
+        if !is_in_page
+        {
+            manager.report_misbehaviour(from, Misbehaviour::InvalidData);
+            return Err(ErrorMessage::new(&format!("Chunk from {} isn't part of page {}", from, chunk_root)));
+        }
+
+        info!("[{}] Got chunk {} of page {} from {}", self.port, index, chunk_root, from);
+        self.requested_page_chunks.remove(&(chunk_root, index));
+        self.data_store.store_compressed(&hash, &chunk)?;
+
+        // The first chunk is the manifest, which lists the rest
+        if index == 0
+        {
+            let hashes = self.data_store.manifest(&hash)?.chunk_hashes(hash);
+            if hashes.len() as u32 != chunk_count || merkle_root_for_chunks(&hashes) != chunk_root {
+                return Err(ErrorMessage::new(&format!("Manifest of page {} doesn't match its chunks", chunk_root)));
+            }
+
+            self.data_store.store_chunk_list(&hashes)?;
+            self.request_page_chunks(manager, &chunk_root, chunk_count)?;
+        }
+
+        manager.send(Packet::Report(None, self.our_report()?))?;
+        Ok(())
+    }
+
+    /// Forget requests that have gone unanswered for `timeout`, so the
+    /// chunks can be asked for again.
+    fn expire_requests_older_than(&mut self, timeout: Duration)
+    {
+        self.requested_chunks.retain(|_, requested| requested.elapsed() < timeout);
+        self.requested_page_chunks.retain(|_, (_, requested)| requested.elapsed() < timeout);
+    }
+
     fn handle_report(&mut self, manager: &mut ClientManager, 
                      from: &str, report: NodeReport)
         -> Result<(), Box<dyn Error>>
@@ -285,13 +616,29 @@ impl Node
         Ok(())
     }
 
-    fn update_reports(&mut self, _manager: &mut ClientManager)
+    fn update_reports(&mut self, manager: &mut ClientManager)
     {
         for address in self.report.update()
         {
             info!("Report for {} expired", address);
             // TODO: Request new reports
         }
+
+        if let Err(err) = self.challenge_peers(manager) {
+            warn!("Unable to challenge peers: {}", err);
+        }
+
+        if let Err(err) = self.update_sync(manager) {
+            warn!("Unable to update sync: {}", err);
+        }
+        self.branches.expire();
+        self.expire_requests_older_than(CHUNK_REQUEST_TIMEOUT);
+        self.update_light_client();
+
+        let connected_nodes = manager.connected_nodes();
+        self.recent_blocks.retain(|address, _| connected_nodes.contains(address));
+        self.requested_blocks.retain(|address, _| connected_nodes.contains(address));
+        self.address_requests.retain(|address, _| connected_nodes.contains(address));
     }
 
 }
@@ -354,6 +701,83 @@ pub mod tests
         block
     }
 
+    #[test]
+    fn test_chunk_request_expiry()
+    {
+        let path = std::env::temp_dir().join(format!("{}chunk_requests", rand::random::<u32>()));
+        let node = Node::new(8095, &path).unwrap();
+        let mut node = node.lock().unwrap();
+
+        let chunk = Hash::from(&[1u8; 32]);
+        node.requested_chunks.insert(chunk, Instant::now());
+        node.requested_page_chunks.insert((chunk, 0), (1, Instant::now()));
+        node.expire_requests_older_than(CHUNK_REQUEST_TIMEOUT);
+        assert_eq!(node.requested_chunks.contains_key(&chunk), true);
+        assert_eq!(node.requested_page_chunks.contains_key(&(chunk, 0)), true);
+
+        // Unanswered requests are forgotten, so they can be made again
+        node.expire_requests_older_than(Duration::ZERO);
+        assert_eq!(node.requested_chunks.is_empty(), true);
+        assert_eq!(node.requested_page_chunks.is_empty(), true);
+
+        let _ = std::fs::remove_dir_all(path);
+    }
+
+    #[test]
+    fn test_page_chunk_requests()
+    {
+        let mut connection = create_node(8096);
+        let handler = connection.handler().clone();
+        let mut node = handler.node();
+
+        // Only the manifest is asked for until we know which chunks the page has
+        let chunk_root = Hash::from(&[1u8; 32]);
+        node.request_page_chunks(connection.manager(), &chunk_root, 100).unwrap();
+        assert_eq!(node.requested_page_chunks.keys().collect::<Vec<_>>(), vec![&(chunk_root, 0)]);
+
+        let huge_root = Hash::from(&[2u8; 32]);
+        assert!(node.request_page_chunks(connection.manager(), &huge_root, PAGE_MAX_CHUNKS + 1).is_err());
+        assert_eq!(node.requested_page_chunks.contains_key(&(huge_root, 0)), false);
+    }
+
+    #[test]
+    fn test_requested_duplicate_blocks()
+    {
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let mut connection = create_node(8098);
+        let block = mine_block(&mut connection, &wallet);
+        let handler = connection.handler().clone();
+        let mut node = handler.node();
+
+        // Sending a block we asked for again isn't relaying it twice
+        let peer = "10.0.0.1:8000";
+        for _ in 0..25
+        {
+            node.request_block(connection.manager(), peer, 0).unwrap();
+            node.handle_block(connection.manager(), peer, block.clone()).unwrap();
+        }
+        assert_eq!(connection.manager().is_banned(peer), false);
+
+        for _ in 0..25 {
+            node.handle_block(connection.manager(), peer, block.clone()).unwrap();
+        }
+        assert_eq!(connection.manager().is_banned(peer), true);
+    }
+
+    #[test]
+    fn test_pruned_node_capabilities()
+    {
+        let connection = create_node(8099);
+        let handler = connection.handler().clone();
+        let mut node = handler.node();
+        assert_eq!(node.handshake().capabilities.contains(Capabilities::FULL_BLOCKS), true);
+
+        node.chain().set_prune_depth(Some(1));
+        let capabilities = node.handshake().capabilities;
+        assert_eq!(capabilities.contains(Capabilities::FULL_BLOCKS), false);
+        assert_eq!(capabilities.contains(Capabilities::PAGE_DATA | Capabilities::STORAGE_PROOFS), true);
+    }
+
     #[test]
     fn test_node_branched_chain()
     {
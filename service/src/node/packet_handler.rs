@@ -7,6 +7,7 @@
 use super::Node;
 use crate::network::packet::{Packet, PacketHandler};
 use crate::network::client_manager::ClientManager;
+use crate::network::handshake::Handshake;
 
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::error::Error;
@@ -44,18 +45,25 @@ impl PacketHandler for NodePacketHandler
         let mut node = self.node.lock().unwrap();
         match packet
         {
-            Packet::OnConnected => 
+            Packet::OnConnected if node.light_client.is_some() =>
             {
-                if let Some(top) = node.chain.top()
-                {
-                    manager.send_to(Packet::Block(top.clone()),
-                        |addr| addr == from)?;
+                if node.has_unknown_top(manager, from)? {
+                    node.request_sync_headers(manager, from)?;
                 }
+            },
 
+            Packet::OnConnected => 
+            {
+                if node.has_unknown_top(manager, from)? {
+                    node.request_sync_headers(manager, from)?;
+                }
                 manager.send_to(Packet::Report(None, node.our_report()?),
                     |addr| addr == from)?;
             },
 
+            Packet::Block(block) if node.light_client.is_some() =>
+                node.handle_light_block(manager, from, block)?,
+
             Packet::Block(block) => 
                 node.handle_block(manager, from, block)?,
 
@@ -65,8 +73,14 @@ impl PacketHandler for NodePacketHandler
             Packet::Transfer(transfer) =>
                 node.handle_transfer(manager, from, transfer)?,
 
-            Packet::Page(page, data) =>
-                node.handle_page(manager, from, page, data)?,
+            Packet::Page(page, chunks) =>
+                node.handle_page(manager, from, page, chunks)?,
+
+            Packet::SiteTransfer(site_transfer) =>
+                node.handle_site_transfer(manager, from, site_transfer)?,
+
+            Packet::NameRegistration(name) =>
+                node.handle_name_registration(manager, from, name)?,
 
             Packet::Report(address, report) =>
                 match address
@@ -75,6 +89,45 @@ impl PacketHandler for NodePacketHandler
                     None => node.handle_report(manager, from, report)?,
                 },
 
+            Packet::ChunkRequest(hash) =>
+                node.handle_chunk_request(manager, from, hash)?,
+
+            Packet::Chunk(hash, chunk) =>
+                node.handle_chunk(manager, from, hash, chunk)?,
+
+            Packet::PageChunkRequest(chunk_root, index) =>
+                node.handle_page_chunk_request(manager, from, chunk_root, index)?,
+
+            Packet::PageChunk(chunk_root, index, chunk, proof) =>
+                node.handle_page_chunk(manager, from, chunk_root, index, chunk, proof)?,
+
+            Packet::StorageChallenge(challenge) =>
+                node.handle_storage_challenge(manager, from, challenge)?,
+
+            Packet::StorageProof(nonce, proof) =>
+                node.handle_storage_proof(from, nonce, proof),
+
+            Packet::GetHeaders(locator) =>
+                node.handle_get_headers(manager, from, locator)?,
+
+            Packet::Headers(headers) if node.light_client.is_some() =>
+                node.handle_headers(manager, from, headers)?,
+
+            Packet::Headers(headers) =>
+                node.handle_sync_headers(manager, from, headers)?,
+
+            Packet::GetBlocks(start, end) =>
+                node.handle_get_blocks(manager, from, start, end)?,
+
+            Packet::Blocks(start, blocks) =>
+                node.handle_blocks(manager, from, start, blocks)?,
+
+            Packet::AddressBlocksRequest(address) =>
+                node.handle_address_blocks_request(manager, from, address)?,
+
+            Packet::AddressBlocks(address, blocks) =>
+                node.handle_address_blocks(from, address, blocks)?,
+
             Packet::Ping(time_sent) =>
                 manager.report_ping_time(from, time_sent),
         }
@@ -88,5 +141,10 @@ impl PacketHandler for NodePacketHandler
         node.update_reports(manager);
     }
 
+    fn handshake(&self) -> Handshake
+    {
+        self.node.lock().unwrap().handshake()
+    }
+
 }
 
@@ -0,0 +1,317 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::Node;
+use crate::network::client_manager::ClientManager;
+use crate::network::packet::Packet;
+use crate::report::StorageUsage;
+
+use libhyperchain::service::command::{ReplicationAction, ReplicationDecision};
+use libhyperchain::config::{REPLICATION_FACTOR, DISK_QUOTA};
+use libhyperchain::block::current_timestamp;
+use libhyperchain::hash::Hash;
+use rand::Rng;
+use std::collections::{HashSet, VecDeque};
+use std::cmp::Reverse;
+use std::path::PathBuf;
+use std::fs::File;
+use std::error::Error;
+
+const MAX_DECISIONS: usize = 32;
+const MAX_FETCHES_PER_ROUND: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplicationConfig
+{
+    /// Number of nodes each chunk should be stored on
+    pub target: usize,
+
+    /// Bytes of chunks this node is willing to store
+    pub disk_quota: u64,
+}
+
+impl Default for ReplicationConfig
+{
+
+    fn default() -> Self
+    {
+        Self
+        {
+            target: REPLICATION_FACTOR,
+            disk_quota: DISK_QUOTA,
+        }
+    }
+
+}
+
+pub struct Replication
+{
+    path: PathBuf,
+    pub config: ReplicationConfig,
+
+    /// Chunks published through this node, which are never dropped
+    pinned: HashSet<Hash>,
+    decisions: VecDeque<ReplicationDecision>,
+}
+
+impl Replication
+{
+
+    pub fn open(path: &PathBuf) -> Self
+    {
+        let pinned = File::open(path).ok()
+            .and_then(|file| serde_json::from_reader(file).ok())
+            .unwrap_or_default();
+
+        Self
+        {
+            path: path.to_owned(),
+            config: ReplicationConfig::default(),
+            pinned,
+            decisions: VecDeque::new(),
+        }
+    }
+
+    fn flush(&self) -> Result<(), Box<dyn Error>>
+    {
+        let file = File::create(&self.path)?;
+        serde_json::to_writer_pretty(file, &self.pinned)?;
+        Ok(())
+    }
+
+    pub fn pin(&mut self, chunks: &[Hash])
+    {
+        self.pinned.extend(chunks.iter().copied());
+        if let Err(err) = self.flush() {
+            warn!("Could not save pinned chunks: {}", err);
+        }
+    }
+
+    pub fn decisions(&self) -> Vec<ReplicationDecision>
+    {
+        self.decisions.iter().cloned().collect()
+    }
+
+    fn record(&mut self, chunk: Hash, action: ReplicationAction, holders: usize)
+    {
+        info!("Replication: {:?} chunk {} (stored on {} nodes, target {})",
+            action, chunk, holders, self.config.target);
+
+        self.decisions.push_front(ReplicationDecision
+        {
+            chunk,
+            action,
+            holders,
+            timestamp: current_timestamp(),
+        });
+        self.decisions.truncate(MAX_DECISIONS);
+    }
+
+    /// Decide which chunks this node should fetch or drop, given how many nodes
+    /// store each one (including us) and how many nodes we know of. Chunks
+    /// only claimed by nodes we haven't checked are treated as stored nowhere.
+    ///
+    /// Every node makes this decision independently, so rather than all of them
+    /// acting on the same chunk, each only does so with the chance that gets the
+    /// network to the target on average. Anything that overshoots is corrected
+    /// in later rounds.
+    pub fn plan<S, C>(&self, usage: &StorageUsage, stored: &HashSet<Hash>,
+                      node_count: usize, mut disk_usage: u64,
+                      chunk_size: S, mut chance: C)
+            -> Vec<(Hash, ReplicationAction)>
+        where S: Fn(&Hash) -> u64,
+              C: FnMut(f64) -> bool
+    {
+        let (config, pinned) = (&self.config, &self.pinned);
+        let mut decisions = Vec::new();
+        let holders = |chunk: &Hash| usage.holders.get(chunk).copied().unwrap_or(1);
+
+        // Over quota, so drop whatever other nodes also have, starting with
+        // the most replicated
+        if disk_usage > config.disk_quota
+        {
+            let mut droppable = stored.iter()
+                .filter(|chunk| !pinned.contains(chunk) && holders(chunk) > 1)
+                .collect::<Vec<_>>();
+            droppable.sort_by_key(|chunk| Reverse(holders(chunk)));
+
+            for chunk in droppable
+            {
+                if disk_usage <= config.disk_quota {
+                    break;
+                }
+
+                disk_usage = disk_usage.saturating_sub(chunk_size(chunk));
+                decisions.push((*chunk, ReplicationAction::Drop));
+            }
+        }
+
+        let mut fetches = 0;
+        let unchecked = usage.unchecked_only().map(|chunk| (chunk, &0));
+        for (chunk, holders) in usage.holders.iter().chain(unchecked)
+        {
+            let (chunk, holders) = (*chunk, *holders);
+            if stored.contains(&chunk)
+            {
+                if holders <= config.target || pinned.contains(&chunk) {
+                    continue;
+                }
+
+                let is_dropped = decisions.iter().any(|(dropped, _)| dropped == &chunk);
+                if !is_dropped && chance((holders - config.target) as f64 / holders as f64) {
+                    decisions.push((chunk, ReplicationAction::Drop));
+                }
+            }
+            else
+            {
+                if holders >= config.target || fetches >= MAX_FETCHES_PER_ROUND || disk_usage >= config.disk_quota {
+                    continue;
+                }
+
+                let candidates = std::cmp::max(node_count.saturating_sub(holders), 1);
+                if chance((config.target - holders) as f64 / candidates as f64)
+                {
+                    decisions.push((chunk, ReplicationAction::Fetch));
+                    fetches += 1;
+                }
+            }
+        }
+
+        decisions
+    }
+
+}
+
+impl Node
+{
+
+    pub fn replication(&mut self) -> &mut Replication
+    {
+        &mut self.replication
+    }
+
+    /// Run one round of fetching under replicated chunks and dropping over
+    /// replicated ones.
+    pub fn replicate(&mut self, manager: &mut ClientManager)
+        -> Result<(), Box<dyn Error>>
+    {
+        let usage = self.storage_usage()?;
+        let stored = self.data_store.report()?;
+        let node_count = self.report.node_count() + 1;
+        let disk_usage = self.data_store.disk_usage()?;
+
+        let data_store = &self.data_store;
+        let mut rng = rand::thread_rng();
+        let decisions = self.replication.plan(&usage, &stored, node_count, disk_usage,
+            |chunk| data_store.chunk_size(chunk).unwrap_or(0),
+            |probability| rng.gen_bool(probability.min(1.0)));
+
+        let mut fetches = Vec::new();
+        let mut has_dropped = false;
+        for (chunk, action) in decisions
+        {
+            self.replication.record(chunk, action, usage.holders.get(&chunk).copied().unwrap_or(0));
+            match action
+            {
+                ReplicationAction::Fetch => fetches.push(chunk),
+                ReplicationAction::Drop =>
+                {
+                    self.data_store.remove(&chunk)?;
+                    has_dropped = true;
+                },
+            }
+        }
+
+        if !fetches.is_empty() {
+            self.request_chunks(manager, &fetches)?;
+        }
+
+        // Let the other nodes know we no longer have these
+        if has_dropped {
+            manager.send(Packet::Report(None, self.our_report()?))?;
+        }
+
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+    use libhyperchain::config::HASH_LEN;
+    use std::collections::HashMap;
+
+    fn chunk(n: u8) -> Hash
+    {
+        Hash::from(&[n; HASH_LEN])
+    }
+
+    #[test]
+    fn test_replication_plan()
+    {
+        let mut replication = Replication::open(&std::env::temp_dir().join("pinned.json"));
+        replication.config = ReplicationConfig { target: 2, disk_quota: 100 };
+        replication.pinned = [chunk(3)].iter().copied().collect();
+        let holders = [(chunk(0), 1), (chunk(1), 2), (chunk(2), 4), (chunk(3), 4), (chunk(4), 1)]
+            .iter().copied().collect::<HashMap<_, _>>();
+        let usage = StorageUsage { holders, unchecked: HashMap::new() };
+        let stored = [chunk(2), chunk(3), chunk(4)].iter().copied().collect::<HashSet<_>>();
+        let sorted = |mut decisions: Vec<(Hash, ReplicationAction)>|
+        {
+            decisions.sort_by_key(|(chunk, _)| chunk.data().to_vec());
+            decisions
+        };
+
+        // Fetch what's under replicated, and drop what's over unless pinned
+        let decisions = replication.plan(&usage, &stored, 5, 0, |_| 10, |_| true);
+        assert_eq!(sorted(decisions), vec![
+            (chunk(0), ReplicationAction::Fetch),
+            (chunk(2), ReplicationAction::Drop),
+        ]);
+
+        // Nothing happens if it's left up to chance
+        let decisions = replication.plan(&usage, &stored, 5, 0, |_| 10, |_| false);
+        assert_eq!(decisions, Vec::new());
+
+        // Over quota, so nothing is fetched and only the pinned chunk and the
+        // last copy of a chunk are kept
+        let decisions = replication.plan(&usage, &stored, 5, 120, |_| 50, |_| false);
+        assert_eq!(decisions, vec![(chunk(2), ReplicationAction::Drop)]);
+    }
+
+    #[test]
+    fn test_replication_plan_unstored()
+    {
+        let mut replication = Replication::open(&std::env::temp_dir().join("pinned.json"));
+        replication.config = ReplicationConfig { target: 2, disk_quota: 100 };
+        let stored = HashSet::new();
+
+        // Claims from nodes that have passed challenges are counted, so
+        // chunks we don't have but are stored enough aren't fetched
+        let holders = [(chunk(0), 2), (chunk(1), 1)].iter().copied().collect();
+        let unchecked = [(chunk(1), 3), (chunk(2), 3)].iter().copied().collect();
+        let usage = StorageUsage { holders, unchecked };
+        let decisions = replication.plan(&usage, &stored, 5, 0, |_| 10, |_| true);
+        let mut fetched = decisions.iter()
+            .map(|(chunk, action)| { assert_eq!(action, &ReplicationAction::Fetch); chunk.data().to_vec() })
+            .collect::<Vec<_>>();
+        fetched.sort();
+        assert_eq!(fetched, vec![chunk(1).data().to_vec(), chunk(2).data().to_vec()]);
+
+        // Once fetched one copy too many is dropped, then it's not fetched
+        // again as the other holders are still counted
+        let stored = [chunk(0)].iter().copied().collect::<HashSet<_>>();
+        let usage = StorageUsage { holders: [(chunk(0), 3)].iter().copied().collect(), unchecked: HashMap::new() };
+        assert_eq!(replication.plan(&usage, &stored, 5, 0, |_| 10, |_| true), vec![(chunk(0), ReplicationAction::Drop)]);
+
+        let usage = StorageUsage { holders: [(chunk(0), 2)].iter().copied().collect(), unchecked: HashMap::new() };
+        assert_eq!(replication.plan(&usage, &HashSet::new(), 5, 0, |_| 10, |_| true), Vec::new());
+    }
+
+}
@@ -0,0 +1,257 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use libhyperchain::chain::BlockChain;
+use libhyperchain::data_store::DataStore;
+use libhyperchain::data_store::site_tree::SiteTree;
+use libhyperchain::transaction::Transaction;
+use libhyperchain::transaction::page::Page;
+use libhyperchain::hash::Hash;
+use std::collections::HashMap;
+use std::error::Error;
+
+const MAX_CACHED_SITES: usize = 64;
+
+struct CachedSite
+{
+    tree: SiteTree,
+
+    /// The last block applied to the tree, or none if the site had no
+    /// updates yet when it was built
+    block: Option<(u64, Hash)>,
+
+    /// When the site was last requested, counted in requests
+    last_used: u64,
+}
+
+/// Current file trees of sites that have been requested, so each request
+/// doesn't have to replay every page update. Only the most recently used
+/// sites are kept.
+pub struct SiteCache
+{
+    sites: HashMap<Hash, CachedSite>,
+    requests: u64,
+}
+
+impl SiteCache
+{
+
+    pub fn new() -> Self
+    {
+        Self
+        {
+            sites: HashMap::new(),
+            requests: 0,
+        }
+    }
+
+    fn insert(&mut self, site: Hash, tree: SiteTree, block: Option<(u64, Hash)>)
+    {
+        if self.sites.len() >= MAX_CACHED_SITES
+        {
+            let least_used_or_none = self.sites.iter()
+                .min_by_key(|(_, cached)| cached.last_used)
+                .map(|(site, _)| *site);
+
+            if let Some(least_used) = least_used_or_none {
+                self.sites.remove(&least_used);
+            }
+        }
+
+        self.requests += 1;
+        self.sites.insert(site, CachedSite { tree, block, last_used: self.requests });
+    }
+
+    fn is_on_chain(chain: &mut BlockChain, block: &Option<(u64, Hash)>) -> bool
+    {
+        match block
+        {
+            Some((block_id, hash)) =>
+            {
+                match chain.block(*block_id)
+                {
+                    Some(block) => block.hash().ok().as_ref() == Some(hash),
+
+                    // NOTE: Pruned blocks are too old to be forked away
+                    None => chain.is_pruned(*block_id),
+                }
+            },
+
+            None => true,
+        }
+    }
+
+    fn apply_updates(tree: &mut SiteTree, data_store: &DataStore,
+                     site: &Hash, updates: &[Transaction<Page>])
+        -> Result<(), Box<dyn Error>>
+    {
+        // NOTE: Load all the data first, so a missing chunk leaves the
+        //       cached tree as it was
+        let mut data_units = Vec::new();
+        for update in updates {
+            data_units.push((data_store.get_data_unit(update)?, update.header.content.chunk_root));
+        }
+
+        for (data_unit, chunk_root) in &data_units
+        {
+            if let Err(err) = tree.apply(data_unit, chunk_root) {
+                warn!("[{}] Skipping page update: {}", site, err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Bring the cached tree for this site up to date with the chain,
+    /// rebuilding it if the block it was built on is no longer there.
+    pub fn site(&mut self, chain: &mut BlockChain, data_store: &DataStore,
+                site: &Hash)
+        -> Result<&SiteTree, Box<dyn Error>>
+    {
+        let cached_or_none = self.sites.remove(site)
+            .filter(|cached| Self::is_on_chain(chain, &cached.block));
+
+        let (mut tree, updates) = match cached_or_none
+        {
+            Some(cached) =>
+            {
+                let updates = match cached.block
+                {
+                    Some((block_id, _)) => chain.get_page_updates_after(site, block_id),
+                    None => chain.get_page_updates(site),
+                };
+                (cached.tree, updates)
+            },
+
+            None =>
+            {
+                info!("[{}] Materialising site", site);
+                (SiteTree::new(), chain.get_page_updates(site))
+            },
+        };
+
+        Self::apply_updates(&mut tree, data_store, site, &updates)?;
+        let block = match chain.top()
+        {
+            Some(top) => Some((top.header.block_id, top.hash()?)),
+            None => None,
+        };
+
+        self.insert(*site, tree, block);
+        Ok(&self.sites[site].tree)
+    }
+
+    /// Drop any sites built on blocks from `block_id` onwards, as they're
+    /// being replaced by another branch.
+    pub fn invalidate_from(&mut self, block_id: u64)
+    {
+        self.sites.retain(|_, cached|
+        {
+            match cached.block
+            {
+                Some((cached_block_id, _)) => cached_block_id < block_id,
+                None => true,
+            }
+        });
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+    use crate::node::Node;
+    use libhyperchain::wallet::Wallet;
+    use libhyperchain::wallet::private_wallet::PrivateWallet;
+    use libhyperchain::block::Block;
+    use libhyperchain::chain::branch::BlockChainCanMergeResult;
+    use libhyperchain::data_store::data_unit::DataUnit;
+    use libhyperchain::data_store::page::{CreatePageData, RenamePageData};
+    use libhyperchain::miner;
+
+    fn add_block(node: &mut Node, wallet: &PrivateWallet, data_unit: Option<DataUnit>)
+        -> Block
+    {
+        let mut pages = Vec::new();
+        if let Some(data_unit) = data_unit
+        {
+            pages.push(node.chain().new_page(wallet, &data_unit, 1.0).unwrap());
+            node.data_store().store_data_unit(&data_unit).unwrap();
+        }
+
+        let block = miner::mine_block(Block::new(node.chain(), wallet, 
+            Vec::new(), pages, Vec::new(), Vec::new()).unwrap());
+        node.chain().add(&block).unwrap();
+        block
+    }
+
+    #[test]
+    fn test_site_cache()
+    {
+        let time = libhyperchain::block::current_timestamp();
+        let path_a = std::env::temp_dir().join(format!("{}site_cache_a", time));
+        let path_b = std::env::temp_dir().join(format!("{}site_cache_b", time));
+        let node_a = Node::new(0, &path_a).unwrap();
+        let node_b = Node::new(0, &path_b).unwrap();
+        let mut node_a = node_a.lock().unwrap();
+        let mut node_b = node_b.lock().unwrap();
+
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let site = wallet.get_address();
+
+        let create = DataUnit::CreatePage(CreatePageData::new("index.html".to_owned(), b"a".to_vec()));
+        let block_a = add_block(&mut node_a, &wallet, None);
+        let block_b = add_block(&mut node_a, &wallet, Some(create));
+        assert_eq!(node_a.site_file(&site, "index.html").unwrap().unwrap().data, b"a".to_vec());
+
+        // New updates are applied on top of the cached tree
+        let rename = DataUnit::RenamePage(RenamePageData { from: "index.html".to_owned(), to: "home.html".to_owned() });
+        add_block(&mut node_a, &wallet, Some(rename));
+        assert_eq!(node_a.site_file(&site, "index.html").unwrap(), None);
+        assert_eq!(node_a.site_file(&site, "home.html").unwrap().unwrap().data, b"a".to_vec());
+
+        // Reorg away the rename, without telling the cache
+        node_b.chain().add(&block_a).unwrap();
+        node_b.chain().add(&block_b).unwrap();
+        let branch = vec![
+            add_block(&mut node_b, &wallet, None),
+            add_block(&mut node_b, &wallet, None),
+        ];
+        assert_eq!(node_a.chain().can_merge_branch(&branch).unwrap(), BlockChainCanMergeResult::Ok);
+        node_a.chain().merge_branch(branch);
+        assert_eq!(node_a.site_file(&site, "home.html").unwrap(), None);
+        assert_eq!(node_a.site_file(&site, "index.html").unwrap().unwrap().data, b"a".to_vec());
+
+        let _ = std::fs::remove_dir_all(path_a);
+        let _ = std::fs::remove_dir_all(path_b);
+    }
+
+    #[test]
+    fn test_site_cache_eviction()
+    {
+        let mut cache = SiteCache::new();
+        let sites = (0..=MAX_CACHED_SITES)
+            .map(|i| Hash::from(&(i as u64).to_le_bytes()))
+            .collect::<Vec<_>>();
+
+        for site in &sites[..MAX_CACHED_SITES] {
+            cache.insert(*site, SiteTree::new(), None);
+        }
+
+        // Using the first site again makes the second the least recently used
+        let first = cache.sites.remove(&sites[0]).unwrap();
+        cache.insert(sites[0], first.tree, first.block);
+        cache.insert(sites[MAX_CACHED_SITES], SiteTree::new(), None);
+
+        assert_eq!(cache.sites.len(), MAX_CACHED_SITES);
+        assert_eq!(cache.sites.contains_key(&sites[0]), true);
+        assert_eq!(cache.sites.contains_key(&sites[1]), false);
+        assert_eq!(cache.sites.contains_key(&sites[MAX_CACHED_SITES]), true);
+    }
+
+}
@@ -0,0 +1,206 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::Node;
+use crate::network::client_manager::ClientManager;
+use crate::network::packet::Packet;
+use crate::network::handshake::Capabilities;
+
+use libhyperchain::data_store::proof::StorageChallenge;
+use libhyperchain::hash::Hash;
+use rand::seq::IteratorRandom;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use std::error::Error;
+
+const CHALLENGE_INTERVAL: Duration = Duration::from_secs(60);
+const CHALLENGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct PendingChallenge
+{
+    address: String,
+    expected: Hash,
+    sent: Instant,
+}
+
+/// Challenges sent to other nodes to check they store what they report.
+pub struct StorageProofs
+{
+    pending: HashMap<u64, PendingChallenge>,
+    last_challenged: HashMap<String, Instant>,
+}
+
+impl StorageProofs
+{
+
+    pub fn new() -> Self
+    {
+        Self
+        {
+            pending: HashMap::new(),
+            last_challenged: HashMap::new(),
+        }
+    }
+
+    fn is_due(&self, address: &str) -> bool
+    {
+        if self.pending.values().any(|x| x.address == address) {
+            return false;
+        }
+
+        match self.last_challenged.get(address)
+        {
+            Some(last_challenged) => last_challenged.elapsed() >= CHALLENGE_INTERVAL,
+            None => true,
+        }
+    }
+
+    fn expire(&mut self) -> Vec<String>
+    {
+        let expired = self.pending.iter()
+            .filter(|(_, challenge)| challenge.sent.elapsed() >= CHALLENGE_TIMEOUT)
+            .map(|(nonce, _)| *nonce)
+            .collect::<Vec<_>>();
+
+        expired.iter()
+            .filter_map(|nonce| self.pending.remove(nonce))
+            .map(|challenge| challenge.address)
+            .collect()
+    }
+
+}
+
+impl Node
+{
+
+    /// Send a challenge to each connected node that's due one, for a random
+    /// chunk it reported that we also have. Unanswered challenges count as
+    /// failed.
+    pub fn challenge_peers(&mut self, manager: &mut ClientManager)
+        -> Result<(), Box<dyn Error>>
+    {
+        for address in self.storage_proofs.expire()
+        {
+            warn!("Node {} didn't answer storage challenge", address);
+            self.report.record_proof(&address, false);
+        }
+
+        for address in manager.connected_nodes_with(Capabilities::STORAGE_PROOFS)
+        {
+            if !self.storage_proofs.is_due(&address) {
+                continue;
+            }
+
+            let report_or_none = self.report.node_report(&address);
+            if report_or_none.is_none() {
+                continue;
+            }
+
+            let data_store = &self.data_store;
+            let chunk_or_none = report_or_none.unwrap().chunks_stored
+                .iter()
+                .filter(|chunk| data_store.has_chunk(chunk))
+                .choose(&mut rand::thread_rng());
+            if chunk_or_none.is_none() {
+                continue;
+            }
+
+            let chunk = *chunk_or_none.unwrap();
+            let data = self.data_store.get(&chunk)?;
+            let challenge = StorageChallenge::random(chunk, data.len());
+            let expected_or_none = challenge.prove(&data);
+            if expected_or_none.is_none() {
+                continue;
+            }
+
+            debug!("Challenging {} to prove it stores {}", address, chunk);
+            self.storage_proofs.last_challenged.insert(address.clone(), Instant::now());
+            self.storage_proofs.pending.insert(challenge.nonce, PendingChallenge
+            {
+                address: address.clone(),
+                expected: expected_or_none.unwrap(),
+                sent: Instant::now(),
+            });
+
+            manager.send_to(Packet::StorageChallenge(challenge), |x| x == address)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn handle_storage_challenge(&mut self, manager: &mut ClientManager,
+                                    from: &str, challenge: StorageChallenge)
+        -> Result<(), Box<dyn Error>>
+    {
+        let proof = self.data_store.get(&challenge.chunk).ok()
+            .and_then(|data| challenge.prove(&data));
+
+        manager.send_to(Packet::StorageProof(challenge.nonce, proof), |x| x == from)?;
+        Ok(())
+    }
+
+    pub fn handle_storage_proof(&mut self, from: &str, nonce: u64, proof: Option<Hash>)
+    {
+        // NOTE: Only the node a challenge was sent to can answer it
+        let is_expected = matches!(self.storage_proofs.pending.get(&nonce),
+            Some(challenge) if challenge.address == from);
+        if !is_expected {
+            return;
+        }
+
+        let challenge = self.storage_proofs.pending.remove(&nonce).unwrap();
+        let passed = proof == Some(challenge.expected);
+        if !passed {
+            warn!("Node {} failed storage challenge", from);
+        }
+
+        self.report.record_proof(from, passed);
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use crate::node::tests::create_node;
+    use crate::report::NodeReport;
+    use crate::network::packet::Packet;
+    use libhyperchain::data_store::page::content_hash;
+    use std::collections::HashSet;
+    use std::iter::FromIterator;
+    use std::time::Duration;
+
+    #[test]
+    fn test_storage_proof()
+    {
+        let _ = pretty_env_logger::try_init();
+
+        let connection_a = create_node(8060);
+        let mut connection_b = create_node(8061);
+        connection_b.manager().register_node("127.0.0.1:8060");
+
+        let data = b"<p>Stored</p>".repeat(100);
+        let chunk = content_hash(&data);
+        connection_a.handler().node().data_store().store(&chunk, &data).unwrap();
+
+        // B claims to store the chunk without having it
+        while connection_b.manager().connected_nodes().is_empty() {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        let false_report = NodeReport::new(HashSet::from_iter([chunk]));
+        connection_b.manager().send(Packet::Report(None, false_report)).unwrap();
+
+        // A challenges B, and stops counting B's copy once it fails
+        let history = || connection_a.handler().node().report.proof_history("127.0.0.1:8061");
+        while history().failed == 0 {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert_eq!(history().passed, 0);
+        assert_eq!(connection_a.handler().node().storage_usage().unwrap().holders[&chunk], 1);
+    }
+
+}
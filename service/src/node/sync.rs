@@ -0,0 +1,636 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use super::Node;
+use crate::network::client_manager::ClientManager;
+use crate::network::packet::Packet;
+use crate::network::peer_score::Misbehaviour;
+use crate::network::handshake::Capabilities;
+
+use libhyperchain::chain::BlockChainAddResult;
+use libhyperchain::chain::branch::BlockChainCanMergeResult;
+use libhyperchain::block::{Block, BlockHeader};
+use libhyperchain::block::validate::BlockValidationResult;
+use libhyperchain::service::command::SyncProgress;
+use libhyperchain::config::BLOCK_SAMPLE_SIZE;
+use libhyperchain::hash::Hash;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use std::error::Error;
+
+pub(super) const HEADER_BATCH_SIZE: u64 = 500;
+const BLOCK_BATCH_SIZE: u64 = 16;
+const MAX_REQUESTS_IN_FLIGHT: usize = 8;
+
+/// Blocks that can be asked for past the next one to add, so arrived blocks
+/// waiting on earlier ones can't pile up without end
+const MAX_BLOCKS_AHEAD: u64 = 4 * MAX_REQUESTS_IN_FLIGHT as u64 * BLOCK_BATCH_SIZE;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+struct BatchRequest
+{
+    end: u64,
+    peer: String,
+    sent: Instant,
+}
+
+/// Catching up with a longer chain. Its headers come from one node, then the
+/// blocks for them are fetched in batches from every connected node and
+/// checked against the headers.
+pub struct BlockSync
+{
+    peer: String,
+
+    /// Headers of the chain being synced, from where it splits from ours
+    headers: Vec<BlockHeader>,
+    has_all_headers: bool,
+
+    /// Blocks that have arrived but haven't been added to the chain yet
+    blocks: HashMap<u64, Block>,
+    next_to_add: u64,
+
+    /// Batches asked for, by the id of their first block
+    requested: HashMap<u64, BatchRequest>,
+    next_to_request: u64,
+    requests_sent: usize,
+    last_progress: Instant,
+}
+
+impl BlockSync
+{
+
+    fn new(peer: &str, headers: Vec<BlockHeader>) -> Self
+    {
+        let fork_block = headers.first().unwrap().block_id;
+        Self
+        {
+            peer: peer.to_owned(),
+            headers,
+            has_all_headers: false,
+            blocks: HashMap::new(),
+            next_to_add: fork_block,
+            requested: HashMap::new(),
+            next_to_request: fork_block,
+            requests_sent: 0,
+            last_progress: Instant::now(),
+        }
+    }
+
+    fn fork_block(&self) -> u64
+    {
+        self.headers.first().unwrap().block_id
+    }
+
+    /// Id of the next header we need.
+    fn next_header(&self) -> u64
+    {
+        self.headers.last().unwrap().block_id + 1
+    }
+
+    fn header(&self, block_id: u64) -> Option<&BlockHeader>
+    {
+        if block_id < self.fork_block() {
+            return None;
+        }
+
+        self.headers.get((block_id - self.fork_block()) as usize)
+    }
+
+    fn has_block(&self, block_id: u64) -> bool
+    {
+        block_id < self.next_to_add || self.blocks.contains_key(&block_id)
+    }
+
+    fn is_finished(&self) -> bool
+    {
+        self.has_all_headers && self.next_to_add == self.next_header()
+    }
+
+    /// Check headers follow on from each other, have the right target and
+    /// the work they claim, without needing their blocks. Headers before 
+    /// them are looked up with `header_at` for the target samples.
+    fn validate_headers<F>(prev_or_none: Option<&BlockHeader>, headers: &[BlockHeader],
+                           mut header_at: F)
+            -> Result<BlockValidationResult, Box<dyn Error>>
+        where F: FnMut(u64) -> Option<BlockHeader>
+    {
+        let bottom_id = headers.first().unwrap().block_id;
+        let mut sample_at = |block_id: u64|
+        {
+            if block_id >= bottom_id {
+                headers.get((block_id - bottom_id) as usize).cloned()
+            } else {
+                header_at(block_id)
+            }
+        };
+
+        let mut prev_or_none = prev_or_none.cloned();
+        for header in headers
+        {
+            if let Some(prev) = &prev_or_none
+            {
+                match header.validate_next(prev)?
+                {
+                    BlockValidationResult::Ok => {},
+                    result => return Ok(result),
+                }
+
+                let (sample_start, sample_end) =
+                    if prev.block_id < BLOCK_SAMPLE_SIZE {
+                        (None, None)
+                    } else {
+                        (sample_at(prev.block_id - BLOCK_SAMPLE_SIZE), sample_at(prev.block_id))
+                    };
+                match header.validate_target(sample_start.as_ref(), sample_end.as_ref())
+                {
+                    BlockValidationResult::Ok => {},
+                    result => return Ok(result),
+                }
+            }
+
+            // NOTE: Storage proofs can only be checked once we have the
+            //       block, so this only turns away headers without the work
+            //       they claim
+            match header.validate_claimed_pow()?
+            {
+                BlockValidationResult::Ok => {},
+                result => return Ok(result),
+            }
+            prev_or_none = Some(header.clone());
+        }
+
+        Ok(BlockValidationResult::Ok)
+    }
+
+    fn request_batch(&mut self, manager: &mut ClientManager, start: u64, end: u64, peer: &str)
+        -> Result<(), Box<dyn Error>>
+    {
+        debug!("Requesting blocks {} to {} from {}", start, end, peer);
+        manager.send_to(Packet::GetBlocks(start, end), |x| x == peer)?;
+        self.requested.insert(start, BatchRequest
+        {
+            end,
+            peer: peer.to_owned(),
+            sent: Instant::now(),
+        });
+        self.requests_sent += 1;
+        Ok(())
+    }
+
+    pub fn progress(&self) -> SyncProgress
+    {
+        SyncProgress
+        {
+            peer: self.peer.clone(),
+            fork_block: self.fork_block(),
+            top_block: self.next_header() - 1,
+            headers: self.headers.len() as u64,
+            blocks: self.next_to_add - self.fork_block() + self.blocks.len() as u64,
+            requests_in_flight: self.requested.len(),
+        }
+    }
+
+}
+
+impl Node
+{
+
+    pub fn sync_progress(&self) -> Option<SyncProgress>
+    {
+        self.sync.as_ref().map(|sync| sync.progress())
+    }
+
+    /// Ask a node for the headers of its chain after where it splits from
+    /// ours.
+    pub fn request_sync_headers(&mut self, manager: &mut ClientManager, to: &str)
+        -> Result<(), Box<dyn Error>>
+    {
+        let locator = match &mut self.light_client
+        {
+            Some(light_client) => light_client.locator(),
+            None => self.chain.locator(),
+        };
+
+        manager.send_to(Packet::GetHeaders(locator), |x| x == to)?;
+        Ok(())
+    }
+
+    fn header(&mut self, block_id: u64) -> Option<BlockHeader>
+    {
+        match &mut self.light_client
+        {
+            Some(light_client) => light_client.header(block_id),
+            None => self.chain.block(block_id).map(|block| block.header),
+        }
+    }
+
+    /// Whether a node said it has a top block we don't when it connected,
+    /// so may have a longer chain.
+    pub fn has_unknown_top(&mut self, manager: &ClientManager, from: &str)
+        -> Result<bool, Box<dyn Error>>
+    {
+        let top_or_none = manager.peer(from).and_then(|peer| peer.top);
+        if top_or_none.is_none() {
+            return Ok(false);
+        }
+
+        let (block_id, hash) = top_or_none.unwrap();
+        match self.header(block_id)
+        {
+            Some(header) => Ok(header.hash()? != hash),
+            None => Ok(true),
+        }
+    }
+
+    pub fn handle_get_headers(&mut self, manager: &mut ClientManager,
+                              from: &str, locator: Vec<(u64, Hash)>)
+        -> Result<(), Box<dyn Error>>
+    {
+        let start = locator.iter()
+            .find(|(block_id, hash)| self.header(*block_id)
+                .and_then(|header| header.hash().ok()).as_ref() == Some(hash))
+            .map(|(block_id, _)| block_id + 1)
+            .unwrap_or(0);
+
+        let headers = (start..start + HEADER_BATCH_SIZE)
+            .map_while(|block_id| self.header(block_id))
+            .collect::<Vec<_>>();
+
+        if !headers.is_empty() {
+            manager.send_to(Packet::Headers(headers), |x| x == from)?;
+        }
+        Ok(())
+    }
+
+    /// Whether a run of headers joins onto our chain and makes it longer.
+    fn is_longer_branch(&mut self, headers: &[BlockHeader]) -> Result<bool, Box<dyn Error>>
+    {
+        let bottom = headers.first().unwrap();
+        let top = headers.last().unwrap();
+        if bottom.block_id > self.chain.next_top() || top.block_id < self.chain.next_top() {
+            return Ok(false);
+        }
+
+        if bottom.block_id == 0 {
+            return Ok(true);
+        }
+
+        match self.chain.block(bottom.block_id - 1)
+        {
+            Some(prev) => Ok(prev.hash()? == bottom.prev_hash),
+            None => Ok(false),
+        }
+    }
+
+    pub fn handle_sync_headers(&mut self, manager: &mut ClientManager,
+                               from: &str, headers: Vec<BlockHeader>)
+        -> Result<(), Box<dyn Error>>
+    {
+        if headers.is_empty() {
+            return Ok(());
+        }
+
+        let bottom_id = headers.first().unwrap().block_id;
+        let is_continuation = matches!(&self.sync,
+            Some(sync) if sync.peer == from && sync.next_header() == bottom_id);
+
+        let prev_or_none =
+            if is_continuation
+            {
+                self.sync.as_ref().unwrap().headers.last().cloned()
+            }
+            else
+            {
+                // Only one chain is synced at a time, unless it's stalled
+                if let Some(sync) = &self.sync
+                {
+                    if sync.last_progress.elapsed() < REQUEST_TIMEOUT {
+                        return Ok(());
+                    }
+                }
+
+                if !self.is_longer_branch(&headers)? {
+                    return Ok(());
+                }
+
+                if bottom_id == 0 {
+                    None
+                } else {
+                    self.chain.block(bottom_id - 1).map(|block| block.header)
+                }
+            };
+
+        // Headers before these are either ones we're syncing or in our chain
+        let chain = &mut self.chain;
+        let sync_or_none = if is_continuation { self.sync.as_ref() } else { None };
+        let header_at = |block_id: u64| match sync_or_none.and_then(|sync| sync.header(block_id))
+        {
+            Some(header) => Some(header.clone()),
+            None => chain.block(block_id).map(|block| block.header),
+        };
+
+        match BlockSync::validate_headers(prev_or_none.as_ref(), &headers, header_at)?
+        {
+            BlockValidationResult::Ok => {},
+            result =>
+            {
+                warn!("[{}] Got invalid headers from {}: {}", self.port, from, result);
+                manager.report_misbehaviour(from, Misbehaviour::InvalidBlock);
+                if is_continuation {
+                    self.sync = None;
+                }
+                return Ok(());
+            },
+        }
+
+        let is_full_batch = headers.len() as u64 == HEADER_BATCH_SIZE;
+        if is_continuation {
+            self.sync.as_mut().unwrap().headers.extend(headers);
+        } else {
+            info!("[{}] Syncing from block {} with {}", self.port, bottom_id, from);
+            self.sync = Some(BlockSync::new(from, headers));
+        }
+
+        let sync = self.sync.as_mut().unwrap();
+        sync.last_progress = Instant::now();
+        if is_full_batch
+        {
+            let last = sync.headers.last().unwrap();
+            manager.send_to(Packet::GetHeaders(vec![(last.block_id, last.hash()?)]), |x| x == from)?;
+        }
+        else
+        {
+            sync.has_all_headers = true;
+        }
+
+        self.request_sync_blocks(manager)
+    }
+
+    /// Spread batches of blocks we still need over the connected nodes.
+    /// Ones that have timed out are asked for again from the node we're
+    /// syncing from, as it's the one known to have them.
+    fn request_sync_blocks(&mut self, manager: &mut ClientManager)
+        -> Result<(), Box<dyn Error>>
+    {
+        if self.sync.is_none() {
+            return Ok(());
+        }
+
+        let sync = self.sync.as_mut().unwrap();
+        let timed_out = sync.requested.iter()
+            .filter(|(_, request)| request.sent.elapsed() > REQUEST_TIMEOUT)
+            .map(|(start, request)| (*start, request.end))
+            .collect::<Vec<_>>();
+
+        let peer = sync.peer.clone();
+        for (start, end) in timed_out {
+            sync.request_batch(manager, start, end, &peer)?;
+        }
+
+        let mut peers = manager.connected_nodes_with(Capabilities::FULL_BLOCKS);
+        if !peers.contains(&peer) {
+            peers.push(peer);
+        }
+
+        let request_limit = std::cmp::min(sync.next_header(), sync.next_to_add + MAX_BLOCKS_AHEAD);
+        while sync.requested.len() < MAX_REQUESTS_IN_FLIGHT && sync.next_to_request < request_limit
+        {
+            let start = sync.next_to_request;
+            let end = std::cmp::min(start + BLOCK_BATCH_SIZE, request_limit) - 1;
+            let peer = peers[sync.requests_sent % peers.len()].clone();
+            sync.request_batch(manager, start, end, &peer)?;
+            sync.next_to_request = end + 1;
+        }
+
+        Ok(())
+    }
+
+    pub fn handle_get_blocks(&mut self, manager: &mut ClientManager,
+                             from: &str, start: u64, end: u64)
+        -> Result<(), Box<dyn Error>>
+    {
+        let end = std::cmp::min(end, start.saturating_add(BLOCK_BATCH_SIZE - 1));
+        let blocks = (start..=end)
+            .map_while(|block_id| self.chain.block(block_id))
+            .collect::<Vec<_>>();
+
+        manager.send_to(Packet::Blocks(start, blocks), |x| x == from)?;
+        Ok(())
+    }
+
+    pub fn handle_blocks(&mut self, manager: &mut ClientManager,
+                         from: &str, start: u64, blocks: Vec<Block>)
+        -> Result<(), Box<dyn Error>>
+    {
+        if self.sync.is_none() {
+            return Ok(());
+        }
+
+        let sync = self.sync.as_mut().unwrap();
+        if !matches!(sync.requested.get(&start), Some(request) if request.peer == from) {
+            return Ok(());
+        }
+
+        let request = sync.requested.remove(&start).unwrap();
+        for block in blocks
+        {
+            let block_id = block.header.block_id;
+            if !(start..=request.end).contains(&block_id) || sync.has_block(block_id) {
+                continue;
+            }
+
+            let header = sync.header(block_id).unwrap();
+            if block.validate_against_header(header)? == BlockValidationResult::Ok {
+                sync.blocks.insert(block_id, block);
+            }
+        }
+
+        // Other nodes may not have the chain we're syncing, but the one we
+        // got the headers from should
+        if !(start..=request.end).all(|block_id| sync.has_block(block_id))
+        {
+            if from == sync.peer
+            {
+                warn!("[{}] {} doesn't have the blocks for its headers", self.port, from);
+                self.sync = None;
+                return Ok(());
+            }
+
+            let peer = sync.peer.clone();
+            sync.request_batch(manager, start, request.end, &peer)?;
+        }
+
+        sync.last_progress = Instant::now();
+        self.add_sync_blocks(manager)?;
+        self.request_sync_blocks(manager)
+    }
+
+    /// Add blocks that follow on from our top as they arrive. If we've moved
+    /// on from where the sync started, the ones that have arrived are merged
+    /// as a branch once there's more of them than blocks we've added since,
+    /// then the rest follow on from there.
+    fn add_sync_blocks(&mut self, manager: &mut ClientManager)
+        -> Result<(), Box<dyn Error>>
+    {
+        let mut sync = self.sync.take().unwrap();
+        while sync.next_to_add == self.chain.next_top()
+        {
+            let block_or_none = sync.blocks.remove(&sync.next_to_add);
+            if block_or_none.is_none() {
+                break;
+            }
+
+            let block = block_or_none.unwrap();
+            match self.chain.add(&block)?
+            {
+                BlockChainAddResult::Ok => sync.next_to_add += 1,
+                result =>
+                {
+                    warn!("[{}] Can't add block {} synced from {}: {:?}",
+                        self.port, block.header.block_id, sync.peer, result);
+                    // NOTE: Our chain may have moved on since the sync started
+                    let is_invalid = matches!(result, BlockChainAddResult::Invalid(reason)
+                        if reason != BlockValidationResult::PrevHash && reason != BlockValidationResult::NotNextBlock);
+                    if is_invalid {
+                        manager.report_misbehaviour(&sync.peer, Misbehaviour::InvalidBlock);
+                    }
+                    return Ok(());
+                },
+            }
+        }
+
+        let mut branch_end = sync.next_to_add;
+        while sync.blocks.contains_key(&branch_end) {
+            branch_end += 1;
+        }
+
+        if sync.next_to_add != self.chain.next_top() && branch_end > self.chain.next_top()
+        {
+            let branch = (sync.next_to_add..branch_end)
+                .map(|block_id| sync.blocks.remove(&block_id).unwrap())
+                .collect::<Vec<_>>();
+
+            match self.chain.can_merge_branch(&branch)?
+            {
+                BlockChainCanMergeResult::Ok =>
+                {
+                    info!("[{}] Merge branch synced from {}", self.port, sync.peer);
+                    self.site_cache.invalidate_from(sync.next_to_add);
+                    self.chain.merge_branch(branch);
+                    sync.next_to_add = branch_end;
+                },
+
+                result =>
+                {
+                    warn!("[{}] Can't merge branch synced from {}: {:?}", self.port, sync.peer, result);
+                    if matches!(result, BlockChainCanMergeResult::Invalid(_)) {
+                        manager.report_misbehaviour(&sync.peer, Misbehaviour::InvalidBlock);
+                    }
+                    return Ok(());
+                },
+            }
+        }
+
+        if !sync.is_finished()
+        {
+            self.sync = Some(sync);
+            return Ok(());
+        }
+
+        info!("[{}] Synced up to block {} with {}", self.port, sync.next_header() - 1, sync.peer);
+        if let Some(top) = self.chain.top() {
+            manager.send(Packet::Block(top))?;
+        }
+
+        // They may have found more blocks while we were syncing
+        self.request_sync_headers(manager, &sync.peer)
+    }
+
+    pub(super) fn update_sync(&mut self, manager: &mut ClientManager)
+        -> Result<(), Box<dyn Error>>
+    {
+        if let Some(sync) = &self.sync
+        {
+            if sync.last_progress.elapsed() > SYNC_TIMEOUT
+            {
+                warn!("[{}] Sync with {} stalled", self.port, sync.peer);
+                self.sync = None;
+            }
+        }
+
+        self.request_sync_blocks(manager)
+    }
+
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::BlockSync;
+    use crate::node::tests::{create_node, mine_block, wait_for_block};
+    use libhyperchain::block::validate::BlockValidationResult;
+    use libhyperchain::wallet::private_wallet::PrivateWallet;
+
+    #[test]
+    fn test_validate_sync_headers()
+    {
+        let _ = pretty_env_logger::try_init();
+
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let mut connection = create_node(8097);
+        for _ in 0..3 {
+            mine_block(&mut connection, &wallet);
+        }
+
+        let mut headers = (0..3)
+            .map(|block_id| connection.handler().node().chain().block(block_id).unwrap().header)
+            .collect::<Vec<_>>();
+        assert_eq!(BlockSync::validate_headers(None, &headers, |_| None).unwrap(), BlockValidationResult::Ok);
+
+        // A header can't make up an easier target
+        headers[1].target = [0xFF; 4];
+        assert_eq!(BlockSync::validate_headers(None, &headers, |_| None).unwrap(), BlockValidationResult::Target);
+    }
+
+    #[test]
+    fn test_node_headers_first_sync()
+    {
+        let _ = pretty_env_logger::try_init();
+
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let mut connection_a = create_node(8080);
+        let mut connection_b = create_node(8081);
+        let connection_c = create_node(8082);
+        for _ in 0..40 {
+            mine_block(&mut connection_a, &wallet);
+        }
+
+        // C has the same chain as A to share the work of sending blocks
+        {
+            let mut node_a = connection_a.handler().node();
+            let mut node_c = connection_c.handler().node();
+            for block_id in 0..40 {
+                node_c.chain().add(&node_a.chain().block(block_id).unwrap()).unwrap();
+            }
+        }
+        connection_b.manager().register_node("127.0.0.1:8082");
+        wait_for_block(&connection_b, 39);
+
+        // B forks off from A, then catches up with A's longer branch
+        let forked = mine_block(&mut connection_b, &wallet);
+        for _ in 0..3 {
+            mine_block(&mut connection_a, &wallet);
+        }
+        connection_b.manager().register_node("127.0.0.1:8080");
+        let top = wait_for_block(&connection_b, 42);
+        assert_ne!(connection_b.handler().node().chain().block(40), Some(forked));
+        assert_eq!(top, connection_a.handler().node().chain().block(42).unwrap());
+        assert_eq!(connection_b.handler().node().sync_progress(), None);
+    }
+
+}
@@ -0,0 +1,82 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use crate::network::NetworkConnection;
+use crate::network::packet::Packet;
+use crate::node::packet_handler::NodePacketHandler;
+use crate::send::deserialize_outputs;
+
+use libhyperchain::service::command::Response;
+use libhyperchain::wallet::public_wallet::PublicWallet;
+use libhyperchain::transaction::{Transaction, TransactionValidationResult};
+use libhyperchain::transaction::transfer::Transfer;
+
+pub fn create_transfer(connection: &mut NetworkConnection<NodePacketHandler>,
+                       inputs: Vec<(PublicWallet, f32)>,
+                       serialized_outputs: Vec<(Vec<u8>, f32)>,
+                       fee: f32)
+    -> Response
+{
+    let outputs_or_none = deserialize_outputs(serialized_outputs);
+    if outputs_or_none.is_none() {
+        return Response::Failed;
+    }
+
+    let outputs = outputs_or_none.unwrap();
+    let ref_inputs = inputs.iter().map(|(w, a)| (w, *a)).collect::<Vec<_>>();
+
+    let mut node = connection.handler().node();
+    let chain = &mut node.chain();
+    let unsigned_or_error = chain.new_unsigned_transfer(ref_inputs, outputs, fee);
+    if unsigned_or_error.is_err()
+    {
+        warn!("Error in create transfer: {}", unsigned_or_error.unwrap_err());
+        return Response::Failed;
+    }
+
+    Response::UnsignedTransfer(unsigned_or_error.unwrap())
+}
+
+pub fn submit_transfer(connection: &mut NetworkConnection<NodePacketHandler>,
+                       transfer: Transaction<Transfer>)
+    -> Response
+{
+    let transfer_id;
+
+    {
+        let result_or_error = transfer.validate_content();
+        if result_or_error.is_err()
+        {
+            warn!("Error in submit transfer: {}", result_or_error.unwrap_err());
+            return Response::Failed;
+        }
+
+        let result = result_or_error.unwrap();
+        if result != TransactionValidationResult::Ok
+        {
+            warn!("Submitted transfer not valid: {}", result);
+            return Response::Failed;
+        }
+
+        let transfer_id_or_error = transfer.hash();
+        if transfer_id_or_error.is_err() {
+            return Response::Failed;
+        }
+        transfer_id = transfer_id_or_error.unwrap();
+
+        let mut node = connection.handler().node();
+        let chain = &mut node.chain();
+        let result = chain.push_transfer_queue(transfer.clone());
+        if result.is_err()
+        {
+            warn!("Error in submit transfer: {}", result.unwrap_err());
+            return Response::Failed;
+        }
+    }
+
+    connection.manager().send(Packet::Transfer(transfer)).unwrap();
+    Response::Sent(transfer_id.data().to_vec())
+}
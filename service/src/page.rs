@@ -9,7 +9,11 @@ use crate::node::packet_handler::NodePacketHandler;
 
 use libhyperchain::service::command::Response;
 use libhyperchain::transaction::TransactionVariant;
+use libhyperchain::data_store::page::guess_content_type;
 use libhyperchain::hash::Hash;
+use std::time::{Duration, Instant};
+
+const CHUNK_FETCH_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub fn page_updates(connection: &mut NetworkConnection<NodePacketHandler>,
                     address_vec: Vec<u8>) -> Response
@@ -24,14 +28,53 @@ pub fn page_updates(connection: &mut NetworkConnection<NodePacketHandler>,
     Response::PageUpdates(chain.get_page_updates(&address))
 }
 
+/// Request the chunks we're missing for these pages from other nodes, and
+/// wait for them to arrive. Returns false if some couldn't be fetched.
+fn fetch_page_chunks(connection: &mut NetworkConnection<NodePacketHandler>,
+                     pages: &[(Hash, u32)]) -> bool
+{
+    let handler = connection.handler().clone();
+    for (chunk_root, chunk_count) in pages
+    {
+        let result = handler.node().request_page_chunks(connection.manager(), chunk_root, *chunk_count);
+        if let Err(err) = result
+        {
+            warn!("Unable to request chunks: {}", err);
+            return false;
+        }
+    }
+
+    let start = Instant::now();
+    loop
+    {
+        {
+            let mut node = handler.node();
+            let data_store = node.data_store();
+            let has_page = |chunk_root: &Hash| match data_store.chunk_list(chunk_root)
+            {
+                Ok(hashes) => hashes.iter().all(|hash| data_store.has_chunk(hash)),
+                Err(_) => false,
+            };
+
+            if pages.iter().all(|(chunk_root, _)| has_page(chunk_root)) {
+                return true;
+            }
+        }
+
+        if start.elapsed() >= CHUNK_FETCH_TIMEOUT {
+            warn!("Timed out waiting for chunks");
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
 pub fn page_data(connection: &mut NetworkConnection<NodePacketHandler>, 
                  transaction_id: Vec<u8>) -> Response
 {
-    let mut node = connection.handler().node();
-    let chain = node.chain();
-
     let transaction_id_hash = Hash::from(&transaction_id);
-    let transaction_or_none = chain.find_transaction(&transaction_id_hash);
+    let transaction_or_none = connection.handler().node()
+        .chain().find_transaction(&transaction_id_hash);
     if transaction_or_none.is_none() {
         return Response::Failed;
     }
@@ -42,7 +85,10 @@ pub fn page_data(connection: &mut NetworkConnection<NodePacketHandler>,
         TransactionVariant::Page(page) =>
         {
             info!("Fetching page data for transaction '{}'", base_62::encode(&transaction_id));
-            match node.data_store().get_data_unit(&page)
+            let content = &page.header.content;
+            fetch_page_chunks(connection, &[(content.chunk_root, content.chunk_count)]);
+
+            match connection.handler().node().data_store().get_data_unit(&page)
             {
                 Ok(data) => Response::PageData(data),
                 Err(_) => Response::Failed,
@@ -54,3 +100,101 @@ pub fn page_data(connection: &mut NetworkConnection<NodePacketHandler>,
 
 }
 
+pub fn site_file(connection: &mut NetworkConnection<NodePacketHandler>,
+                 site_vec: Vec<u8>, path: String) -> Response
+{
+    let site = Hash::from(&site_vec);
+    let mut file_or_error = connection.handler().node().site_file(&site, &path);
+    if file_or_error.is_err()
+    {
+        // Some of the site's data may be stored on other nodes
+        let pages = connection.handler().node().chain()
+            .get_page_updates(&site)
+            .iter()
+            .map(|update| (update.header.content.chunk_root, update.header.content.chunk_count))
+            .collect::<Vec<_>>();
+
+        if fetch_page_chunks(connection, &pages) {
+            file_or_error = connection.handler().node().site_file(&site, &path);
+        }
+    }
+
+    match file_or_error
+    {
+        Ok(Some(file)) => 
+        {
+            let content_type = file.content_type
+                .or_else(|| guess_content_type(&path));
+            Response::SiteFile(file.data, content_type, file.chunk_root)
+        },
+
+        Ok(None) => Response::Failed,
+        Err(err) =>
+        {
+            warn!("[{}] Unable to materialise site: {}", site, err);
+            Response::Failed
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+
+    use super::*;
+    use crate::node::tests::{create_node, mine_block, wait_for_block};
+    use crate::network::packet::Packet;
+    use libhyperchain::wallet::private_wallet::PrivateWallet;
+    use libhyperchain::data_store::data_unit::DataUnit;
+    use libhyperchain::data_store::page::CreatePageData;
+    use libhyperchain::block::Block;
+    use libhyperchain::miner;
+
+    #[test]
+    fn test_fetch_page_data()
+    {
+        let _ = pretty_env_logger::try_init();
+
+        let wallet = PrivateWallet::open_temp(0).unwrap();
+        let mut connection_a = create_node(8050);
+        let mut connection_b = create_node(8051);
+        connection_b.manager().register_node("127.0.0.1:8050");
+
+        // Ensure nodes are connected.
+        mine_block(&mut connection_a, &wallet);
+        wait_for_block(&connection_b, 0);
+
+        // Only A stores the page's data
+        let data_unit = DataUnit::CreatePage(
+            CreatePageData::new("index.html".to_owned(), b"<p>Hi</p>".repeat(100)));
+        let (block, page, report) =
+        {
+            let mut node = connection_a.handler().node();
+            let page = node.chain().new_page(&wallet, &data_unit, 0.0).unwrap();
+            node.data_store().store_data_unit(&data_unit).unwrap();
+
+            let block = miner::mine_block(Block::new(node.chain(), &wallet,
+                Vec::new(), vec![page.clone()], Vec::new(), Vec::new()).unwrap());
+            node.chain().add(&block).unwrap();
+            (block, page, node.our_report().unwrap())
+        };
+
+        connection_a.manager().send(Packet::Block(block)).unwrap();
+        connection_a.manager().send(Packet::Report(None, report)).unwrap();
+        wait_for_block(&connection_b, 1);
+
+        // B knows A has the manifest, but can't count it without a copy to
+        // challenge A with
+        let manifest = data_unit.hashes().unwrap()[0];
+        while connection_b.handler().node().storage_usage().unwrap().unchecked.get(&manifest) != Some(&1) {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert!(!connection_b.handler().node().data_store().has_chunk(&manifest));
+
+        let page_id = page.hash().unwrap().data().to_vec();
+        assert_eq!(page_data(&mut connection_b, page_id), Response::PageData(data_unit));
+        assert!(connection_b.handler().node().data_store().has_chunk(&manifest));
+        assert!(connection_b.handler().node().data_store().chunk_list(&page.header.content.chunk_root).is_ok());
+    }
+
+}
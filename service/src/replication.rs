@@ -0,0 +1,33 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use crate::network::NetworkConnection;
+use crate::node::packet_handler::NodePacketHandler;
+
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const REPLICATION_INTERVAL: Duration = Duration::from_secs(10);
+
+pub fn start_replication_thread(mut connection: NetworkConnection<NodePacketHandler>)
+    -> JoinHandle<()>
+{
+    std::thread::spawn(move || loop
+    {
+        if connection.should_shutdown() 
+        {
+            debug!("Exit replication manager");
+            break;
+        }
+
+        let handler = connection.handler().clone();
+        if let Err(err) = handler.node().replicate(connection.manager()) {
+            warn!("Error in replication: {}", err);
+        }
+
+        std::thread::sleep(REPLICATION_INTERVAL);
+    })
+}
@@ -6,7 +6,8 @@
 
 use libhyperchain::hash::Hash;
 use serde::{Serialize, Deserialize};
-use std::collections::{HashSet, HashMap};
+use serde::de::DeserializeOwned;
+use std::collections::{HashSet, HashMap, VecDeque};
 use std::path::PathBuf;
 use std::fs::File;
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
@@ -14,6 +15,9 @@ use std::error::Error;
 
 const EXPIRE_TIME: Duration = Duration::from_secs(60 * 60 * 24); // 24 hours
 
+/// How many of a node's latest storage challenges are remembered.
+const PROOF_WINDOW: usize = 10;
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct NodeReport
 {
@@ -40,17 +44,64 @@ impl NodeReport
 
 }
 
+/// Results of the storage challenges sent to a node.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ProofHistory
+{
+    pub passed: u32,
+    pub failed: u32,
+
+    /// Results of the latest challenges, oldest first. Nodes are only
+    /// trusted to store what they report while none of these failed.
+    #[serde(default)]
+    pub recent: VecDeque<bool>,
+}
+
+impl ProofHistory
+{
+
+    pub fn recent_failures(&self) -> usize
+    {
+        self.recent.iter().filter(|passed| !**passed).count()
+    }
+
+}
+
+/// How many nodes store each chunk. Claims on chunks we can't challenge
+/// are only counted for nodes that have passed challenges before, and are
+/// kept apart for the ones that haven't.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StorageUsage
+{
+    pub holders: HashMap<Hash, usize>,
+    pub unchecked: HashMap<Hash, usize>,
+}
+
+impl StorageUsage
+{
+
+    /// Chunks only claimed by nodes we haven't checked yet.
+    pub fn unchecked_only(&self) -> impl Iterator<Item = &Hash>
+    {
+        self.unchecked.keys().filter(move |chunk| !self.holders.contains_key(*chunk))
+    }
+
+}
+
 pub struct Report
 {
     path: PathBuf,
     nodes: HashMap<String, NodeReport>,
+
+    proofs_path: PathBuf,
+    proofs: HashMap<String, ProofHistory>,
 }
 
 impl Report
 {
 
-    fn load_existing_report(path: &PathBuf) 
-        -> Result<HashMap<String, NodeReport>, Box<dyn Error>>
+    fn load_existing_report<T>(path: &PathBuf) -> Result<T, Box<dyn Error>>
+        where T: DeserializeOwned
     {
         let file = File::open(path)?;
         Ok(serde_json::from_reader(file)?)
@@ -61,10 +112,17 @@ impl Report
         let nodes = Self::load_existing_report(path)
             .unwrap_or(Default::default());
 
+        let proofs_path = path.with_file_name("storage_proofs.json");
+        let proofs = Self::load_existing_report(&proofs_path)
+            .unwrap_or(Default::default());
+
         Self
         {
             path: path.to_owned(),
             nodes,
+
+            proofs_path,
+            proofs,
         }
     }
 
@@ -72,6 +130,9 @@ impl Report
     {
         let file = File::create(&self.path)?;
         serde_json::to_writer_pretty(file, &self.nodes)?;
+
+        let file = File::create(&self.proofs_path)?;
+        serde_json::to_writer_pretty(file, &self.proofs)?;
         Ok(())
     }
 
@@ -121,17 +182,77 @@ impl Report
         expired.into_iter()
     }
 
-    pub fn storage_usage(&self) -> HashMap<Hash, usize>
+    pub fn node_report(&self, address: &str) -> Option<&NodeReport>
+    {
+        self.nodes.get(address)
+    }
+
+    pub fn record_proof(&mut self, address: &str, passed: bool)
     {
-        let mut usage = HashMap::new();
-        for (_, report) in &self.nodes
+        let history = self.proofs.entry(address.to_owned()).or_default();
+        if passed {
+            history.passed += 1;
+        } else {
+            history.failed += 1;
+        }
+
+        history.recent.push_back(passed);
+        if history.recent.len() > PROOF_WINDOW {
+            history.recent.pop_front();
+        }
+        self.flush_handle_errors();
+    }
+
+    pub fn proof_history(&self, address: &str) -> ProofHistory
+    {
+        self.proofs.get(address).cloned().unwrap_or_default()
+    }
+
+    fn is_trusted(&self, address: &str) -> bool
+    {
+        self.proof_history(address).recent_failures() == 0
+    }
+
+    pub fn node_count(&self) -> usize
+    {
+        self.nodes.len()
+    }
+
+    /// Addresses of nodes that have reported storing this chunk.
+    pub fn nodes_storing(&self, chunk: &Hash) -> HashSet<String>
+    {
+        self.nodes
+            .iter()
+            .filter(|(address, _)| self.is_trusted(address))
+            .filter(|(_, report)| report.chunks_stored.contains(chunk))
+            .map(|(address, _)| address.to_owned())
+            .collect()
+    }
+
+    /// How many trusted nodes store each chunk that's been reported. Claims
+    /// on chunks `is_challengeable` rejects are taken provisionally from
+    /// nodes that have passed a challenge before, and left unchecked for
+    /// the rest.
+    pub fn storage_usage<F>(&self, is_challengeable: F) -> StorageUsage
+        where F: Fn(&Hash) -> bool
+    {
+        let mut usage = StorageUsage::default();
+        for (address, report) in &self.nodes
         {
+            if !self.is_trusted(address) {
+                continue;
+            }
+
+            let has_proven_storage = self.proof_history(address).passed > 0;
             for chunk in &report.chunks_stored 
             {
-                if !usage.contains_key(chunk) {
-                    usage.insert(chunk.clone(), 0);
-                }
-                *usage.get_mut(chunk).unwrap() += 1;
+                let counts =
+                    if has_proven_storage || is_challengeable(chunk) {
+                        &mut usage.holders
+                    } else {
+                        &mut usage.unchecked
+                    };
+                *counts.entry(*chunk).or_insert(0) += 1;
             }
         }
 
@@ -166,7 +287,9 @@ mod test
         let chunk_b = Hash::from(&[1u8; HASH_LEN]);
         let chunk_c = Hash::from(&[2u8; HASH_LEN]);
 
-        let mut report = Report::open(&std::env::temp_dir().join("report.json"));
+        let path = std::env::temp_dir().join(rand::random::<u32>().to_string());
+        std::fs::create_dir_all(&path).unwrap();
+        let mut report = Report::open(&path.join("report.json"));
         report.add("a", NodeReport::new(HashSet::from_iter([chunk_a, chunk_b, chunk_c])));
         report.add("b", NodeReport::new(HashSet::from_iter([chunk_a, chunk_c])));
 
@@ -174,12 +297,59 @@ mod test
         node_c_report.expires -= EXPIRE_TIME.as_nanos() + 1;
         report.add("c", node_c_report);
 
-        assert_eq!(report.storage_usage(), 
+        assert_eq!(report.storage_usage(|_| true).holders, 
                    HashMap::from_iter([(chunk_a, 2), (chunk_b, 2), (chunk_c, 2)]));
 
         assert_eq!(report.update().collect::<Vec<_>>(), vec!["c"]);
-        assert_eq!(report.storage_usage(), 
+        assert_eq!(report.storage_usage(|_| true).holders, 
                    HashMap::from_iter([(chunk_a, 2), (chunk_b, 1), (chunk_c, 2)]));
+
+        // Claims that can't be checked are kept apart
+        let usage = report.storage_usage(|chunk| chunk != &chunk_c);
+        assert_eq!(usage.holders, HashMap::from_iter([(chunk_a, 2), (chunk_b, 1)]));
+        assert_eq!(usage.unchecked, HashMap::from_iter([(chunk_c, 2)]));
+        assert_eq!(usage.unchecked_only().collect::<Vec<_>>(), vec![&chunk_c]);
+
+        // Unless the node has proven it stores what it says before
+        report.record_proof("a", true);
+        let usage = report.storage_usage(|chunk| chunk != &chunk_c);
+        assert_eq!(usage.holders, HashMap::from_iter([(chunk_a, 2), (chunk_b, 1), (chunk_c, 1)]));
+        assert_eq!(usage.unchecked, HashMap::from_iter([(chunk_c, 1)]));
+        assert_eq!(usage.unchecked_only().count(), 0);
+    }
+
+    #[test]
+    fn test_proof_history()
+    {
+        let chunk = Hash::from(&[3u8; HASH_LEN]);
+        let path = std::env::temp_dir().join(rand::random::<u32>().to_string());
+        std::fs::create_dir_all(&path).unwrap();
+
+        let mut report = Report::open(&path.join("report.json"));
+        report.add("honest", NodeReport::new(HashSet::from_iter([chunk])));
+        report.add("liar", NodeReport::new(HashSet::from_iter([chunk])));
+        report.record_proof("honest", true);
+        report.record_proof("liar", true);
+        report.record_proof("liar", false);
+        report.record_proof("liar", true);
+
+        // Only nodes that haven't failed a recent challenge are counted
+        assert_eq!(report.storage_usage(|_| true).holders, HashMap::from_iter([(chunk, 1)]));
+        assert_eq!(report.nodes_storing(&chunk), HashSet::from_iter(["honest".to_owned()]));
+
+        let mut reopened = Report::open(&path.join("report.json"));
+        assert_eq!(reopened.proof_history("liar"), 
+                   ProofHistory { passed: 2, failed: 1, recent: VecDeque::from(vec![true, false, true]) });
+        assert_eq!(reopened.proof_history("unknown"), ProofHistory::default());
+
+        // Until the failure is out of the window
+        for _ in 0..PROOF_WINDOW - 2 {
+            reopened.record_proof("liar", true);
+        }
+        assert_eq!(reopened.proof_history("liar").recent_failures(), 1);
+        reopened.record_proof("liar", true);
+        assert_eq!(reopened.proof_history("liar").recent_failures(), 0);
+        assert_eq!(reopened.storage_usage(|_| true).holders, HashMap::from_iter([(chunk, 2)]));
     }
 
     fn create_page(connection: &mut NetworkConnection<NodePacketHandler>,
@@ -212,7 +382,7 @@ mod test
     }
 
     fn get_storage_usage(connection: &NetworkConnection<NodePacketHandler>
-                         ) -> HashMap<Hash, usize>
+                         ) -> StorageUsage
     {
         let node = connection.handler().node();
         node.storage_usage()
@@ -250,15 +420,16 @@ mod test
         wait_for_block(&connection_b, 0);
 
         let (test_page, test_data) = create_page(&mut connection_a, &wallet);
-        connection_a.manager().send(Packet::Page(test_page, test_data))
+        connection_a.manager().send(Packet::Page(test_page, test_data.compressed_chunks().unwrap()))
             .expect("Failed to send page request");
 
+        // The page's manifest and its one chunk of content
         let storage_usage_a = get_storage_usage(&connection_a);
-        assert_eq!(storage_usage_a.len(), 1);
+        assert_eq!(storage_usage_a.holders.len(), 2);
 
-        wait_for(|| get_storage_usage(&connection_b).len() == 1, 1000)
+        wait_for(|| get_storage_usage(&connection_b).holders.len() == 2, 1000)
             .expect("Node B did not receive page");
-        wait_for(|| get_storage_usage(&connection_b).values().next() == Some(&2), 1000)
+        wait_for(|| get_storage_usage(&connection_b).holders.values().all(|count| count == &2), 1000)
             .expect("Node B did not receive report");
 
         let mut connection_c = create_node(8042);
@@ -268,11 +439,24 @@ mod test
         mine_block(&mut connection_b, &wallet);
         wait_for_block(&connection_c, 1);
 
-        wait_for(|| get_storage_usage(&connection_c).len() == 1, 1000)
-            .expect("Node C did not receive reports");
+        // Reports are sent when nodes connect, so A's only arrives once C
+        // has found and connected to it
+        while connection_c.manager().connected_nodes().len() < 2 {
+            std::thread::sleep(Duration::from_millis(100));
+        }
 
-        let storage_usage_c = get_storage_usage(&connection_c);
-        assert_eq!(storage_usage_c.values().next(), Some(&2));
+        // C can't challenge nodes on chunks it doesn't have, so their claims
+        // are unchecked until it stores them too
+        wait_for(|| get_storage_usage(&connection_c).unchecked.len() == 2, 1000)
+            .expect("Node C did not receive reports");
+        wait_for(|| get_storage_usage(&connection_c).unchecked.values().all(|count| count == &2), 1000)
+            .expect("Node C did not receive every report");
+        assert_eq!(get_storage_usage(&connection_c).holders, HashMap::new());
+
+        connection_c.handler().node().data_store().store_data_unit(&test_data).unwrap();
+        let usage = get_storage_usage(&connection_c);
+        assert_eq!(usage.holders.len(), 2);
+        assert_eq!(usage.holders.values().all(|count| count == &3), true);
     }
 
 }
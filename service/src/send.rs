@@ -11,6 +11,7 @@ use crate::node::packet_handler::NodePacketHandler;
 use libhyperchain::service::command::Response;
 use libhyperchain::wallet::private_wallet::PrivateWallet;
 use libhyperchain::hash::Hash;
+use libhyperchain::config::HASH_LEN;
 
 fn deserialize_inputs(serialized_inputs: Vec<(Vec<u8>, f32)>) 
     -> Option<Vec<(PrivateWallet, f32)>>
@@ -30,13 +31,17 @@ fn deserialize_inputs(serialized_inputs: Vec<(Vec<u8>, f32)>)
     Some(inputs)
 }
 
-fn deserialize_outputs(serialized_outputs: Vec<(Vec<u8>, f32)>)
+pub fn deserialize_outputs(serialized_outputs: Vec<(Vec<u8>, f32)>)
     -> Option<Vec<(Hash, f32)>>
 {
     let mut outputs = Vec::new();
     for (to_vec, amount) in serialized_outputs
     {
-        // TODO: Varify this is a valid hash
+        // Addresses are checksummed by the client, we just need the raw hash
+        if to_vec.len() != HASH_LEN {
+            return None;
+        }
+
         let to = Hash::from(&to_vec);
         outputs.push((to, amount));
     }
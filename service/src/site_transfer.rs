@@ -0,0 +1,68 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use crate::network::NetworkConnection;
+use crate::network::packet::Packet;
+use crate::node::packet_handler::NodePacketHandler;
+
+use libhyperchain::service::command::Response;
+use libhyperchain::wallet::private_wallet::PrivateWallet;
+use libhyperchain::config::HASH_LEN;
+use libhyperchain::hash::Hash;
+
+pub fn transfer_site(connection: &mut NetworkConnection<NodePacketHandler>,
+                     from: Vec<u8>, site: Vec<u8>, new_owner: Vec<u8>, fee: f32)
+    -> Response
+{
+    let site_transfer;
+    let site_transfer_id;
+
+    {
+        let from_wallet_or_error = PrivateWallet::deserialize(from);
+        if from_wallet_or_error.is_err() {
+            return Response::Failed;
+        }
+
+        if site.len() != HASH_LEN || new_owner.len() != HASH_LEN {
+            return Response::Failed;
+        }
+
+        let from_wallet = from_wallet_or_error.unwrap();
+        let site = Hash::from(&site);
+        let new_owner = Hash::from(&new_owner);
+
+        let mut node = connection.handler().node();
+        let chain = &mut node.chain();
+        let site_transfer_or_error = chain.new_site_transfer(&from_wallet, &site, &new_owner, fee);
+        if site_transfer_or_error.is_err()
+        {
+            warn!("Error in transfer site: {}", site_transfer_or_error.unwrap_err());
+            return Response::Failed;
+        }
+
+        site_transfer = site_transfer_or_error.unwrap();
+        site_transfer_id = site_transfer.hash().unwrap();
+        let result = chain.push_site_transfer_queue(site_transfer.clone());
+        if result.is_err()
+        {
+            warn!("Error in transfer site: {}", result.unwrap_err());
+            return Response::Failed;
+        }
+    }
+
+    connection.manager().send(Packet::SiteTransfer(site_transfer)).unwrap();
+    Response::Sent(site_transfer_id.data().to_vec())
+}
+
+pub fn sites_owned(connection: &mut NetworkConnection<NodePacketHandler>,
+                   address: Vec<u8>) -> Response
+{
+    let mut node = connection.handler().node();
+    let chain = node.chain();
+
+    let address_hash = Hash::from(&address);
+    Response::Sites(chain.get_sites_owned(&address_hash))
+}
@@ -0,0 +1,55 @@
+/*
+ * Copyright (c) 2022, Ben Jilks <benjyjilks@gmail.com>
+ *
+ * SPDX-License-Identifier: BSD-2-Clause
+ */
+
+use crate::network::NetworkConnection;
+use crate::node::packet_handler::NodePacketHandler;
+
+use libhyperchain::service::command::Response;
+use libhyperchain::chain::snapshot::ChainSnapshot;
+use std::path::PathBuf;
+
+pub fn export_snapshot(connection: &mut NetworkConnection<NodePacketHandler>,
+                       path: String)
+    -> Response
+{
+    let mut node = connection.handler().node();
+    let snapshot_or_error = node.chain().snapshot();
+    if snapshot_or_error.is_err() {
+        return Response::Failed;
+    }
+
+    let snapshot = snapshot_or_error.unwrap();
+    if let Err(err) = snapshot.save(&PathBuf::from(&path))
+    {
+        warn!("Unable to save snapshot to {}: {}", path, err);
+        return Response::Failed;
+    }
+
+    info!("Exported snapshot at block {} to {}", snapshot.top.block_id, path);
+    Response::Snapshot(snapshot.top.block_id, snapshot.commitment)
+}
+
+pub fn verify_snapshot(connection: &mut NetworkConnection<NodePacketHandler>,
+                       path: String)
+    -> Response
+{
+    let snapshot_or_error = ChainSnapshot::load(&PathBuf::from(&path));
+    if snapshot_or_error.is_err() {
+        return Response::Failed;
+    }
+
+    let snapshot = snapshot_or_error.unwrap();
+    let mut node = connection.handler().node();
+    match node.chain().verify_snapshot(&snapshot)
+    {
+        Ok(_) => Response::Snapshot(snapshot.top.block_id, snapshot.commitment),
+        Err(err) =>
+        {
+            warn!("Snapshot {} is invalid: {}", path, err);
+            Response::Failed
+        },
+    }
+}
@@ -21,8 +21,10 @@ pub fn statistics(connection: &mut NetworkConnection<NodePacketHandler>)
     // TODO: Handle errors.
     let usage = node.storage_usage().unwrap();
 
-    let total_chunks = usage.len();
-    let total_chunks_stored = usage
+    // Chunks only claimed by nodes we haven't checked aren't known to be
+    // stored anywhere, so are left out of the replication figures
+    let total_chunks = usage.holders.len();
+    let total_chunks_stored = usage.holders
         .iter()
         .fold(0, |acc, (_, count)| acc + count);
 
@@ -33,11 +35,24 @@ pub fn statistics(connection: &mut NetworkConnection<NodePacketHandler>)
             1.0
         };
 
+    let config = node.replication().config;
+    let under_replicated = usage.holders.values().filter(|count| **count < config.target).count();
+    let over_replicated = usage.holders.values().filter(|count| **count > config.target).count();
+    let disk_usage = node.data_store().disk_usage().unwrap_or(0);
+
     Response::Statistics(Statistics
     {
         hash_rate,
-        known_chunks: total_chunks,
+        known_chunks: total_chunks + usage.unchecked_only().count(),
         replication,
+
+        replication_target: config.target,
+        under_replicated,
+        over_replicated,
+        disk_usage,
+        disk_quota: config.disk_quota,
+        replication_decisions: node.replication().decisions(),
+        sync: node.sync_progress(),
     })
 }
 
@@ -29,3 +29,29 @@ pub fn transaction_info(connection: &mut NetworkConnection<NodePacketHandler>,
     Response::TransactionInfo(transaction, block)
 }
 
+
+/// Proof a transaction is in the chain, against the header of its block.
+pub fn transaction_proof(connection: &mut NetworkConnection<NodePacketHandler>,
+                         transaction_id: Vec<u8>) 
+    -> Response
+{
+    let transaction_id_hash = Hash::from(&transaction_id);
+
+    let mut node = connection.handler().node();
+    let block_or_none = match node.light_client()
+    {
+        // NOTE: Only transactions of watched addresses are known
+        Some(light_client) => light_client.block_with_transaction(&transaction_id_hash),
+        None => node.chain().find_transaction_in_chain(&transaction_id_hash).map(|(_, block)| block),
+    };
+    if block_or_none.is_none() {
+        return Response::Failed;
+    }
+
+    let block = block_or_none.unwrap();
+    match block.transaction_proof(&transaction_id_hash)
+    {
+        Ok(Some(proof)) => Response::TransactionProof(block.header, proof),
+        _ => Response::Failed,
+    }
+}
@@ -9,25 +9,46 @@ use crate::network::packet::Packet;
 use crate::node::packet_handler::NodePacketHandler;
 
 use libhyperchain::service::command::Response;
+use libhyperchain::wallet::Wallet;
 use libhyperchain::wallet::private_wallet::PrivateWallet;
 use libhyperchain::transaction::Transaction;
 use libhyperchain::transaction::page::Page;
 use libhyperchain::data_store::data_unit::DataUnit;
 use libhyperchain::data_store::page::CreatePageData;
+use libhyperchain::config::HASH_LEN;
+use libhyperchain::hash::Hash;
 
 fn add_page(connection: &mut NetworkConnection<NodePacketHandler>,
-            from: Vec<u8>, data_unit: &DataUnit)
+            from: Vec<u8>, site: Option<Vec<u8>>, data_unit: &DataUnit)
     -> Option<(Transaction<Page>, Vec<u8>)>
 {
     let from_wallet_or_error = PrivateWallet::deserialize(from);
     if from_wallet_or_error.is_err() {
         return None;
     }
+
+    if matches!(&site, Some(site) if site.len() != HASH_LEN)
+    {
+        warn!("Error in send: Invalid site");
+        return None;
+    }
     
     let mut node = connection.handler().node();
+    let data_store = node.data_store().clone();
     let chain = &mut node.chain();
     let from_wallet = from_wallet_or_error.unwrap();
-    let page_or_error = chain.new_page(&from_wallet, &data_unit, 1.0);
+    let site = 
+        match site
+        {
+            Some(site) => Hash::from(&site),
+            None => from_wallet.get_address(),
+        };
+
+    // Chunks we have from pages already on the chain aren't paid for again
+    let hashes = data_unit.hashes().unwrap_or_default();
+    let reused = data_store.find_chunks(&chain.get_page_chunks(), &hashes);
+    let page_or_error = chain.new_page_for_site(&from_wallet, &site, &data_unit, 
+        |hash| reused.get(hash).cloned(), 1.0);
     if page_or_error.is_err() 
     {
         warn!("Error in send: {}", page_or_error.unwrap_err());
@@ -46,12 +67,11 @@ fn add_page(connection: &mut NetworkConnection<NodePacketHandler>,
     Some((page, page_id.data().to_vec()))
 }
 
-pub fn update_page(connection: &mut NetworkConnection<NodePacketHandler>,
-                   from: Vec<u8>, name: String, data: Vec<u8>)
+pub fn publish_data(connection: &mut NetworkConnection<NodePacketHandler>,
+                    from: Vec<u8>, site: Option<Vec<u8>>, data_unit: DataUnit)
     -> Response
 {
-    let data_unit = DataUnit::CreatePage(CreatePageData::new(name, data));
-    let page_or_none = add_page(connection, from, &data_unit);
+    let page_or_none = add_page(connection, from, site, &data_unit);
     if page_or_none.is_none() {
         return Response::Failed;
     }
@@ -59,8 +79,12 @@ pub fn update_page(connection: &mut NetworkConnection<NodePacketHandler>,
     // TODO: Handle errors.
 
     let (page, page_id) = page_or_none.unwrap();
-    connection.handler().node().data_store().store_data_unit(&data_unit).unwrap();
-    connection.manager().send(Packet::Page(page, data_unit)).unwrap();
+    {
+        let mut node = connection.handler().node();
+        node.data_store().store_data_unit(&data_unit).unwrap();
+        node.replication().pin(&data_unit.hashes().unwrap());
+    }
+    connection.manager().send(Packet::Page(page, data_unit.compressed_chunks().unwrap())).unwrap();
 
     let report = connection.handler().node().our_report().unwrap();
     connection.manager().send(Packet::Report(None, report)).unwrap();
@@ -68,3 +92,10 @@ pub fn update_page(connection: &mut NetworkConnection<NodePacketHandler>,
     Response::Sent(page_id)
 }
 
+pub fn update_page(connection: &mut NetworkConnection<NodePacketHandler>,
+                   from: Vec<u8>, site: Option<Vec<u8>>, name: String, data: Vec<u8>)
+    -> Response
+{
+    let data_unit = DataUnit::CreatePage(CreatePageData::new(name, data));
+    publish_data(connection, from, site, data_unit)
+}